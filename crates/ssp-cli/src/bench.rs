@@ -1,16 +1,27 @@
+use std::collections::HashMap;
 use std::io::{self, BufReader, Read};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+use arrow::array::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use ssp_core::decoders::{self, DecoderRouter, known_mints};
 use ssp_core::parser::{self, AccountHeader};
 
+use crate::pipeline::NUM_DECODED_WRITERS;
+
+/// Decoded batches `run_full_decoded` keeps for `run_decoded_writers`.
+const WRITER_SAMPLE_BATCHES: usize = 512;
+
 /// Benchmark each pipeline stage separately to find the bottleneck.
-pub fn run(reader: impl Read + Send) {
-    let buffered = BufReader::with_capacity(1024 * 1024, reader);
+pub fn run(reader: impl Read + Send, buf_size: usize, window_log: u32) {
+    let buffered = BufReader::with_capacity(buf_size, reader);
 
-    // Stage 1: zstd only — decompress to sink
+    // Stage 1: zstd only — decompress to sink (frame-parallel when the
+    // input has many frames, like the pipeline)
     let start = Instant::now();
-    let mut decoder = zstd::Decoder::new(buffered).expect("zstd init failed");
-    decoder.window_log_max(31).unwrap();
+    let mut decoder =
+        ssp_core::format::open_decoder(buffered, window_log).expect("zstd init failed");
     let bytes = io::copy(&mut decoder, &mut io::sink()).expect("zstd decompress failed");
     let elapsed = start.elapsed().as_secs_f64();
     let gb = bytes as f64 / 1_073_741_824.0;
@@ -22,13 +33,13 @@ pub fn run(reader: impl Read + Send) {
     );
 }
 
-pub fn run_tar(reader: impl Read + Send) {
-    let buffered = BufReader::with_capacity(1024 * 1024, reader);
+pub fn run_tar(reader: impl Read + Send, buf_size: usize, window_log: u32) {
+    let buffered = BufReader::with_capacity(buf_size, reader);
 
     // Stage 2: zstd + tar — iterate entries, read data, no parsing
     let start = Instant::now();
-    let mut decoder = zstd::Decoder::new(buffered).expect("zstd init failed");
-    decoder.window_log_max(31).unwrap();
+    let mut decoder =
+        ssp_core::format::open_decoder(buffered, window_log).expect("zstd init failed");
 
     let mut header = [0u8; parser::TAR_BLOCK];
     let mut skip_buf = [0u8; 32768];
@@ -82,17 +93,21 @@ pub fn run_tar(reader: impl Read + Send) {
     );
 }
 
-pub fn run_full(reader: impl Read + Send) {
-    let buffered = BufReader::with_capacity(1024 * 1024, reader);
-
-    // Stage 3: zstd + tar + parse — full pipeline minus channel/writers
-    let start = Instant::now();
-    let mut decoder = zstd::Decoder::new(buffered).expect("zstd init failed");
-    decoder.window_log_max(31).unwrap();
+/// Read every accounts file in the archive into a buffer and hand it to
+/// `f`. Returns the accounts-file bytes read, as counted by `run_tar`.
+fn for_each_accounts_file(
+    reader: impl Read + Send,
+    buf_size: usize,
+    window_log: u32,
+    mut f: impl FnMut(&[u8]),
+) -> u64 {
+    let buffered = BufReader::with_capacity(buf_size, reader);
+    let mut decoder =
+        ssp_core::format::open_decoder(buffered, window_log).expect("zstd init failed");
 
     let mut header = [0u8; parser::TAR_BLOCK];
     let mut skip_buf = [0u8; 32768];
-    let mut total_accounts: u64 = 0;
+    let mut total_bytes: u64 = 0;
 
     loop {
         match decoder.read_exact(&mut header) {
@@ -111,22 +126,14 @@ pub fn run_full(reader: impl Read + Send) {
         if parser::is_accounts_entry(&header) {
             let mut buf = vec![0u8; size];
             decoder.read_exact(&mut buf).expect("read data failed");
+            total_bytes += size as u64;
 
             let padding = padded - size;
             if padding > 0 {
                 decoder.read_exact(&mut skip_buf[..padding]).unwrap();
             }
 
-            let mut offset = 0;
-            while offset + size_of::<AccountHeader>() <= buf.len() {
-                let h = bytemuck::from_bytes::<AccountHeader>(
-                    &buf[offset..offset + size_of::<AccountHeader>()],
-                );
-                offset += size_of::<AccountHeader>();
-                offset += h.data_len as usize;
-                offset = (offset + 7) & !7;
-                total_accounts += 1;
-            }
+            f(&buf);
         } else {
             let mut remaining = padded;
             while remaining > 0 {
@@ -137,9 +144,166 @@ pub fn run_full(reader: impl Read + Send) {
         }
     }
 
+    total_bytes
+}
+
+/// Walk the account headers in an AppendVec buffer.
+fn for_each_account(buf: &[u8], mut f: impl FnMut(&AccountHeader, &[u8])) {
+    let mut offset = 0;
+    while offset + size_of::<AccountHeader>() <= buf.len() {
+        let h = bytemuck::from_bytes::<AccountHeader>(
+            &buf[offset..offset + size_of::<AccountHeader>()],
+        );
+        offset += size_of::<AccountHeader>();
+        let end = (offset + h.data_len as usize).min(buf.len());
+        f(h, &buf[offset..end]);
+        offset = (offset + h.data_len as usize + 7) & !7;
+    }
+}
+
+fn print_parse_rate(label: &str, elapsed: f64, accounts: u64, bytes: u64) {
+    eprintln!(
+        "{label} {:.1}s — {} accounts parsed, {:.0} accounts/s, {:.0} MB/s decompressed",
+        elapsed,
+        accounts,
+        accounts as f64 / elapsed,
+        (bytes as f64 / 1_048_576.0) / elapsed
+    );
+}
+
+/// Stage 3: zstd + tar + parse — full pipeline minus channel/writers.
+/// Returns the wall time in seconds.
+pub fn run_full(reader: impl Read + Send, buf_size: usize, window_log: u32) -> f64 {
+    let start = Instant::now();
+    let mut total_accounts: u64 = 0;
+    let bytes = for_each_accounts_file(reader, buf_size, window_log, |buf| {
+        for_each_account(buf, |_, _| total_accounts += 1);
+    });
+
+    let elapsed = start.elapsed().as_secs_f64();
+    print_parse_rate("[zstd + tar + parse]", elapsed, total_accounts, bytes);
+    elapsed
+}
+
+/// Stage 4: stage 3 plus every default decoder (token, system, governance,
+/// Pyth, Metaplex), with the decoded batches dropped instead of written. The gap to
+/// `run_full` is the decode overhead. Returns the wall time in seconds and
+/// the first `WRITER_SAMPLE_BATCHES` batches for `run_decoded_writers`.
+pub fn run_full_decoded(
+    reader: impl Read + Send,
+    buf_size: usize,
+    window_log: u32,
+) -> (f64, Vec<(&'static str, RecordBatch)>) {
+    let known_mints = Arc::new(known_mints::load());
+    let mut decoders = decoders::build_default_registry(known_mints, false, false);
+    let router = DecoderRouter::new(&decoders);
+
+    let start = Instant::now();
+    let mut total_accounts: u64 = 0;
+    let mut decoded_rows: u64 = 0;
+    let mut errors: u64 = 0;
+    let mut sample = Vec::new();
+    let mut keep = |name, batch: RecordBatch| {
+        decoded_rows += batch.num_rows() as u64;
+        if sample.len() < WRITER_SAMPLE_BATCHES {
+            sample.push((name, batch));
+        }
+    };
+    let bytes = for_each_accounts_file(reader, buf_size, window_log, |buf| {
+        for_each_account(buf, |h, data| {
+            total_accounts += 1;
+            if let Some(idx) = router.route(&decoders, &h.owner, h.data_len) {
+                match decoders[idx].decode(h.pubkey, h.write_version, data, false) {
+                    Ok(Some(batch)) => keep(decoders[idx].batch_name(&batch), batch),
+                    Ok(None) => {}
+                    Err(_) => errors += 1,
+                }
+            }
+        });
+    });
+    for dec in &mut decoders {
+        while let Some(batch) = dec.flush() {
+            keep(dec.batch_name(&batch), batch);
+        }
+    }
+
     let elapsed = start.elapsed().as_secs_f64();
+    print_parse_rate(
+        "[zstd + tar + parse + decode]",
+        elapsed,
+        total_accounts,
+        bytes,
+    );
+    eprintln!("  {decoded_rows} rows decoded, {errors} decode errors");
+    (elapsed, sample)
+}
+
+/// Stage 5: `batches` encoded by `NUM_DECODED_WRITERS` threads, first with a
+/// writer per thread and decoder (the default), then through one shared
+/// `Mutex` of writers (`--merge-decoded-output`). Output goes to `io::sink()`.
+pub fn run_decoded_writers(batches: Vec<(&'static str, RecordBatch)>) {
+    let rows: usize = batches.iter().map(|(_, b)| b.num_rows()).sum();
+    let batches = Arc::new(batches);
+    let separate = time_decoded_writers(&batches, false);
+    let merged = time_decoded_writers(&batches, true);
     eprintln!(
-        "[zstd + tar + parse] {:.1}s — {} accounts parsed",
-        elapsed, total_accounts
+        "[per-thread writers] {:.2}s — {:.0} rows/s",
+        separate,
+        rows as f64 / separate
     );
+    eprintln!(
+        "[shared writers]     {:.2}s — {:.0} rows/s",
+        merged,
+        rows as f64 / merged
+    );
+    eprintln!(
+        "  mutex overhead: {:+.2}s ({:+.0}%) over {} batches",
+        merged - separate,
+        (merged - separate) / separate * 100.0,
+        batches.len()
+    );
+}
+
+type SinkWriters = HashMap<&'static str, ArrowWriter<io::Sink>>;
+
+fn time_decoded_writers(batches: &Arc<Vec<(&'static str, RecordBatch)>>, shared: bool) -> f64 {
+    let merged: Arc<Mutex<SinkWriters>> = Arc::default();
+    let start = Instant::now();
+    let threads: Vec<_> = (0..NUM_DECODED_WRITERS)
+        .map(|i| {
+            let batches = batches.clone();
+            let writers = if shared {
+                merged.clone()
+            } else {
+                Arc::default()
+            };
+            std::thread::spawn(move || {
+                // Dealt round-robin, as the decoded channel roughly does.
+                for (name, batch) in batches.iter().skip(i).step_by(NUM_DECODED_WRITERS) {
+                    let mut writers = writers.lock().unwrap();
+                    let writer = writers.entry(name).or_insert_with(|| {
+                        ArrowWriter::try_new(io::sink(), batch.schema(), None)
+                            .expect("parquet writer init failed")
+                    });
+                    writer.write(batch).expect("parquet write failed");
+                }
+                if let Some(writers) = Arc::into_inner(writers) {
+                    close_sink_writers(writers);
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().expect("bench writer panicked");
+    }
+    if let Some(writers) = Arc::into_inner(merged) {
+        close_sink_writers(writers);
+    }
+    start.elapsed().as_secs_f64()
+}
+
+fn close_sink_writers(writers: Mutex<SinkWriters>) {
+    for (_, writer) in writers.into_inner().unwrap() {
+        writer.close().expect("parquet close failed");
+    }
 }
@@ -1,4 +1,62 @@
 use duckdb::Connection;
+use duckdb::types::Value;
+use ssp_core::Pubkey;
+use ssp_core::filters::ResolvedFilters;
+
+use crate::pipeline::PARTITION_DIR;
+
+pub struct DiffCounts {
+    pub added: i64,
+    pub removed: i64,
+    pub modified: i64,
+}
+
+/// One bucket of `DuckDB::rent_epoch_histogram`.
+pub struct RentEpochBucket {
+    /// First epoch in the bucket; `None` for the rent-exempt `u64::MAX`.
+    pub start: Option<u64>,
+    pub count: i64,
+}
+
+/// Result of `DuckDB::token_states`.
+pub struct TokenStates {
+    /// `(state, state_str, accounts)`, by state.
+    pub counts: Vec<(u8, String, i64)>,
+    /// Mints with the most frozen accounts, most first.
+    pub top_frozen_mints: Vec<(Pubkey, i64)>,
+}
+
+/// Views `DuckDB::create_views` defines, and the parquet glob each reads.
+const VIEWS: &[(&str, &str)] = &[
+    // matches both accounts_{i}.parquet and --single-file's accounts.parquet
+    ("accounts", "accounts*.parquet"),
+    ("mints", "mints_*.parquet"),
+    ("nft_mints", "nft_mints_*.parquet"),
+    // [0-9] keeps token_accounts_normalized.parquet out of the glob
+    ("token_accounts", "token_accounts_[0-9]*.parquet"),
+    ("mint_aggregates", "mint_aggregates_*.parquet"),
+    ("token_2022_mints", "token_2022_mints_*.parquet"),
+    ("multisigs", "multisigs_*.parquet"),
+    ("nonce_accounts", "nonce_accounts_*.parquet"),
+    ("pyth_prices", "pyth_prices_*.parquet"),
+    ("metaplex_metadata", "metaplex_metadata_*.parquet"),
+    ("config_accounts", "config_accounts_*.parquet"),
+    ("governance_realms", "governance_realms_*.parquet"),
+    ("governance_governances", "governance_governances_*.parquet"),
+    ("governance_proposals", "governance_proposals_*.parquet"),
+    (
+        "governance_vote_records",
+        "governance_vote_records_*.parquet",
+    ),
+    (
+        "governance_token_owner_records",
+        "governance_token_owner_records_*.parquet",
+    ),
+];
+
+/// `--partition-by owner`'s accounts, under `pipeline::PARTITION_DIR`. The
+/// `accounts` view falls back to these when there are no top-level files.
+const PARTITIONED_ACCOUNTS: &str = "*/accounts_*.parquet";
 
 pub struct DuckDB {
     connection: Connection,
@@ -14,42 +72,367 @@ impl DuckDB {
         })
     }
 
-    pub fn register_views_tui(&mut self) -> Result<Vec<(String, i64)>, anyhow::Error> {
-        let candidates = [
-            ("accounts", "accounts_*.parquet"),
-            ("mints", "mints_*.parquet"),
-            ("token_accounts", "token_accounts_*.parquet"),
-        ];
+    /// Define a view per output found in `output_dir`, named after it:
+    /// `accounts`, `mints`, `token_accounts` and the other decoder outputs,
+    /// plus `token_balances` when both `token_accounts` and `mints` exist.
+    /// Views already defined are replaced, so this can be re-run after more
+    /// output lands. Queries can then say `FROM accounts` instead of
+    /// repeating the parquet globs.
+    ///
+    /// DuckDB can't bind parameters in a view definition, so the globs are
+    /// spliced in as quoted string literals.
+    pub fn create_views(&mut self, output_dir: &str) -> anyhow::Result<()> {
+        let dir = std::path::Path::new(output_dir);
+        for &(name, glob) in VIEWS {
+            // --merge-decoded-output's single {name}.parquet
+            let merged = format!("{name}.parquet");
+            let glob = if name != "accounts" && dir.join(&merged).exists() {
+                merged.as_str()
+            } else {
+                glob
+            };
+            let path = if has_match(dir, glob) {
+                dir.join(glob)
+            } else if name == "accounts" && dir.join(PARTITION_DIR).is_dir() {
+                dir.join(PARTITION_DIR).join(PARTITIONED_ACCOUNTS)
+            } else {
+                continue;
+            };
+            self.connection.execute_batch(&format!(
+                "CREATE OR REPLACE VIEW {name} AS SELECT * FROM {}",
+                sql_string(&path.to_string_lossy())
+            ))?;
+            self.add_table(name);
+        }
 
+        if self.tables.iter().any(|t| t == "token_accounts")
+            && self.tables.iter().any(|t| t == "mints")
+        {
+            self.connection.execute_batch(&format!(
+                "CREATE OR REPLACE VIEW token_balances AS {}",
+                token_balances_sql("token_accounts", "mints")
+            ))?;
+            self.add_table("token_balances");
+        }
+        Ok(())
+    }
+
+    /// `create_views` over the working directory, with each view's row count.
+    pub fn register_views_tui(&mut self) -> Result<Vec<(String, i64)>, anyhow::Error> {
+        self.create_views(".")?;
         let mut result = Vec::new();
-        for (name, glob) in &candidates {
-            if parquet_exists(name) {
+        for name in &self.tables {
+            let count: i64 = self
+                .connection
+                .prepare(&format!("SELECT COUNT(*) FROM {name}"))?
+                .query_row([], |row| row.get(0))?;
+            result.push((name.clone(), count));
+        }
+        Ok(result)
+    }
+
+    fn add_table(&mut self, name: &str) {
+        if !self.tables.iter().any(|t| t == name) {
+            self.tables.push(name.to_string());
+        }
+    }
+
+    /// Create a `<name>_latest` view over `glob` that keeps one row per `key_col`,
+    /// picking the row with the highest `order_col` (e.g. `write_version`).
+    /// Needed when the same account shows up in several merged snapshots.
+    pub fn dedup_decoded(
+        &mut self,
+        glob: &str,
+        key_col: &str,
+        order_col: &str,
+    ) -> Result<String, anyhow::Error> {
+        let stem = std::path::Path::new(glob)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or(glob)
+            .split(['*', '.'])
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('_');
+        let name = format!("{stem}_latest");
+
+        self.connection.execute_batch(&format!(
+            "CREATE OR REPLACE VIEW {name} AS \
+             SELECT * FROM '{glob}' \
+             QUALIFY row_number() OVER (PARTITION BY {key_col} ORDER BY {order_col} DESC) = 1"
+        ))?;
+        if !self.tables.contains(&name) {
+            self.tables.push(name.clone());
+        }
+        Ok(name)
+    }
+
+    /// Join token accounts with their mints and write `output` with two extra
+    /// columns: the mint's `decimals` and `amount_normalized = amount / 10^decimals`.
+    /// Accounts whose mint wasn't decoded keep NULLs in both. The same rows
+    /// as the `token_balances` view.
+    pub fn normalize_token_amounts(
+        &self,
+        ta_glob: &str,
+        mint_glob: &str,
+        output: &str,
+    ) -> anyhow::Result<()> {
+        self.connection.execute_batch(&format!(
+            "COPY ({}) TO '{output}' (FORMAT PARQUET)",
+            token_balances_sql(&format!("'{ta_glob}'"), &format!("'{mint_glob}'"))
+        ))?;
+        Ok(())
+    }
+
+    /// Write `output` with only the highest-`write_version` entry per pubkey from
+    /// `parquet_glob` (which must have a `write_version` column), then only
+    /// the latest entries `filters` keeps. `parquet_glob` must hold every
+    /// version, dead ones and other owners' included: filtering first would
+    /// let an older live version stand in for a later close or owner change.
+    /// Returns the number of rows kept.
+    pub fn deduplicate(
+        &self,
+        parquet_glob: &str,
+        output: &str,
+        filters: &ResolvedFilters,
+    ) -> anyhow::Result<usize> {
+        let (where_clause, params) = account_filter_sql(filters);
+        let kept = self.connection.execute(
+            &format!(
+                "COPY (
+                     SELECT * FROM (
+                         SELECT * FROM '{parquet_glob}'
+                         QUALIFY row_number() OVER (PARTITION BY pubkey ORDER BY write_version DESC) = 1
+                     ) {where_clause}
+                 ) TO '{output}' (FORMAT PARQUET)"
+            ),
+            duckdb::params_from_iter(params),
+        )?;
+        Ok(kept)
+    }
+
+    /// Write `output` as the state after applying an incremental run to a
+    /// base run: the rows of `inc_glob` that are the latest version of their
+    /// pubkey in `touched_glob` (the incremental's accounts output with
+    /// `write_version`, dead accounts and every owner included), plus the rows
+    /// of `base_glob` whose pubkey isn't touched. `inc_glob` is `None` when
+    /// the incremental produced nothing for this table. `filters`, given for
+    /// `accounts`, then drops the latest versions the run's filters reject,
+    /// such as the zero-lamport rows that only mark deletions. Returns rows
+    /// written.
+    pub fn merge_incremental(
+        &self,
+        base_glob: &str,
+        inc_glob: Option<&str>,
+        touched_glob: &str,
+        filters: Option<&ResolvedFilters>,
+        output: &str,
+    ) -> anyhow::Result<usize> {
+        let (where_clause, params) = filters.map(account_filter_sql).unwrap_or_default();
+        // A decoded row joins only if it was decoded from the account's last
+        // write, so a closed mint doesn't survive as an earlier version.
+        let inc = match inc_glob {
+            Some(glob) => format!(
+                "SELECT * FROM (
+                     SELECT inc.* FROM '{glob}' inc
+                     JOIN latest USING (pubkey, write_version)
+                 ) {where_clause}
+                 UNION ALL BY NAME"
+            ),
+            None => String::new(),
+        };
+        let rows = self.connection.execute(
+            &format!(
+                "COPY (
+                     WITH latest AS (
+                         SELECT pubkey, write_version FROM '{touched_glob}'
+                         QUALIFY row_number() OVER (PARTITION BY pubkey ORDER BY write_version DESC) = 1
+                     )
+                     {inc}
+                     SELECT * FROM '{base_glob}'
+                     WHERE pubkey NOT IN (SELECT pubkey FROM latest)
+                 ) TO '{output}' (FORMAT PARQUET)"
+            ),
+            duckdb::params_from_iter(params),
+        )?;
+        Ok(rows)
+    }
+
+    /// Diff the `accounts*.parquet` output of two runs by pubkey.
+    ///
+    /// - added: in B (alive) but not in A
+    /// - removed: in A, zero lamports in B (B must be parsed with `--include-dead`)
+    /// - modified: in both, lamports/owner/data_len differ
+    ///
+    /// With `output_dir`, each set is also written to `<output_dir>/<change>.parquet`.
+    pub fn compare(
+        &mut self,
+        dir_a: &str,
+        dir_b: &str,
+        output_dir: Option<&str>,
+    ) -> Result<DiffCounts, anyhow::Error> {
+        self.connection.execute_batch(&format!(
+            "CREATE OR REPLACE VIEW accounts_a AS SELECT * FROM '{dir_a}/accounts*.parquet';
+             CREATE OR REPLACE VIEW accounts_b AS SELECT * FROM '{dir_b}/accounts*.parquet';
+             CREATE OR REPLACE VIEW accounts_diff AS
+             SELECT
+                 coalesce(a.pubkey, b.pubkey) AS pubkey,
+                 CASE
+                     WHEN a.pubkey IS NULL AND b.lamports > 0 THEN 'added'
+                     WHEN a.pubkey IS NOT NULL AND b.lamports = 0 THEN 'removed'
+                     WHEN a.lamports <> b.lamports
+                       OR a.owner <> b.owner
+                       OR a.data_len <> b.data_len THEN 'modified'
+                 END AS change,
+                 a.lamports AS lamports_a, b.lamports AS lamports_b,
+                 a.owner AS owner_a, b.owner AS owner_b,
+                 a.data_len AS data_len_a, b.data_len AS data_len_b
+             FROM accounts_a a
+             FULL OUTER JOIN accounts_b b ON a.pubkey = b.pubkey;"
+        ))?;
+        for name in ["accounts_a", "accounts_b", "accounts_diff"] {
+            if !self.tables.iter().any(|t| t == name) {
+                self.tables.push(name.to_string());
+            }
+        }
+
+        let mut counts = DiffCounts {
+            added: 0,
+            removed: 0,
+            modified: 0,
+        };
+        let mut stmt = self.connection.prepare(
+            "SELECT change, COUNT(*) FROM accounts_diff WHERE change IS NOT NULL GROUP BY change",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let change: String = row.get(0)?;
+            let count: i64 = row.get(1)?;
+            match change.as_str() {
+                "added" => counts.added = count,
+                "removed" => counts.removed = count,
+                "modified" => counts.modified = count,
+                _ => {}
+            }
+        }
+
+        if let Some(out) = output_dir {
+            std::fs::create_dir_all(out)?;
+            for change in ["added", "removed", "modified"] {
                 self.connection.execute_batch(&format!(
-                    "CREATE VIEW {name} AS SELECT * FROM '{glob}'"
+                    "COPY (SELECT * FROM accounts_diff WHERE change = '{change}') \
+                     TO '{out}/{change}.parquet' (FORMAT PARQUET)"
                 ))?;
-                let count: i64 = self
-                    .connection
-                    .prepare(&format!("SELECT COUNT(*) FROM {name}"))?
-                    .query_row([], |row| row.get(0))?;
-                self.tables.push(name.to_string());
-                result.push((name.to_string(), count));
             }
         }
-        Ok(result)
+
+        Ok(counts)
+    }
+
+    /// Top `limit` accounts by lamports in the `accounts` view
+    /// (`create_views`), restricted to what `filters` kept. Owners (included
+    /// or excluded) and pubkey are bound as blob parameters, never spliced
+    /// into the SQL. Filters with no column in the accounts output (hash,
+    /// sampling, data prefix) were already applied while parsing.
+    pub fn query_top_accounts(
+        &self,
+        filters: &ResolvedFilters,
+        limit: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<String>>), anyhow::Error> {
+        let (where_clause, params) = account_filter_sql(filters);
+        self.query_to_vecs(
+            &format!(
+                "SELECT pubkey, owner, lamports, data_len FROM accounts {where_clause} \
+                 ORDER BY lamports DESC LIMIT {limit}"
+            ),
+            duckdb::params_from_iter(params),
+        )
+    }
+
+    /// The `accounts` view counted by `rent_epoch` in buckets of `width`
+    /// epochs, lowest first. Rent-exempt accounts store `u64::MAX` instead of
+    /// an epoch; they come first, as their own bucket.
+    pub fn rent_epoch_histogram(&self, width: u64) -> anyhow::Result<Vec<RentEpochBucket>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT CASE WHEN rent_epoch = {max} THEN NULL
+                         ELSE rent_epoch // {width} * {width} END AS start,
+                    COUNT(*)
+             FROM accounts
+             GROUP BY start
+             ORDER BY start NULLS FIRST",
+            max = u64::MAX,
+            width = width.max(1),
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut buckets = Vec::new();
+        while let Some(row) = rows.next()? {
+            buckets.push(RentEpochBucket {
+                start: row.get(0)?,
+                count: row.get(1)?,
+            });
+        }
+        Ok(buckets)
+    }
+
+    /// The `token_accounts` view counted by `state` (uninitialized,
+    /// initialized, frozen), plus the `limit` mints with the most frozen
+    /// accounts. A frozen account is one its mint's freeze authority locked.
+    pub fn token_states(&self, limit: usize) -> anyhow::Result<TokenStates> {
+        let mut stmt = self.connection.prepare(
+            "SELECT state, any_value(state_str), COUNT(*)
+             FROM token_accounts
+             GROUP BY state
+             ORDER BY state",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut counts = Vec::new();
+        while let Some(row) = rows.next()? {
+            counts.push((row.get(0)?, row.get(1)?, row.get(2)?));
+        }
+
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT mint, COUNT(*) AS frozen
+             FROM token_accounts
+             WHERE state = 2
+             GROUP BY mint
+             ORDER BY frozen DESC, mint
+             LIMIT {limit}"
+        ))?;
+        let mut rows = stmt.query([])?;
+        let mut top_frozen_mints = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mint: Vec<u8> = row.get(0)?;
+            let mint: [u8; 32] = mint
+                .try_into()
+                .map_err(|m: Vec<u8>| anyhow::anyhow!("{}-byte mint in token_accounts", m.len()))?;
+            top_frozen_mints.push((Pubkey::from(mint), row.get(1)?));
+        }
+        Ok(TokenStates {
+            counts,
+            top_frozen_mints,
+        })
     }
 
     pub fn execute_to_vecs(
         &self,
         sql: &str,
+    ) -> Result<(Vec<String>, Vec<Vec<String>>), anyhow::Error> {
+        self.query_to_vecs(sql, [])
+    }
+
+    fn query_to_vecs(
+        &self,
+        sql: &str,
+        params: impl duckdb::Params,
     ) -> Result<(Vec<String>, Vec<Vec<String>>), anyhow::Error> {
         let mut stmt = self.connection.prepare(sql)?;
-        let mut rows = stmt.query([])?;
+        let mut rows = stmt.query(params)?;
 
-        let mut data: Vec<Vec<duckdb::types::Value>> = Vec::new();
+        let mut data: Vec<Vec<Value>> = Vec::new();
         while let Some(row) = rows.next()? {
             let mut cols = Vec::new();
             let mut i = 0;
-            while let Ok(val) = row.get::<_, duckdb::types::Value>(i) {
+            while let Ok(val) = row.get::<_, Value>(i) {
                 cols.push(val);
                 i += 1;
             }
@@ -70,6 +453,61 @@ impl DuckDB {
 
         Ok((names, formatted))
     }
+
+    /// Run `sql` and print the result as an aligned table to stdout.
+    pub fn print_query(&self, sql: &str) -> Result<(), anyhow::Error> {
+        let (names, rows) = self.execute_to_vecs(sql)?;
+        print!("{}", render_table(&names, &rows));
+        Ok(())
+    }
+}
+
+/// Shared console formatting for query results: binary columns arrive here
+/// already base58-encoded (see `format_value`); numeric columns such as
+/// lamports are right-aligned, everything else left-aligned.
+pub fn render_table(names: &[String], rows: &[Vec<String>]) -> String {
+    use std::fmt::Write as _;
+
+    let widths: Vec<usize> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            rows.iter()
+                .filter_map(|r| r.get(i))
+                .map(|v| v.chars().count())
+                .fold(name.len(), usize::max)
+        })
+        .collect();
+    let numeric: Vec<bool> = (0..names.len())
+        .map(|i| {
+            rows.iter()
+                .filter_map(|r| r.get(i))
+                .all(|v| v == "NULL" || v.parse::<f64>().is_ok())
+        })
+        .collect();
+
+    let mut out = String::new();
+    for (i, name) in names.iter().enumerate() {
+        let w = widths[i];
+        let _ = if numeric[i] {
+            write!(out, "{name:>w$}  ")
+        } else {
+            write!(out, "{name:<w$}  ")
+        };
+    }
+    out.push('\n');
+    for row in rows {
+        for (i, val) in row.iter().enumerate() {
+            let w = widths[i];
+            let _ = if numeric[i] {
+                write!(out, "{val:>w$}  ")
+            } else {
+                write!(out, "{val:<w$}  ")
+            };
+        }
+        out.push('\n');
+    }
+    out
 }
 
 fn format_value(val: &duckdb::types::Value) -> String {
@@ -93,6 +531,76 @@ fn format_value(val: &duckdb::types::Value) -> String {
     }
 }
 
-fn parquet_exists(prefix: &str) -> bool {
-    (0..4).any(|i| std::path::Path::new(&format!("{prefix}_{i}.parquet")).exists())
+/// Whether any file in `dir` matches `glob`, one of the `VIEWS` patterns: a
+/// literal, or a prefix ending in `*` or `[0-9]*` followed by a suffix.
+fn has_match(dir: &std::path::Path, glob: &str) -> bool {
+    let Some((head, tail)) = glob.split_once('*') else {
+        return dir.join(glob).exists();
+    };
+    let (prefix, digit) = match head.strip_suffix("[0-9]") {
+        Some(prefix) => (prefix, true),
+        None => (head, false),
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        name.strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(tail))
+            .is_some_and(|mid| !digit || mid.starts_with(|c: char| c.is_ascii_digit()))
+    })
+}
+
+/// A `WHERE` clause (empty if nothing filters) and its parameters for the
+/// `filters` that map onto `accounts` columns: dead accounts, owner and
+/// pubkey.
+fn account_filter_sql(filters: &ResolvedFilters) -> (String, Vec<Value>) {
+    let mut clauses = Vec::new();
+    let mut params = Vec::new();
+    if !filters.include_dead {
+        clauses.push("lamports > 0".to_string());
+    }
+    if let Some(owner) = &filters.owner {
+        clauses.push("owner = ?".to_string());
+        params.push(Value::Blob(owner.as_bytes().to_vec()));
+    } else if !filters.exclude_owners.is_empty() {
+        let marks = vec!["?"; filters.exclude_owners.len()].join(", ");
+        clauses.push(format!("owner NOT IN ({marks})"));
+        params.extend(
+            filters
+                .exclude_owners
+                .iter()
+                .map(|o| Value::Blob(o.as_bytes().to_vec())),
+        );
+    }
+    if let Some(pubkey) = &filters.pubkey {
+        clauses.push("pubkey = ?".to_string());
+        params.push(Value::Blob(pubkey.as_bytes().to_vec()));
+    }
+    let where_clause = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    (where_clause, params)
+}
+
+/// `s` as an SQL string literal.
+fn sql_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Token accounts LEFT JOINed to their mint's `decimals`, with
+/// `amount_normalized = amount / 10^decimals` as a DOUBLE. `token_accounts`
+/// and `mints` are table names or quoted parquet globs; orphan accounts keep
+/// their row with NULLs.
+fn token_balances_sql(token_accounts: &str, mints: &str) -> String {
+    format!(
+        "SELECT ta.*, m.decimals,
+                ta.amount / pow(10, m.decimals) AS amount_normalized
+         FROM {token_accounts} ta
+         LEFT JOIN (SELECT pubkey, decimals FROM {mints}) m ON ta.mint = m.pubkey"
+    )
 }
@@ -0,0 +1,156 @@
+//! Snapshot download stream that survives dropped connections.
+//!
+//! A read error mid-body re-issues the request with `Range: bytes=<offset>-`
+//! and carries on from where the last one stopped, so the decompressor
+//! downstream never notices. `Sha256Reader` checks the bytes end to end
+//! against a published digest (`--expect-sha256`).
+
+use std::io::{self, Read};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::RANGE;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+pub struct RetryingReader {
+    client: Client,
+    url: String,
+    resp: Response,
+    /// Body bytes handed out so far; the next `Range` starts here.
+    offset: u64,
+    max_retries: u32,
+    /// Consecutive failed reconnects, reset once bytes flow again.
+    retries: u32,
+}
+
+impl RetryingReader {
+    pub fn open(client: Client, url: &str, max_retries: u32) -> anyhow::Result<Self> {
+        let resp = client.get(url).send()?.error_for_status()?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            resp,
+            offset: 0,
+            max_retries,
+            retries: 0,
+        })
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.resp.content_length()
+    }
+
+    fn reconnect(&mut self) -> anyhow::Result<()> {
+        let resp = self
+            .client
+            .get(&self.url)
+            .header(RANGE, format!("bytes={}-", self.offset))
+            .send()?
+            .error_for_status()?;
+        // A 200 would restart the body at byte 0 and corrupt the stream.
+        if resp.status() != StatusCode::PARTIAL_CONTENT {
+            anyhow::bail!("server ignored the range request ({})", resp.status());
+        }
+        self.resp = resp;
+        Ok(())
+    }
+}
+
+impl Read for RetryingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let err = match self.resp.read(buf) {
+                Ok(n) => {
+                    if n > 0 {
+                        self.retries = 0;
+                    }
+                    self.offset += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => e,
+            };
+
+            // Keep trying until a reconnect sticks or the budget runs out.
+            loop {
+                if self.retries >= self.max_retries {
+                    return Err(err);
+                }
+                self.retries += 1;
+                // Saturating: a large --max-retries would overflow the doubling.
+                let backoff = RETRY_BACKOFF
+                    .saturating_mul(2u32.saturating_pow(self.retries - 1))
+                    .min(MAX_BACKOFF);
+                warn!(
+                    "download interrupted at byte {} ({err}), retry {}/{} in {}s",
+                    self.offset,
+                    self.retries,
+                    self.max_retries,
+                    backoff.as_secs()
+                );
+                std::thread::sleep(backoff);
+                match self.reconnect() {
+                    Ok(()) => break,
+                    Err(e) => warn!("reconnect failed: {e}"),
+                }
+            }
+        }
+    }
+}
+
+/// Hashes every byte read through it. The read that hits EOF fails with
+/// `InvalidData` if the SHA-256 of the whole stream isn't `expected`, so
+/// the reader must be read to the end for the check to happen.
+pub struct Sha256Reader<R> {
+    inner: R,
+    /// `None` once EOF has been reached and the digest checked.
+    hasher: Option<Sha256>,
+    expected: [u8; 32],
+}
+
+impl<R: Read> Sha256Reader<R> {
+    pub fn new(inner: R, expected: [u8; 32]) -> Self {
+        Self {
+            inner,
+            hasher: Some(Sha256::new()),
+            expected,
+        }
+    }
+}
+
+impl<R: Read> Read for Sha256Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 || buf.is_empty() {
+            if let Some(hasher) = &mut self.hasher {
+                hasher.update(&buf[..n]);
+            }
+            return Ok(n);
+        }
+        if let Some(hasher) = self.hasher.take() {
+            let digest: [u8; 32] = hasher.finalize().into();
+            if digest != self.expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "sha256 mismatch: expected {}, got {}",
+                        hex(&self.expected),
+                        hex(&digest)
+                    ),
+                ));
+            }
+            info!("sha256 verified: {}", hex(&digest));
+        }
+        Ok(0)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
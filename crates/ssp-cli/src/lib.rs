@@ -0,0 +1,9 @@
+//! The `ssp` pipeline as a library, for parsing a snapshot from another
+//! program: `pipeline::run` writes files like the CLI, `pipeline::collect`
+//! returns the record batches instead.
+
+pub mod mem;
+pub mod pipeline;
+pub mod region;
+pub mod rpc;
+pub mod writer;
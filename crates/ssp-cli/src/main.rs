@@ -1,49 +1,212 @@
 use clap::Parser;
-use std::io::{self, Read, Write};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, IsTerminal, Read, Write};
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
-use std::thread::JoinHandle;
-use std::time::{Duration, Instant};
+use std::time::Instant;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::filter::LevelFilter;
 
 use ssp_core::Pubkey;
 use ssp_core::filters::ResolvedFilters;
+use ssp_core::parser::{DEFAULT_BUF_SIZE, SIZE_BUCKET_LABELS, SizeHistogram};
+
+use ssp_cli::{mem, pipeline, region, rpc, writer};
 
 mod bench;
+#[cfg(feature = "duckdb")]
 #[allow(dead_code)]
 mod db;
-mod pipeline;
-mod rpc;
+mod download;
+mod manifest;
+mod tune;
+mod watch;
 
 #[derive(clap::Args, Debug, Clone)]
 pub struct Filters {
     #[arg(long)]
     pub owner: Option<String>,
 
+    #[arg(long)]
+    pub exclude_owner: Vec<String>,
+
     #[arg(long)]
     pub hash: Option<String>,
 
+    #[arg(long, value_parser = parse_hash_prefix)]
+    pub hash_prefix: Option<Vec<u8>>,
+
     #[arg(long)]
     pub pubkey: Option<String>,
 
+    #[arg(long)]
+    pub min_write_version: Option<u64>,
+
+    #[arg(long)]
+    pub max_write_version: Option<u64>,
+
+    #[arg(long, conflicts_with = "min_write_version")]
+    pub write_version_after: Option<u64>,
+
+    #[arg(long, conflicts_with = "max_write_version")]
+    pub write_version_before: Option<u64>,
+
     #[arg(long, default_value = "false")]
     pub include_dead: bool,
 
     #[arg(long, default_value = "false")]
     pub include_spam: bool,
+
+    #[arg(long, value_parser = parse_sample)]
+    pub sample: Option<(u64, u64)>,
+
+    #[arg(long, value_parser = parse_hex)]
+    pub data_prefix: Option<Vec<u8>>,
 }
 
 impl Filters {
     pub fn resolve(&self) -> Result<ResolvedFilters, anyhow::Error> {
+        // The exclusive --write-version-after/-before as inclusive bounds.
+        let min_write_version = match self.write_version_after {
+            Some(v) => Some(v.checked_add(1).ok_or_else(|| {
+                anyhow::anyhow!("--write-version-after {v} leaves nothing to keep")
+            })?),
+            None => self.min_write_version,
+        };
+        let max_write_version = match self.write_version_before {
+            Some(v) => Some(v.checked_sub(1).ok_or_else(|| {
+                anyhow::anyhow!("--write-version-before {v} leaves nothing to keep")
+            })?),
+            None => self.max_write_version,
+        };
         Ok(ResolvedFilters {
-            owner: Pubkey::try_from_b58(self.owner.as_deref())?,
+            owner: self.owner.as_deref().map(resolve_program).transpose()?,
+            exclude_owners: self
+                .exclude_owner
+                .iter()
+                .map(|s| resolve_program(s))
+                .collect::<Result<_, _>>()?,
             hash: decode_b58_32(&self.hash)?,
+            hash_prefix: self.hash_prefix.clone(),
             pubkey: Pubkey::try_from_b58(self.pubkey.as_deref())?,
+            min_write_version,
+            max_write_version,
             include_dead: self.include_dead,
             include_spam: self.include_spam,
+            sample: self.sample,
+            data_prefix: self.data_prefix.clone(),
         })
     }
 }
 
+/// Well-known programs by alias, for `--owner`/`--exclude-owner` and for
+/// labelling owners in the console summaries.
+const PROGRAM_ALIASES: &[(&str, Pubkey)] = &[
+    ("system", Pubkey::SYSTEM_PROGRAM),
+    ("token", Pubkey::TOKEN_PROGRAM),
+    ("token2022", Pubkey::TOKEN_2022_PROGRAM),
+    ("stake", Pubkey::STAKE_PROGRAM),
+    ("vote", Pubkey::VOTE_PROGRAM),
+    ("metaplex", Pubkey::METAPLEX_METADATA),
+    ("bpf-upgradeable", Pubkey::BPF_UPGRADEABLE_LOADER),
+];
+
+/// Map a well-known program alias (`token`, `stake`, ...) to its ID,
+/// falling back to base58 for anything else.
+fn resolve_program(s: &str) -> Result<Pubkey, anyhow::Error> {
+    match PROGRAM_ALIASES.iter().find(|(alias, _)| *alias == s) {
+        Some(&(_, pk)) => Ok(pk),
+        None => Pubkey::from_b58(s),
+    }
+}
+
+/// Widest `owner_label`: a 44-character pubkey plus ` (bpf-upgradeable)`.
+const OWNER_LABEL_WIDTH: usize = 62;
+
+/// An owner as the summaries print it: base58, followed by the program's
+/// alias in parentheses when it has one.
+fn owner_label(owner: &Pubkey) -> String {
+    match PROGRAM_ALIASES.iter().find(|(_, pk)| pk == owner) {
+        Some((alias, _)) => format!("{owner} ({alias})"),
+        None => owner.to_string(),
+    }
+}
+
+/// Parse `--sample N/D` (e.g. `1/100`).
+fn parse_sample(s: &str) -> Result<(u64, u64), String> {
+    let (num, den) = s.split_once('/').ok_or("expected N/D, e.g. 1/100")?;
+    let num: u64 = num.trim().parse().map_err(|e| format!("{e}"))?;
+    let den: u64 = den.trim().parse().map_err(|e| format!("{e}"))?;
+    if den == 0 || num > den {
+        return Err("need 0 <= N <= D and D > 0".into());
+    }
+    Ok((num, den))
+}
+
+/// Parse `--data-prefix` hex (e.g. `f8c69e91e17587c8`, optional `0x`).
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if s.is_empty() || !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err("expected a non-empty, even-length hex string".into());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| format!("{e}")))
+        .collect()
+}
+
+/// Parse `--hash-prefix` hex, at most a whole 32-byte hash.
+fn parse_hash_prefix(s: &str) -> Result<Vec<u8>, String> {
+    let prefix = parse_hex(s)?;
+    if prefix.len() > 32 {
+        let len = prefix.len();
+        return Err(format!("{len} bytes, longer than a 32-byte hash"));
+    }
+    Ok(prefix)
+}
+
+/// Parse `--expect-sha256`: a 64-digit hex digest.
+fn parse_sha256(s: &str) -> Result<[u8; 32], String> {
+    parse_hex(s)?
+        .try_into()
+        .map_err(|d: Vec<u8>| format!("{} bytes, expected a 32-byte SHA-256 digest", d.len()))
+}
+
+/// Parse a `--timeout-*-secs` value (fractional seconds, > 0).
+fn parse_secs(s: &str) -> Result<std::time::Duration, String> {
+    let secs: f64 = s.trim().parse().map_err(|e| format!("{e}"))?;
+    if secs <= 0.0 {
+        return Err("need a positive number of seconds".into());
+    }
+    std::time::Duration::try_from_secs_f64(secs).map_err(|e| format!("{e}"))
+}
+
+/// Parse `--decoded-channel`: `unbounded` or a capacity.
+fn parse_decoded_channel(s: &str) -> Result<pipeline::DecodedChannel, String> {
+    if s.eq_ignore_ascii_case("unbounded") {
+        return Ok(pipeline::DecodedChannel::Unbounded);
+    }
+    match s.parse() {
+        Ok(0) | Err(_) => Err("expected `unbounded` or a capacity above 0".into()),
+        Ok(size) => Ok(pipeline::DecodedChannel::Bounded(size)),
+    }
+}
+
+/// Parse `--num-parsers` / `--num-writers`: a thread count above 0.
+fn parse_thread_count(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) | Err(_) => Err("expected a thread count above 0".into()),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Parse `--header "Name: value"`.
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once(':').ok_or("expected \"Name: value\"")?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
 fn decode_b58_32(input: &Option<String>) -> Result<Option<[u8; 32]>, anyhow::Error> {
     input
         .as_deref()
@@ -55,21 +218,133 @@ fn decode_b58_32(input: &Option<String>) -> Result<Option<[u8; 32]>, anyhow::Err
         .transpose()
 }
 
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Diff the parquet output of two runs (added / removed / modified accounts)
+    #[cfg(feature = "duckdb")]
+    Compare {
+        #[arg(long)]
+        a: String,
+
+        #[arg(long)]
+        b: String,
+
+        #[arg(long)]
+        output_dir: Option<String>,
+    },
+    /// Print snapshot sources as JSON: the fastest, or all with `--list`
+    Discover {
+        /// Every viable source, ranked by a quick speed test
+        #[arg(long)]
+        list: bool,
+
+        #[arg(long)]
+        incremental: bool,
+    },
+    /// Show a pubkey as base58, a byte array and hex, and whether it's on the ed25519 curve
+    Key {
+        /// Base58 pubkey
+        #[arg(required_unless_present = "bytes")]
+        b58: Option<String>,
+
+        /// The 32 bytes instead, comma-separated (e.g. `6,221,246,...`)
+        #[arg(long, value_delimiter = ',', conflicts_with = "b58")]
+        bytes: Option<Vec<u8>>,
+    },
+}
+
 #[derive(Parser, Debug)]
 #[command(version, about)]
+#[command(group(clap::ArgGroup::new("follow").args(["watch", "tail"])))]
 pub struct CliArgs {
-    #[arg(short, long)]
-    path: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, num_args = 1..)]
+    path: Vec<String>,
 
     #[arg(long)]
     bench: bool,
 
+    #[arg(long)]
+    validate: bool,
+
+    #[arg(long)]
+    print_owners: bool,
+
+    #[arg(long, conflicts_with = "print_owners")]
+    stats_only: bool,
+
+    #[arg(long, conflicts_with_all = ["print_owners", "stats_only", "validate"])]
+    count_only: bool,
+
+    #[arg(
+        long,
+        value_parser = parse_sha256,
+        conflicts_with_all = ["max_accounts", "bench", "watch", "tail"],
+    )]
+    expect_sha256: Option<[u8; 32]>,
+
     #[arg(long)]
     discover: bool,
 
     #[arg(long)]
     incremental: bool,
 
+    #[arg(long)]
+    max_snapshot_age_slots: Option<u64>,
+
+    #[arg(long, value_enum, default_value_t = region::Region::Any)]
+    prefer_region: region::Region,
+
+    #[arg(long, value_parser = rpc::parse_rpc_url, default_value = rpc::DEFAULT_RPC)]
+    rpc_url: String,
+
+    #[arg(long)]
+    rpc_addrs: Vec<String>,
+
+    #[arg(long, value_parser = parse_secs)]
+    timeout_probe_secs: Option<std::time::Duration>,
+
+    #[arg(long, value_parser = parse_secs)]
+    timeout_speed_test_secs: Option<std::time::Duration>,
+
+    #[arg(long, default_value_t = rpc::DEFAULT_PROBE_CONCURRENCY_PER_NODE)]
+    probe_concurrency_per_node: usize,
+
+    #[arg(long, default_value_t = 1)]
+    min_candidates: usize,
+
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_candidates: Option<u64>,
+
+    #[arg(long, value_parser = parse_secs)]
+    timeout_download_secs: Option<std::time::Duration>,
+
+    #[arg(
+        long,
+        requires = "discover",
+        conflicts_with_all = [
+            "single_file", "deduplicate", "normalize_amounts", "token_states",
+            "aggregate_by_mint", "partition_by", "merge_decoded_output", "append",
+            "print_owners", "stats_only", "count_only", "validate", "tune_pipeline",
+        ],
+    )]
+    watch: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "single_file", "deduplicate", "normalize_amounts", "token_states",
+            "aggregate_by_mint", "partition_by", "merge_decoded_output", "append",
+            "print_owners", "stats_only", "count_only", "validate", "tune_pipeline",
+        ],
+    )]
+    tail: Option<std::path::PathBuf>,
+
+    #[arg(long, requires = "follow", default_value_t = watch::DEFAULT_INTERVAL_SECS)]
+    interval_secs: u64,
+
     #[arg(long, conflicts_with = "download_incremental")]
     download_full: bool,
 
@@ -79,13 +354,193 @@ pub struct CliArgs {
     #[arg(long, default_value = ".")]
     output: String,
 
+    #[arg(long, value_parser = parse_header)]
+    header: Vec<(String, String)>,
+
+    #[arg(long, default_value_t = download::DEFAULT_MAX_RETRIES)]
+    max_retries: u32,
+
+    #[arg(long)]
+    histogram: bool,
+
+    #[arg(long)]
+    no_summary: bool,
+
+    #[arg(long)]
+    rent_epochs: bool,
+
+    #[arg(long)]
+    token_states: bool,
+
+    #[arg(long, visible_alias = "limit")]
+    max_accounts: Option<u64>,
+
+    #[arg(long)]
+    normalize_amounts: bool,
+
+    #[arg(long, conflicts_with_all = ["normalize_amounts", "token_states"])]
+    aggregate_by_mint: bool,
+
+    #[arg(long, conflicts_with = "normalize_amounts")]
+    nfts_only: bool,
+
+    #[arg(long, value_enum, default_value_t = writer::OutputFormat::Parquet)]
+    format: writer::OutputFormat,
+
+    #[arg(long)]
+    single_file: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["single_file", "hash", "hash_prefix", "data_prefix"]
+    )]
+    deduplicate: bool,
+
+    #[arg(long)]
+    bloom_pubkey: bool,
+
+    #[arg(long, conflicts_with_all = ["single_file", "deduplicate"])]
+    sort_by_pubkey: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = ["single_file", "deduplicate", "sort_by_pubkey"],
+    )]
+    partition_by: Option<pipeline::PartitionBy>,
+
+    #[arg(long, default_value_t = DEFAULT_BUF_SIZE)]
+    buf_size: usize,
+
+    #[arg(
+        long,
+        default_value_t = ssp_core::zstd_frames::DEFAULT_WINDOW_LOG_MAX,
+        value_parser = clap::value_parser!(u32).range(10..=31),
+    )]
+    zstd_window_log: u32,
+
+    #[arg(short, long)]
+    verbose: bool,
+
+    #[arg(long)]
+    no_progress: bool,
+
+    #[arg(long)]
+    log_level: Option<LevelFilter>,
+
+    #[arg(long)]
+    verbose_discovery: bool,
+
+    #[arg(
+        long,
+        default_value_t = pipeline::DEFAULT_NUM_PARSERS,
+        value_parser = parse_thread_count,
+    )]
+    num_parsers: usize,
+
+    #[arg(
+        long,
+        default_value_t = pipeline::DEFAULT_NUM_WRITERS,
+        value_parser = parse_thread_count,
+    )]
+    num_writers: usize,
+
+    #[arg(long, default_value_t = pipeline::DEFAULT_CHANNEL_SIZE)]
+    channel_size: usize,
+
+    #[arg(long, value_parser = parse_decoded_channel)]
+    decoded_channel: Option<pipeline::DecodedChannel>,
+
+    #[arg(long)]
+    merge_decoded_output: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "single_file", "deduplicate", "partition_by", "merge_decoded_output",
+            "print_owners", "stats_only", "count_only", "validate", "tune_pipeline",
+        ],
+    )]
+    append: bool,
+
+    #[arg(long)]
+    batch_size: Option<usize>,
+
+    #[arg(long, default_value_t = 0)]
+    skip_entries: usize,
+
+    #[arg(long)]
+    tune_pipeline: bool,
+
     #[command(flatten)]
     filters: Filters,
 }
 
-fn download_snapshot(incremental: bool, output_dir: &str) -> anyhow::Result<()> {
+impl CliArgs {
+    /// Discovery settings, defaults where the flags weren't given.
+    fn discovery(&self) -> rpc::Discovery {
+        rpc::Discovery {
+            rpc_url: self.rpc_url.clone(),
+            addrs: self.rpc_addrs.clone(),
+            region: self.prefer_region,
+            timeouts: rpc::Timeouts {
+                probe: self
+                    .timeout_probe_secs
+                    .unwrap_or(rpc::Timeouts::default().probe),
+                speed_test: self.timeout_speed_test_secs,
+                probe_concurrency_per_node: self.probe_concurrency_per_node,
+            },
+            candidates: rpc::CandidateLimits {
+                min: self.min_candidates,
+                max: self.max_candidates.map(|n| n as usize),
+            },
+        }
+    }
+}
+
+/// Reject a discovered snapshot more than `max_age` slots behind the cluster.
+fn check_snapshot_age(
+    rt: &tokio::runtime::Runtime,
+    rpc_url: &str,
+    url: &str,
+    max_age: u64,
+) -> anyhow::Result<()> {
+    let Some(slot) = rpc::snapshot_slot(url) else {
+        warn!("can't read a slot from {url}; skipping age check");
+        return Ok(());
+    };
+    let current = rt.block_on(rpc::get_current_slot(rpc_url))?;
+    let age = current.saturating_sub(slot);
+    if age > max_age {
+        anyhow::bail!("snapshot at slot {slot} is {age} slots behind the tip (max {max_age})");
+    }
+    info!("snapshot slot {slot}, {age} slots behind the tip");
+    Ok(())
+}
+
+/// Client for the snapshot stream itself (not discovery). `headers` are sent
+/// with every request; proxies come from `HTTP_PROXY`/`HTTPS_PROXY` as usual.
+fn download_client(
+    headers: &[(String, String)],
+    timeout: Option<std::time::Duration>,
+) -> anyhow::Result<reqwest::blocking::Client> {
+    let mut map = reqwest::header::HeaderMap::new();
+    for (name, value) in headers {
+        map.append(
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())?,
+            reqwest::header::HeaderValue::from_str(value)?,
+        );
+    }
+    Ok(reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .default_headers(map)
+        .build()?)
+}
+
+fn download_snapshot(args: &CliArgs) -> anyhow::Result<()> {
+    let incremental = args.download_incremental;
     let rt = tokio::runtime::Runtime::new()?;
-    let source = rt.block_on(rpc::find_fastest_snapshot(None, incremental))?;
+    let source = rt.block_on(args.discovery().find(incremental))?;
 
     let filename = reqwest::Url::parse(&source.url)
         .ok()
@@ -104,29 +559,69 @@ fn download_snapshot(incremental: bool, output_dir: &str) -> anyhow::Result<()>
             .into()
         });
 
-    let dir = std::path::Path::new(output_dir);
+    let dir = std::path::Path::new(&args.output);
     std::fs::create_dir_all(dir)?;
     let dest = dir.join(&filename);
 
-    eprintln!(
+    info!(
         "downloading to {} ({:.1} MB/s, {:.1} GB)",
         dest.display(),
         source.speed_mbps,
         source.size.unwrap_or(0) as f64 / 1_073_741_824.0
     );
 
-    let mut resp = reqwest::blocking::Client::builder()
-        .timeout(None)
-        .build()?
-        .get(&source.url)
-        .send()?;
+    let resp = download::RetryingReader::open(
+        download_client(&args.header, args.timeout_download_secs)?,
+        &source.url,
+        args.max_retries,
+    )?;
 
     let total = resp
         .content_length()
         .or(source.size)
         .filter(|&s| s > 0);
+    let mut resp = expect_sha256(Box::new(resp), args.expect_sha256);
 
-    let mut file = std::fs::File::create(&dest)?;
+    // Written under `.part` and renamed once complete, so `dest` never holds
+    // a partial download or one that failed `--expect-sha256`.
+    let part = dir.join(format!("{filename}.part"));
+    let mut file = std::fs::File::create(&part)?;
+    let start = Instant::now();
+    let downloaded = match write_download(&mut resp, &mut file, total, !args.no_progress) {
+        Ok(downloaded) => downloaded,
+        Err(e) => {
+            drop(file);
+            // Best effort: the download error is the one worth reporting.
+            let _ = std::fs::remove_file(&part);
+            return Err(e.into());
+        }
+    };
+    drop(file);
+    std::fs::rename(&part, &dest)?;
+
+    let elapsed = start.elapsed();
+    if !args.no_progress {
+        // End the live line.
+        eprintln!();
+    }
+    info!(
+        "done: {:.2} GB in {:.0}s → {}",
+        downloaded as f64 / 1e9,
+        elapsed.as_secs_f64(),
+        dest.display()
+    );
+
+    Ok(())
+}
+
+/// Copy `resp` into `file`, printing progress against `total` every 500 ms
+/// when `progress` is set. Returns the bytes copied.
+fn write_download(
+    resp: &mut impl Read,
+    file: &mut std::fs::File,
+    total: Option<u64>,
+    progress: bool,
+) -> io::Result<u64> {
     let mut buf = vec![0u8; 1024 * 1024];
     let mut downloaded: u64 = 0;
     let start = Instant::now();
@@ -140,7 +635,7 @@ fn download_snapshot(incremental: bool, output_dir: &str) -> anyhow::Result<()>
         file.write_all(&buf[..n])?;
         downloaded += n as u64;
 
-        if last_print.elapsed().as_millis() >= 500 {
+        if progress && last_print.elapsed().as_millis() >= 500 {
             let elapsed = start.elapsed().as_secs_f64();
             let speed = if elapsed > 0.5 {
                 downloaded as f64 / elapsed / 1_000_000.0
@@ -167,21 +662,38 @@ fn download_snapshot(incremental: bool, output_dir: &str) -> anyhow::Result<()>
             last_print = Instant::now();
         }
     }
-
-    let elapsed = start.elapsed();
-    eprintln!(
-        "\ndone: {:.2} GB in {:.0}s → {}",
-        downloaded as f64 / 1e9,
-        elapsed.as_secs_f64(),
-        dest.display()
-    );
-
-    Ok(())
+    Ok(downloaded)
 }
 
 // ── Live stats printer ──────────────────────────────────────────
 
 const GB: f64 = 1_000_000_000.0;
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+/// Compressed bytes to read before projecting a total account count.
+const ESTIMATE_MIN_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Project the snapshot's total account count from the accounts seen per
+/// compressed byte so far. Rough — account sizes vary by AppendVec — but
+/// steady once a few hundred MB have gone by.
+fn estimate_total_accounts(seen: u64, bytes: u64, total_bytes: u64) -> Option<u64> {
+    (bytes >= ESTIMATE_MIN_BYTES).then(|| (seen as f64 * total_bytes as f64 / bytes as f64) as u64)
+}
+
+/// Seconds of reading before the progress line shows an ETA.
+const ETA_MIN_SECS: f64 = 2.0;
+
+/// Seconds left in the run: the compressed bytes left of `total`, at the
+/// rate `read` of them took `elapsed` seconds. The input's size is only
+/// known compressed (file size or `content-length`), so the rate is too.
+/// The caller counts all three from where `--skip-entries` finished, so
+/// the skip doesn't drag the rate down.
+fn estimate_eta(read: u64, total: u64, elapsed: f64) -> Option<u64> {
+    if elapsed < ETA_MIN_SECS || read == 0 {
+        return None;
+    }
+    Some((total.saturating_sub(read) as f64 * elapsed / read as f64) as u64)
+}
 
 fn format_rows(n: u64) -> String {
     if n >= 1_000_000_000 {
@@ -195,141 +707,827 @@ fn format_rows(n: u64) -> String {
     }
 }
 
-fn spawn_stats_printer(
-    stats: Arc<pipeline::PipelineStats>,
-    total_bytes: Option<u64>,
-) -> JoinHandle<()> {
-    std::thread::spawn(move || {
-        use std::fmt::Write as _;
-        let start = Instant::now();
-        let mut buf = String::new();
+fn print_key(key: &Pubkey) {
+    let bytes = key.as_bytes();
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    println!("base58:   {key}");
+    println!("bytes:    {bytes:?}");
+    println!("hex:      {hex}");
+    println!(
+        "on curve: {}",
+        if key.is_on_curve() {
+            "yes (can be a wallet key)"
+        } else {
+            "no (e.g. a PDA)"
+        }
+    );
+}
 
-        loop {
-            let bytes = stats.bytes_read.load(Ordering::Relaxed);
-            let rows = stats.rows_parsed.load(Ordering::Relaxed);
-            let elapsed = start.elapsed().as_secs_f64();
+fn print_size_histogram(sizes: &SizeHistogram) {
+    let total = sizes.total().max(1);
+    eprintln!("\naccount data_len histogram:");
+    for (label, &count) in SIZE_BUCKET_LABELS.iter().zip(&sizes.0) {
+        let pct = count as f64 / total as f64 * 100.0;
+        let bar = "█".repeat((pct / 2.5).round() as usize);
+        eprintln!(
+            "  {label:>11}  {:>8}  {pct:5.1}%  {bar}",
+            format_rows(count)
+        );
+    }
+}
 
-            let avg_speed = if elapsed > 0.5 {
-                bytes as f64 / elapsed / 1_000_000.0
-            } else {
-                0.0
-            };
+const TOP_OWNERS: usize = 50;
+const STATS_OWNERS: usize = 20;
+const STATS_SIZES: usize = 8;
+#[cfg(feature = "duckdb")]
+const TOP_ACCOUNTS: usize = 10;
+#[cfg(feature = "duckdb")]
+const RENT_EPOCH_BUCKET: u64 = 100;
+#[cfg(feature = "duckdb")]
+const TOP_FROZEN_MINTS: usize = 10;
 
-            let mins = elapsed as u64 / 60;
-            let secs = elapsed as u64 % 60;
+#[cfg(feature = "duckdb")]
+fn print_rent_epochs(buckets: &[db::RentEpochBucket]) {
+    let total = buckets.iter().map(|b| b.count).sum::<i64>().max(1);
+    eprintln!("\naccounts by rent_epoch:");
+    for bucket in buckets {
+        let label = match bucket.start {
+            Some(start) => format!("{start}-{}", start + RENT_EPOCH_BUCKET - 1),
+            None => "rent-exempt".to_string(),
+        };
+        let pct = bucket.count as f64 / total as f64 * 100.0;
+        let bar = "█".repeat((pct / 2.5).round() as usize);
+        eprintln!(
+            "  {label:>11}  {:>8}  {pct:5.1}%  {bar}",
+            format_rows(bucket.count as u64)
+        );
+    }
+}
 
-            buf.clear();
-            let _ = write!(buf, "\r\x1b[2K  ");
+#[cfg(feature = "duckdb")]
+fn print_token_states(states: &db::TokenStates) {
+    let total = states.counts.iter().map(|c| c.2).sum::<i64>().max(1);
+    eprintln!("\ntoken accounts by state:");
+    for (_, name, count) in &states.counts {
+        let pct = *count as f64 / total as f64 * 100.0;
+        eprintln!(
+            "  {name:>13}  {:>12}  {pct:5.1}%",
+            format_rows(*count as u64)
+        );
+    }
+    if states.top_frozen_mints.is_empty() {
+        return;
+    }
+    let shown = states.top_frozen_mints.len();
+    eprintln!("\ntop {shown} mints by frozen accounts:");
+    for (mint, count) in &states.top_frozen_mints {
+        eprintln!("  {mint:<44}  {:>12}", format_rows(*count as u64));
+    }
+}
 
-            if let Some(total) = total_bytes {
-                let pct = (bytes as f64 / total as f64 * 100.0).min(100.0);
-                let bar_w = 20;
-                let filled = (pct / 100.0 * bar_w as f64) as usize;
-                let bar: String = "█".repeat(filled) + &"░".repeat(bar_w - filled);
+fn print_top_owners(owners: &HashMap<Pubkey, u64>) {
+    let mut sorted: Vec<_> = owners.iter().collect();
+    sorted.sort_unstable_by(|a, b| b.1.cmp(a.1));
+    let total: u64 = owners.values().sum();
 
-                let _ = write!(
-                    buf,
-                    "{bar} {pct:.0}%  {:.1}/{:.1} GB",
-                    bytes as f64 / GB,
-                    total as f64 / GB,
-                );
-            } else {
-                let _ = write!(buf, "{:.1} GB", bytes as f64 / GB);
+    eprintln!(
+        "\n{} owners, {} accounts; top {}:",
+        owners.len(),
+        format_rows(total),
+        TOP_OWNERS.min(sorted.len())
+    );
+    for (owner, &count) in sorted.into_iter().take(TOP_OWNERS) {
+        let owner = owner_label(owner);
+        eprintln!("  {owner:<OWNER_LABEL_WIDTH$}  {count:>12}");
+    }
+}
+
+/// `--stats-only`: the largest owners, each with its most common `data_len`s.
+fn print_owner_sizes(owners: &pipeline::OwnerSizes) {
+    let mut sorted: Vec<_> = owners
+        .iter()
+        .map(|(owner, sizes)| (owner, sizes, sizes.values().sum::<u64>()))
+        .collect();
+    sorted.sort_unstable_by_key(|&(_, _, total)| std::cmp::Reverse(total));
+
+    eprintln!(
+        "\n{} owners; top {} with their most common account sizes:",
+        owners.len(),
+        STATS_OWNERS.min(sorted.len())
+    );
+    for (owner, sizes, total) in sorted.into_iter().take(STATS_OWNERS) {
+        let owner = owner_label(owner);
+        eprintln!(
+            "\n  {owner:<OWNER_LABEL_WIDTH$}  {total:>12}  ({} sizes)",
+            sizes.len()
+        );
+
+        let mut sizes: Vec<_> = sizes.iter().collect();
+        sizes.sort_unstable_by(|a, b| b.1.cmp(a.1));
+        for (size, &n) in sizes.into_iter().take(STATS_SIZES) {
+            let pct = n as f64 / total as f64 * 100.0;
+            eprintln!("    {size:>10} B  {n:>12}  {pct:>5.1}%");
+        }
+    }
+}
+
+/// The live progress line, drawn by `pipeline::run` progress callbacks.
+/// With `skip_entries`, the bar restarts once skipping ends and counts only
+/// the bytes after it. Without `live` (`--no-progress`) only the final
+/// per-decoder row counts are printed. `--watch`/`--tail` keep one line for
+/// every incremental and give it each run's size, so it is redrawn in place.
+struct ProgressBar {
+    total_bytes: std::sync::Mutex<Option<u64>>,
+    skip_entries: usize,
+    live: bool,
+    /// `elapsed` at the first call that saw skipping finished.
+    skip_end: std::sync::OnceLock<f64>,
+}
+
+impl ProgressBar {
+    fn new(total_bytes: Option<u64>, skip_entries: usize, live: bool) -> Arc<Self> {
+        Arc::new(Self {
+            total_bytes: std::sync::Mutex::new(total_bytes),
+            skip_entries,
+            live,
+            skip_end: std::sync::OnceLock::new(),
+        })
+    }
+
+    /// Size of the input the next run reads.
+    fn set_total(&self, total_bytes: Option<u64>) {
+        *self.total_bytes.lock().unwrap() = total_bytes;
+    }
+
+    /// A progress callback for one `pipeline::run` that draws this line.
+    fn callback(self: &Arc<Self>) -> pipeline::ProgressFn {
+        let bar = self.clone();
+        Box::new(move |p| bar.draw(p))
+    }
+
+    fn draw(&self, p: pipeline::Progress) {
+        use std::fmt::Write as _;
+        let mut buf = String::new();
+        if !self.live {
+            if p.finished {
+                write_decoded_rows(&mut buf, &p.decoded_rows);
+                io::stderr().write_all(buf.as_bytes()).ok();
             }
+            return;
+        }
+        let bytes = p.bytes_read - p.bytes_skipped;
+        let total_bytes = self
+            .total_bytes
+            .lock()
+            .unwrap()
+            .map(|t| t.saturating_sub(p.bytes_skipped));
+        let elapsed = p.elapsed.as_secs_f64();
+
+        let avg_speed = if elapsed > 0.5 {
+            p.bytes_read as f64 / elapsed / 1_000_000.0
+        } else {
+            0.0
+        };
+
+        let mins = elapsed as u64 / 60;
+        let secs = elapsed as u64 % 60;
+
+        let _ = write!(buf, "\r\x1b[2K  ");
+
+        if let Some(total) = total_bytes {
+            let pct = (bytes as f64 / total as f64 * 100.0).min(100.0);
+            let bar_w = 20;
+            let filled = (pct / 100.0 * bar_w as f64) as usize;
+            let bar: String = "█".repeat(filled) + &"░".repeat(bar_w - filled);
 
             let _ = write!(
                 buf,
-                "  {avg_speed:.0} MB/s  {} rows  {mins}m{secs:02}s",
-                format_rows(rows),
+                "{bar} {pct:.0}%  {:.1}/{:.1} GB",
+                bytes as f64 / GB,
+                total as f64 / GB,
             );
+        } else {
+            let _ = write!(buf, "{:.1} GB", bytes as f64 / GB);
+        }
+        let _ = write!(buf, " ({:.1} GB raw)", p.bytes_decompressed as f64 / GB);
 
-            io::stderr().write_all(buf.as_bytes()).ok();
-            io::stderr().flush().ok();
+        let _ = write!(
+            buf,
+            "  {avg_speed:.0} MB/s  {} rows  {mins}m{secs:02}s",
+            format_rows(p.rows_parsed),
+        );
+        let (acct_blocked, decoded_blocked) = p.send_blocked;
+        let _ = write!(
+            buf,
+            " | ch:{:.0}% dec:{:.0}%",
+            acct_blocked * 100.0,
+            decoded_blocked * 100.0
+        );
 
-            if stats.finished.load(Ordering::Acquire) {
-                break;
+        // Not while skipping: nothing is decompressed for the parsers yet.
+        let since_skip = if self.skip_entries == 0 {
+            Some(elapsed)
+        } else if p.bytes_skipped > 0 {
+            Some(elapsed - *self.skip_end.get_or_init(|| elapsed))
+        } else {
+            None
+        };
+        if let Some(eta) = total_bytes
+            .zip(since_skip)
+            .filter(|_| !p.finished)
+            .and_then(|(t, secs)| estimate_eta(bytes, t, secs))
+        {
+            let _ = write!(buf, "  ETA {}m{:02}s", eta / 60, eta % 60);
+        }
+
+        if let Some(est) =
+            total_bytes.and_then(|t| estimate_total_accounts(p.accounts_seen, bytes, t))
+        {
+            let _ = write!(buf, "  ~{} accounts total", format_rows(est));
+        }
+
+        if self.skip_entries > 0 {
+            if p.bytes_skipped == 0 {
+                let _ = write!(buf, "  (skipping {} entries)", self.skip_entries);
+            } else {
+                let _ = write!(buf, "  ({} entries skipped)", self.skip_entries);
             }
+        }
 
-            std::thread::sleep(Duration::from_millis(250));
+        if p.finished {
+            buf.push('\n');
+            write_decoded_rows(&mut buf, &p.decoded_rows);
         }
 
-        eprintln!();
-    })
+        io::stderr().write_all(buf.as_bytes()).ok();
+        io::stderr().flush().ok();
+    }
+}
+
+fn write_decoded_rows(buf: &mut String, decoded_rows: &BTreeMap<&'static str, u64>) {
+    use std::fmt::Write as _;
+    for (name, rows) in decoded_rows {
+        let _ = writeln!(buf, "  {name}: {} rows", format_rows(*rows));
+    }
+}
+
+/// `reader` behind a `--expect-sha256` check, when one was asked for.
+fn expect_sha256(reader: Box<dyn Read + Send>, expected: Option<[u8; 32]>) -> Box<dyn Read + Send> {
+    match expected {
+        Some(expected) => Box::new(download::Sha256Reader::new(reader, expected)),
+        None => reader,
+    }
+}
+
+/// Log to stderr at `--log-level`, else as `RUST_LOG` says, else at `info`.
+/// `--log-level` and the default only cover ssp's own messages.
+/// `verbose_discovery` adds every probe's outcome on top.
+fn init_logging(level: Option<LevelFilter>, verbose_discovery: bool) {
+    let filter = match level {
+        Some(level) => EnvFilter::new(format!("ssp={level}")),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("ssp=info")),
+    };
+    let filter = if verbose_discovery {
+        filter.add_directive(format!("{}=debug", rpc::DISCOVERY_TARGET).parse().unwrap())
+    } else {
+        filter
+    };
+    let terminal = io::stderr().is_terminal();
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(terminal)
+        .with_target(false)
+        .without_time()
+        .with_writer(move || {
+            // Start on a clean line rather than after the progress bar.
+            let mut stderr = io::stderr();
+            if terminal {
+                stderr.write_all(b"\r\x1b[2K").ok();
+            }
+            stderr
+        })
+        .init();
+}
+
+/// `--merge-decoded-output`, implied when there's only one decoded writer.
+fn merge_decoded(args: &CliArgs) -> bool {
+    args.merge_decoded_output || pipeline::NUM_DECODED_WRITERS == 1
+}
+
+/// Output files this run left in the working directory, for the manifest.
+/// `decoded` are the decoder outputs that got rows.
+fn run_files<'a>(args: &CliArgs, tag: &str, decoded: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut stems: Vec<String> = (0..args.num_writers)
+        .map(|i| format!("accounts{tag}_{i}"))
+        .collect();
+    if tag.is_empty() {
+        // --single-file and --deduplicate replace the parts with this.
+        stems.push("accounts".into());
+    }
+    for name in decoded {
+        if merge_decoded(args) {
+            stems.push(format!("{name}{tag}"));
+        } else {
+            stems.extend((0..pipeline::NUM_DECODED_WRITERS).map(|i| format!("{name}{tag}_{i}")));
+        }
+    }
+    let ext = args.format.extension();
+    let mut files: Vec<String> = stems
+        .into_iter()
+        .map(|stem| format!("{stem}.{ext}"))
+        .filter(|f| std::path::Path::new(f).exists())
+        .collect();
+    if args.partition_by.is_some() {
+        files.push(format!("{}/", pipeline::PARTITION_DIR));
+    }
+    files
+}
+
+/// Glob over this run's output for decoder `name`.
+#[cfg(feature = "duckdb")]
+fn decoded_glob(args: &CliArgs, name: &str) -> String {
+    if merge_decoded(args) {
+        format!("{name}.parquet")
+    } else {
+        // [0-9] keeps token_accounts_normalized.parquet out of the glob
+        format!("{name}_[0-9]*.parquet")
+    }
+}
+
+/// The steps that read the parquet output back through DuckDB:
+/// `--deduplicate`, `--normalize-amounts`, `--rent-epochs`,
+/// `--token-states` and the top-accounts summary.
+#[cfg(feature = "duckdb")]
+fn post_process(args: &CliArgs, rows: u64, filters: &ResolvedFilters) -> anyhow::Result<()> {
+    if args.deduplicate {
+        let kept = db::DuckDB::open()?.deduplicate(
+            "accounts_[0-9]*.parquet",
+            "accounts.parquet",
+            filters,
+        )?;
+        for i in 0..args.num_writers {
+            std::fs::remove_file(format!("accounts_{i}.parquet"))?;
+        }
+        info!(
+            "deduplicated: kept {} of {} rows in accounts.parquet",
+            format_rows(kept as u64),
+            format_rows(rows)
+        );
+    }
+
+    if args.normalize_amounts {
+        let db = db::DuckDB::open()?;
+        db.normalize_token_amounts(
+            &decoded_glob(args, "token_accounts"),
+            &decoded_glob(args, "mints"),
+            "token_accounts_normalized.parquet",
+        )?;
+        info!("wrote token_accounts_normalized.parquet");
+    }
+
+    let summary = args.format == writer::OutputFormat::Parquet && !args.no_summary;
+    if !summary && !args.rent_epochs && !args.token_states {
+        return Ok(());
+    }
+    let mut db = db::DuckDB::open()?;
+    db.create_views(".")?;
+
+    if summary {
+        let (names, mut rows) = db.query_top_accounts(filters, TOP_ACCOUNTS)?;
+        // Cells come back as base58; label the owner column like the histograms.
+        let owner_col = names.iter().position(|n| n == "owner");
+        for row in &mut rows {
+            if let Some(cell) = owner_col.map(|i| &mut row[i])
+                && let Ok(owner) = Pubkey::from_b58(cell)
+            {
+                *cell = owner_label(&owner);
+            }
+        }
+        eprintln!("\ntop {} accounts by lamports:", rows.len());
+        eprint!("{}", db::render_table(&names, &rows));
+    }
+
+    if args.rent_epochs {
+        let buckets = db.rent_epoch_histogram(RENT_EPOCH_BUCKET)?;
+        print_rent_epochs(&buckets);
+    }
+
+    if args.token_states {
+        let states = db.token_states(TOP_FROZEN_MINTS)?;
+        print_token_states(&states);
+    }
+    Ok(())
 }
 
 // ── Main ────────────────────────────────────────────────────────
 
 fn main() -> anyhow::Result<()> {
     let args = CliArgs::parse();
+    init_logging(args.log_level, args.verbose_discovery);
+
+    #[cfg(feature = "duckdb")]
+    if let Some(Command::Compare { a, b, output_dir }) = &args.command {
+        let mut db = db::DuckDB::open()?;
+        let diff = db.compare(a, b, output_dir.as_deref())?;
+        eprintln!("added:    {}", diff.added);
+        eprintln!("removed:  {}", diff.removed);
+        eprintln!("modified: {}", diff.modified);
+        if let Some(out) = output_dir {
+            info!("diff sets written to {out}/{{added,removed,modified}}.parquet");
+        }
+        return Ok(());
+    }
+
+    if !args.rpc_addrs.is_empty() && args.prefer_region != region::Region::Any {
+        warn!("--prefer-region is ignored with --rpc-addrs");
+    }
+
+    if let Some(Command::Key { b58, bytes }) = &args.command {
+        let key = match (b58, bytes) {
+            (Some(s), _) => Pubkey::from_b58(s)?,
+            (None, Some(bytes)) => {
+                anyhow::ensure!(
+                    bytes.len() == 32,
+                    "--bytes needs 32 values, got {}",
+                    bytes.len()
+                );
+                Pubkey::new(bytes.as_slice().try_into()?)
+            }
+            (None, None) => unreachable!("clap requires one of them"),
+        };
+        print_key(&key);
+        return Ok(());
+    }
+
+    if let Some(Command::Discover { list, incremental }) = &args.command {
+        let rt = tokio::runtime::Runtime::new()?;
+        let discovery = args.discovery();
+        let sources = if *list {
+            rt.block_on(discovery.list(*incremental))?
+        } else {
+            vec![rt.block_on(discovery.find(*incremental))?]
+        };
+        let json: Vec<_> = sources
+            .iter()
+            .map(|s| {
+                let region = region::region_of_url(&s.url).map(|r| format!("{r:?}").to_lowercase());
+                serde_json::json!({
+                    "rank": s.rank,
+                    "url": s.url,
+                    "size": s.size,
+                    "speed_mbps": s.speed_mbps,
+                    "region": region,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
 
     if args.download_full || args.download_incremental {
-        return download_snapshot(args.download_incremental, &args.output);
+        return download_snapshot(&args);
     }
 
     if args.bench {
-        let path = args.path.as_deref().expect("--bench requires --path");
+        let path = args.path.first().expect("--bench requires --path");
+        let window_log = args.zstd_window_log;
         eprintln!("=== Stage 1: zstd only ===");
-        bench::run(std::fs::File::open(path)?);
+        bench::run(std::fs::File::open(path)?, args.buf_size, window_log);
         eprintln!("\n=== Stage 2: zstd + tar ===");
-        bench::run_tar(std::fs::File::open(path)?);
+        bench::run_tar(std::fs::File::open(path)?, args.buf_size, window_log);
         eprintln!("\n=== Stage 3: zstd + tar + parse ===");
-        bench::run_full(std::fs::File::open(path)?);
+        let parse = bench::run_full(std::fs::File::open(path)?, args.buf_size, window_log);
+        eprintln!("\n=== Stage 4: zstd + tar + parse + decode ===");
+        let (decode, sample) =
+            bench::run_full_decoded(std::fs::File::open(path)?, args.buf_size, window_log);
+        eprintln!(
+            "\ndecode overhead: {:+.1}s ({:+.0}%)",
+            decode - parse,
+            (decode - parse) / parse * 100.0
+        );
+        eprintln!("\n=== Stage 5: decoded writers, per-thread vs shared ===");
+        bench::run_decoded_writers(sample);
         return Ok(());
     }
 
-    if args.path.is_none() && !args.discover {
-        eprintln!("usage: ssp --path <file> [filters]");
+    if args.path.is_empty() && !args.discover {
+        eprintln!("usage: ssp --path <file|->... [filters]");
         eprintln!("       ssp --discover [--incremental] [filters]");
         eprintln!("       ssp --download-full | --download-incremental");
         eprintln!("       ssp --bench --path <file>");
+        eprintln!("       ssp compare --a <dir> --b <dir> [--output-dir <dir>]");
         std::process::exit(1);
     }
 
     let filters = args.filters.resolve()?;
+    if filters.owner.is_some() && !filters.exclude_owners.is_empty() {
+        warn!("--exclude-owner is ignored when --owner is given");
+    }
+
+    if args.path.iter().filter(|p| *p == "-").count() > 1 {
+        anyhow::bail!("stdin (`--path -`) can only be read once");
+    }
+    if args.expect_sha256.is_some() && args.path.len() > 1 {
+        anyhow::bail!(
+            "--expect-sha256 checks a single input, got {}",
+            args.path.len()
+        );
+    }
+
+    if args.max_snapshot_age_slots.is_some() && !args.discover {
+        warn!("--max-snapshot-age-slots is only checked with --discover");
+    }
+
+    // These post-processing steps read the parquet output back through DuckDB.
+    if args.format != writer::OutputFormat::Parquet
+        && (args.single_file
+            || args.deduplicate
+            || args.normalize_amounts
+            || args.rent_epochs
+            || args.token_states
+            || args.watch
+            || args.tail.is_some())
+    {
+        anyhow::bail!(
+            "--single-file, --deduplicate, --normalize-amounts, --rent-epochs, \
+             --token-states, --watch and --tail need --format parquet"
+        );
+    }
+    let following = args.watch || args.tail.is_some();
+    if !cfg!(feature = "duckdb")
+        && (args.deduplicate
+            || args.normalize_amounts
+            || args.rent_epochs
+            || args.token_states
+            || following)
+    {
+        anyhow::bail!(
+            "--deduplicate, --normalize-amounts, --rent-epochs, --token-states, --watch and \
+             --tail need the `duckdb` feature"
+        );
+    }
+    let stop = following.then(watch::install_ctrlc).transpose()?;
+    let mut discovered_url = None;
+    if args.format != writer::OutputFormat::Parquet && args.bloom_pubkey {
+        warn!("--bloom-pubkey only applies to parquet output");
+    }
 
-    let (reader, total_bytes): (Box<dyn Read + Send>, Option<u64>) = if let Some(path) = &args.path
+    let (readers, total_bytes): (Vec<Box<dyn Read + Send>>, Option<u64>) = if !args.path.is_empty()
     {
-        let file = std::fs::File::open(path)?;
-        let size = file.metadata().ok().map(|m| m.len());
-        (Box::new(file), size)
+        // Multiple inputs (e.g. full + incrementals) are streamed one after
+        // another through the same pipeline, so writers accumulate across all.
+        let mut readers: Vec<Box<dyn Read + Send>> = Vec::with_capacity(args.path.len());
+        let mut total = Some(0u64);
+        for path in &args.path {
+            // `-` reads stdin (e.g. from an external downloader); size unknown.
+            if path == "-" {
+                readers.push(Box::new(io::stdin()));
+                total = None;
+                continue;
+            }
+            let file = std::fs::File::open(path)?;
+            let size = file.metadata().ok().map(|m| m.len());
+            total = total.zip(size).map(|(t, s)| t + s);
+            readers.push(Box::new(file));
+        }
+        (readers, total)
     } else if args.discover {
         let rt = tokio::runtime::Runtime::new()?;
-        let source = rt.block_on(rpc::find_fastest_snapshot(None, args.incremental))?;
-        eprintln!(
+        let source = rt.block_on(args.discovery().find(args.incremental))?;
+        if let Some(max_age) = args.max_snapshot_age_slots {
+            check_snapshot_age(&rt, &args.rpc_url, &source.url, max_age)?;
+        }
+        info!(
             "streaming from {} ({:.1} MB/s, {:.1} GB)",
             source.url,
             source.speed_mbps,
             source.size.unwrap_or(0) as f64 / 1_073_741_824.0
         );
-        let resp = reqwest::blocking::Client::builder()
-            .timeout(None)
-            .build()?
-            .get(&source.url)
-            .send()?;
-        (Box::new(resp), source.size)
+        let resp = download::RetryingReader::open(
+            download_client(&args.header, args.timeout_download_secs)?,
+            &source.url,
+            args.max_retries,
+        )?;
+        discovered_url = Some(source.url);
+        (vec![Box::new(resp)], source.size)
     } else {
         unreachable!()
     };
+    let readers = readers
+        .into_iter()
+        .map(|r| expect_sha256(r, args.expect_sha256))
+        .collect();
 
     let stats = Arc::new(pipeline::PipelineStats::new());
-    let printer = spawn_stats_printer(stats.clone(), total_bytes);
+    let bar = ProgressBar::new(total_bytes, args.skip_entries, !args.no_progress).callback();
 
+    let opts = pipeline::PipelineOptions {
+        histogram: args.histogram,
+        max_accounts: args.max_accounts,
+        single_file: args.single_file,
+        buf_size: args.buf_size,
+        zstd_window_log: args.zstd_window_log,
+        num_parsers: args.num_parsers,
+        num_writers: args.num_writers,
+        channel_size: args.channel_size,
+        decoded_channel: args
+            .decoded_channel
+            .unwrap_or(pipeline::DecodedChannel::Bounded(2 * args.channel_size)),
+        merge_decoded: merge_decoded(&args),
+        discard_output: args.tune_pipeline,
+        bloom_pubkey: args.bloom_pubkey,
+        // `--watch`/`--tail` merge incrementals by each pubkey's latest write.
+        write_version: args.deduplicate || following,
+        format: args.format,
+        aggregate_by_mint: args.aggregate_by_mint,
+        nfts_only: args.nfts_only,
+        sort_by_pubkey: args.sort_by_pubkey,
+        partition_by: args.partition_by,
+        batch_size: args.batch_size,
+        log_entries: args.verbose,
+        skip_entries: args.skip_entries,
+        // Numbered after the runs already in the manifest.
+        run: if args.append {
+            Some(manifest::Manifest::load()?.next_run())
+        } else {
+            None
+        },
+    };
+
+    if args.validate {
+        let printer = pipeline::spawn_progress(stats.clone(), bar);
+        let (checked, violations) = pipeline::validate(readers, stats.clone(), opts)?;
+        printer.join().ok();
+        eprintln!(
+            "\nvalidation: {} accounts checked, {violations} violations found",
+            format_rows(checked)
+        );
+        return Ok(());
+    }
+
+    if args.count_only {
+        let printer = pipeline::spawn_progress(stats.clone(), bar);
+        let count = pipeline::count_accounts(readers, filters, stats.clone(), opts)?;
+        printer.join().ok();
+        eprintln!();
+        println!("{count}");
+        return Ok(());
+    }
+
+    if args.print_owners {
+        let printer = pipeline::spawn_progress(stats.clone(), bar);
+        let owners = pipeline::owner_counts(readers, filters, stats.clone(), opts)?;
+        printer.join().ok();
+        print_top_owners(&owners);
+        return Ok(());
+    }
+
+    if args.stats_only {
+        let printer = pipeline::spawn_progress(stats.clone(), bar);
+        let owners = pipeline::owner_size_counts(readers, filters, stats.clone(), opts)?;
+        printer.join().ok();
+        print_owner_sizes(&owners);
+        return Ok(());
+    }
+
+    let rss_before = mem::current_rss();
     let start = Instant::now();
-    pipeline::run(reader, filters, stats.clone())?;
+    let sampler = args
+        .tune_pipeline
+        .then(|| tune::spawn_sampler(stats.clone(), opts.channel_size));
+    let summary_filters = filters.clone();
+    // Deduplication has to see every version: a close (zero lamports, handed
+    // back to the system program) must still outrank the live versions before
+    // it. post_process applies the dead-account and owner filters afterwards.
+    let filters = if args.deduplicate {
+        ResolvedFilters {
+            owner: None,
+            exclude_owners: Default::default(),
+            include_dead: true,
+            ..filters
+        }
+    } else {
+        filters
+    };
+    let inputs = match &discovered_url {
+        Some(url) => vec![url.clone()],
+        None => args.path.clone(),
+    };
+    let sizes = pipeline::run(
+        readers,
+        &inputs,
+        filters,
+        stats.clone(),
+        opts,
+        std::path::Path::new("."),
+        Some(bar),
+    )?;
     let elapsed = start.elapsed();
 
-    printer.join().ok();
+    if let Some(sampler) = sampler {
+        let samples = sampler.join().expect("sampler panicked");
+        tune::report(&stats, &samples, &opts);
+        return Ok(());
+    }
 
     let rows = stats.rows_parsed.load(Ordering::Relaxed);
     let bytes = stats.bytes_read.load(Ordering::Relaxed);
     let avg_speed = bytes as f64 / elapsed.as_secs_f64() / 1_000_000.0;
-    eprintln!(
-        "\ndone: {} rows, {:.1} GB in {:.1}s ({:.0} MB/s)",
+    info!(
+        "done: {} rows, {:.1} GB in {:.1}s ({:.0} MB/s)",
         format_rows(rows),
         bytes as f64 / GB,
         elapsed.as_secs_f64(),
         avg_speed,
     );
+    // Sums every stored version of every account, before filters. Well above
+    // the SOL supply means the snapshot holds duplicates worth deduplicating.
+    let lamports = stats.total_lamports.load(Ordering::Relaxed);
+    info!(
+        "total lamports: {lamports} ({:.0} SOL)",
+        lamports as f64 / LAMPORTS_PER_SOL
+    );
+
+    for (name, n) in stats.decoder_skipped.lock().unwrap().iter() {
+        warn!("{name}: {} matched accounts not emitted", format_rows(*n));
+    }
+    for (name, n) in stats.decoder_errors.lock().unwrap().iter() {
+        warn!("{name}: {} decode errors", format_rows(*n));
+    }
+
+    if let Some(sizes) = sizes {
+        print_size_histogram(&sizes);
+    }
+
+    #[cfg(feature = "duckdb")]
+    post_process(&args, rows, &summary_filters)?;
+
+    let mut manifest = manifest::Manifest::load()?;
+    if opts.run.is_none() {
+        let stale = manifest.appended_files();
+        if !stale.is_empty() {
+            warn!(
+                "{} files from earlier --append runs are still here and match the same globs, \
+                 e.g. {}",
+                stale.len(),
+                stale[0]
+            );
+        }
+    }
+    let decoded = stats.decoded_rows.lock().unwrap().clone();
+    manifest.record(manifest::Run {
+        run: opts.run.unwrap_or(0),
+        finished_at: 0,
+        inputs,
+        accounts: rows,
+        files: run_files(&args, &opts.run_tag(), decoded.keys().copied()),
+        decoded: decoded.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+    })?;
+
+    if args.verbose {
+        eprintln!("\nmemory (RSS):");
+        eprintln!("  before parse  {:.2} GB", rss_before as f64 / GB);
+        eprintln!(
+            "  after parse   {:.2} GB",
+            stats.rss_after_parse.load(Ordering::Relaxed) as f64 / GB
+        );
+        eprintln!(
+            "  after write   {:.2} GB",
+            stats.rss_after_write.load(Ordering::Relaxed) as f64 / GB
+        );
+    }
+    match mem::measure_peak_rss() {
+        0 => info!("peak memory: not available on this platform"),
+        peak => info!("peak memory: {:.1} GB", peak as f64 / GB),
+    }
+
+    if let Some(stop) = stop {
+        // The output reflects the newest input: the discovered snapshot, or
+        // the last `--path` (full snapshot, then any incrementals).
+        let slot = discovered_url
+            .as_deref()
+            .or(args.path.last().map(String::as_str))
+            .and_then(rpc::snapshot_slot)
+            .ok_or_else(|| anyhow::anyhow!("can't read the initial snapshot's slot"))?;
+        let follow = match args.tail {
+            Some(dir) => watch::Follow::Dir(dir),
+            None => watch::Follow::Discover {
+                discovery: args.discovery(),
+                download_timeout: args.timeout_download_secs,
+                headers: args.header.clone(),
+                max_retries: args.max_retries,
+            },
+        };
+        watch::Watch {
+            interval: std::time::Duration::from_secs(args.interval_secs),
+            follow,
+            filters: summary_filters,
+            opts,
+            slot,
+            bar: ProgressBar::new(None, 0, !args.no_progress),
+        }
+        .run(&stop)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,72 @@
+//! `manifest.json`: what each run into the output directory wrote, so
+//! `--append` can number the next run and readers can tell runs apart.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MANIFEST: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Manifest {
+    pub runs: Vec<Run>,
+}
+
+/// One run's contribution. Run 0 is a plain run, which replaces the output;
+/// `--append` runs count up from 1.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Run {
+    pub run: u32,
+    /// Unix seconds when the run finished, set by `Manifest::record`.
+    pub finished_at: u64,
+    /// Snapshot paths or the discovered URL.
+    pub inputs: Vec<String>,
+    pub accounts: u64,
+    /// Rows written per decoder output.
+    pub decoded: BTreeMap<String, u64>,
+    /// Output files, relative to the output directory.
+    pub files: Vec<String>,
+}
+
+impl Manifest {
+    /// The manifest in the current directory, empty if there is none.
+    pub fn load() -> anyhow::Result<Self> {
+        match std::fs::read(MANIFEST) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Number for the next `--append` run.
+    pub fn next_run(&self) -> u32 {
+        self.runs.iter().map(|r| r.run).max().unwrap_or(0) + 1
+    }
+
+    /// Files of earlier `--append` runs that are still on disk.
+    pub fn appended_files(&self) -> Vec<&str> {
+        self.runs
+            .iter()
+            .filter(|r| r.run > 0)
+            .flat_map(|r| &r.files)
+            .map(String::as_str)
+            .filter(|f| Path::new(f).exists())
+            .collect()
+    }
+
+    /// Record `run`. A plain run replaces every earlier entry, as it does the
+    /// output; an `--append` run is added to them.
+    pub fn record(&mut self, mut run: Run) -> anyhow::Result<()> {
+        run.finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        if run.run == 0 {
+            self.runs.clear();
+        }
+        self.runs.push(run);
+        let file = std::fs::File::create(MANIFEST)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
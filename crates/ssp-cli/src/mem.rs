@@ -0,0 +1,50 @@
+//! Process memory usage, for spotting OOM-prone runs.
+
+/// Peak resident set size of this process, in bytes. 0 if unsupported.
+///
+/// Linux reads `VmHWM` from `/proc/self/status` (`VmPeak` is peak *virtual*
+/// size, which overstates by the reserved-but-untouched address space).
+#[cfg(target_os = "linux")]
+pub fn measure_peak_rss() -> u64 {
+    proc_status_kb("VmHWM:") * 1024
+}
+
+/// Peak resident set size of this process, in bytes. 0 if unsupported.
+///
+/// macOS reports `ru_maxrss` from `getrusage` in bytes.
+#[cfg(target_os = "macos")]
+pub fn measure_peak_rss() -> u64 {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return 0;
+    }
+    usage.ru_maxrss as u64
+}
+
+/// Peak resident set size of this process, in bytes. 0 if unsupported.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn measure_peak_rss() -> u64 {
+    0
+}
+
+/// Current resident set size in bytes. Linux only; 0 elsewhere.
+pub fn current_rss() -> u64 {
+    if cfg!(target_os = "linux") {
+        proc_status_kb("VmRSS:") * 1024
+    } else {
+        0
+    }
+}
+
+/// Read a `kB` field from `/proc/self/status`, 0 if missing.
+fn proc_status_kb(field: &str) -> u64 {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(field))
+                .and_then(|rest| rest.trim().trim_end_matches("kB").trim().parse().ok())
+        })
+        .unwrap_or(0)
+}
@@ -1,49 +1,541 @@
 use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::writer::FileWriter;
 use crossbeam::channel;
 use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use parquet::basic::Compression;
-use parquet::file::properties::WriterProperties;
-use std::collections::HashMap;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+use parquet::format::SortingColumn;
+use parquet::schema::types::ColumnPath;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::Read;
-use std::sync::Arc;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{error, info_span, warn};
 
+use crate::writer::{AccountWriter, OutputFormat};
+use crate::{mem, rpc};
 use ssp_core::Pubkey;
-use ssp_core::decoders::Decoder;
-use ssp_core::decoders::known_mints;
-use ssp_core::decoders::token_program::mint::MintDecoder;
-use ssp_core::decoders::token_program::token_account::TokenAccountDecoder;
+use ssp_core::decoders::{self, DecoderRouter, known_mints};
 use ssp_core::filters::ResolvedFilters;
-use ssp_core::parser::AccountHeader;
+use ssp_core::parser::{AccountHeader, ParseCounters, SendWindow, SizeHistogram, StreamEnd};
 use ssp_core::record_batch;
 
-const NUM_WRITERS: usize = 2;
-const NUM_PARSERS: usize = 4;
-const NUM_DECODED_WRITERS: usize = 2;
+pub const DEFAULT_NUM_PARSERS: usize = 4;
+pub const DEFAULT_NUM_WRITERS: usize = 2;
+pub const DEFAULT_CHANNEL_SIZE: usize = 128;
+pub const NUM_DECODED_WRITERS: usize = 2;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Capacity of the decoded-batch channel (`--decoded-channel`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecodedChannel {
+    /// Parsers block once this many decoded batches are queued.
+    Bounded(usize),
+    /// Parsers never wait on the decoded writers. Nothing bounds the queue
+    /// then: if the writers fall behind, every pending batch sits in memory.
+    Unbounded,
+}
+
+/// How the accounts output is split into files (`--partition-by`).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionBy {
+    /// `by_owner/<owner>/accounts_{n}`, one directory per owner program.
+    Owner,
+}
+
+/// Knobs for a pipeline run that aren't account filters.
+#[derive(Clone, Copy)]
+pub struct PipelineOptions {
+    /// Collect a per-thread `data_len` histogram.
+    pub histogram: bool,
+    /// Stop once this many accounts have passed the filters.
+    pub max_accounts: Option<u64>,
+    /// Merge the per-writer account files into a single `accounts.parquet`.
+    pub single_file: bool,
+    /// `BufReader` capacity in front of the zstd decoder.
+    pub buf_size: usize,
+    /// zstd `window_log_max`; windows over `2^zstd_window_log` bytes fail.
+    pub zstd_window_log: u32,
+    pub num_parsers: usize,
+    /// Account writers; decoded output always gets `NUM_DECODED_WRITERS`.
+    pub num_writers: usize,
+    /// Capacity of the raw and account channels.
+    pub channel_size: usize,
+    /// Capacity of the channel from the parsers to the decoded writers.
+    pub decoded_channel: DecodedChannel,
+    /// Have the decoded writers share one `{name}` file per decoder instead
+    /// of writing `{name}_{i}` each (`--merge-decoded-output`).
+    pub merge_decoded: bool,
+    /// Encode parquet as usual but write it to `io::sink()` (`--tune-pipeline`).
+    pub discard_output: bool,
+    /// Write a Bloom filter for the `pubkey` column of every output file.
+    pub bloom_pubkey: bool,
+    /// Add a `write_version` column to the accounts output.
+    pub write_version: bool,
+    /// File format for the accounts and decoded outputs.
+    pub format: OutputFormat,
+    /// Write per-mint token totals instead of one row per token account.
+    pub aggregate_by_mint: bool,
+    /// Write `nft_mints` (supply 1, no decimals) instead of every mint.
+    pub nfts_only: bool,
+    /// Buffer each account writer's rows and write them sorted by pubkey.
+    pub sort_by_pubkey: bool,
+    /// Split the accounts output into one directory per partition key.
+    pub partition_by: Option<PartitionBy>,
+    /// Rows per decoded batch for every decoder; `None` keeps each default.
+    pub batch_size: Option<usize>,
+    /// Print a line per tar entry as it is read (`--verbose`).
+    pub log_entries: bool,
+    /// Tar entries of the first input to read past without parsing.
+    pub skip_entries: usize,
+    /// `--append` run number, spliced into every output stem
+    /// (`accounts_{run}_{i}`) so earlier runs' files are kept.
+    pub run: Option<u32>,
+}
+
+impl PipelineOptions {
+    /// `_{run}` for an `--append` run, otherwise empty.
+    pub fn run_tag(&self) -> String {
+        self.run.map(|run| format!("_{run}")).unwrap_or_default()
+    }
+}
 
 pub struct PipelineStats {
+    /// Compressed bytes read from the inputs.
     pub bytes_read: AtomicU64,
+    /// `bytes_read` when `--skip-entries` finished skipping.
+    pub bytes_skipped: AtomicU64,
+    /// Decompressed bytes handed to the parsers.
+    pub bytes_decompressed: AtomicU64,
     pub rows_parsed: AtomicU64,
+    pub rows_matched: AtomicU64,
+    /// Every account header parsed, before filters; drives the total estimate.
+    pub accounts_seen: AtomicU64,
+    /// Lamports summed over `accounts_seen`. Every stored version of an
+    /// account counts, so this overstates the supply until deduplicated.
+    pub total_lamports: AtomicU64,
     pub parser_blocked_tx: AtomicU64,
     pub parser_blocked_decoded: AtomicU64,
     pub writer_starved_acct: AtomicU64,
     pub writer_starved_decoded: AtomicU64,
+    /// Per parser, the blocked fraction of its recent account-channel and
+    /// decoded-channel sends (`SendWindow`), refreshed after every buffer.
+    pub send_blocked: Mutex<Vec<(f64, f64)>>,
+    /// Account batches parsers sent / account writers received.
+    pub batches_sent: AtomicU64,
+    pub batches_written: AtomicU64,
+    pub decoded_batches_written: AtomicU64,
+    /// Rows written per decoder output, updated by the decoded writers.
+    pub decoded_rows: Mutex<BTreeMap<&'static str, u64>>,
+    /// Last observed length of the raw (decompressor → parsers) and account
+    /// (parsers → writers) channels, updated by their receivers.
+    pub raw_queue_len: AtomicU64,
+    pub acct_queue_len: AtomicU64,
+    /// Per-decoder `Decoder::skipped()` totals, merged once per parser at exit.
+    pub decoder_skipped: Mutex<BTreeMap<&'static str, u64>>,
+    /// Per-decoder count of accounts that failed to decode, merged the same way.
+    pub decoder_errors: Mutex<BTreeMap<&'static str, u64>>,
+    /// Resident set size (bytes) once all parsers / all writers have exited.
+    pub rss_after_parse: AtomicU64,
+    pub rss_after_write: AtomicU64,
     pub finished: AtomicBool,
 }
 
+impl Default for PipelineStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PipelineStats {
     pub fn new() -> Self {
         Self {
             bytes_read: AtomicU64::new(0),
+            bytes_skipped: AtomicU64::new(0),
+            bytes_decompressed: AtomicU64::new(0),
             rows_parsed: AtomicU64::new(0),
+            rows_matched: AtomicU64::new(0),
+            accounts_seen: AtomicU64::new(0),
+            total_lamports: AtomicU64::new(0),
             parser_blocked_tx: AtomicU64::new(0),
             parser_blocked_decoded: AtomicU64::new(0),
             writer_starved_acct: AtomicU64::new(0),
             writer_starved_decoded: AtomicU64::new(0),
+            send_blocked: Mutex::new(Vec::new()),
+            batches_sent: AtomicU64::new(0),
+            batches_written: AtomicU64::new(0),
+            decoded_batches_written: AtomicU64::new(0),
+            decoded_rows: Mutex::new(BTreeMap::new()),
+            raw_queue_len: AtomicU64::new(0),
+            acct_queue_len: AtomicU64::new(0),
+            decoder_skipped: Mutex::new(BTreeMap::new()),
+            decoder_errors: Mutex::new(BTreeMap::new()),
+            rss_after_parse: AtomicU64::new(0),
+            rss_after_write: AtomicU64::new(0),
             finished: AtomicBool::new(false),
         }
     }
+
+    fn progress(&self, elapsed: Duration, finished: bool) -> Progress {
+        Progress {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_skipped: self.bytes_skipped.load(Ordering::Relaxed),
+            bytes_decompressed: self.bytes_decompressed.load(Ordering::Relaxed),
+            accounts_seen: self.accounts_seen.load(Ordering::Relaxed),
+            rows_parsed: self.rows_parsed.load(Ordering::Relaxed),
+            decoded_rows: self.decoded_rows.lock().unwrap().clone(),
+            send_blocked: mean_send_blocked(&self.send_blocked.lock().unwrap()),
+            elapsed,
+            finished,
+        }
+    }
+}
+
+fn mean_send_blocked(per_parser: &[(f64, f64)]) -> (f64, f64) {
+    if per_parser.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = per_parser.len() as f64;
+    let (acct, decoded) = per_parser
+        .iter()
+        .fold((0.0, 0.0), |(a, d), &(pa, pd)| (a + pa, d + pd));
+    (acct / n, decoded / n)
+}
+
+/// Point-in-time view of a run, handed to a progress callback.
+pub struct Progress {
+    pub bytes_read: u64,
+    /// Part of `bytes_read` spent on `--skip-entries`; 0 until skipping ends.
+    pub bytes_skipped: u64,
+    pub bytes_decompressed: u64,
+    pub accounts_seen: u64,
+    /// Accounts written (or checked / counted outside `run`).
+    pub rows_parsed: u64,
+    pub decoded_rows: BTreeMap<&'static str, u64>,
+    /// Blocked fraction of recent account / decoded sends, mean over parsers.
+    pub send_blocked: (f64, f64),
+    pub elapsed: Duration,
+    /// Set on the last call only, once every stage has exited.
+    pub finished: bool,
+}
+
+pub type ProgressFn = Box<dyn Fn(Progress) + Send>;
+
+/// Call `callback` every `PROGRESS_INTERVAL` until `stats.finished` is set,
+/// then once more with the final numbers.
+pub fn spawn_progress(stats: Arc<PipelineStats>, callback: ProgressFn) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        loop {
+            let finished = stats.finished.load(Ordering::Acquire);
+            callback(stats.progress(start.elapsed(), finished));
+            if finished {
+                break;
+            }
+            std::thread::sleep(PROGRESS_INTERVAL);
+        }
+    })
+}
+
+/// Output destination; `io::sink()` when output is discarded.
+fn create_output(path: &Path, discard: bool) -> std::io::Result<Box<dyn Write + Send>> {
+    if discard {
+        Ok(Box::new(std::io::sink()))
+    } else {
+        Ok(Box::new(File::create(path)?))
+    }
+}
+
+const ROW_GROUP_SIZE: usize = 1_000_000;
+
+/// Buffered account rows, across all writers, past which `--sort-by-pubkey`
+/// warns that it may run out of memory. A mainnet snapshot holds about a
+/// billion accounts, ~136 GB of headers.
+const SORT_MEMORY_WARN: u64 = 16 << 30;
+
+/// Page-level min/max statistics are always on. With `bloom_pubkey`, every
+/// row group also gets a Bloom filter on `pubkey` so `WHERE pubkey = ?` can
+/// skip row groups. Pubkeys are random, so min/max alone can't prune them.
+/// At one distinct pubkey per row and the default 5% false-positive rate,
+/// the filter is ~1 MiB per 1M-row group.
+///
+/// `sorted_by_pubkey` records that rows are in ascending `pubkey` order
+/// (column 0 of the accounts schema), so readers can rely on the min/max
+/// statistics for range pruning.
+///
+/// `metadata` goes into the footer's key-value metadata (`run_metadata`).
+fn writer_props(
+    bloom_pubkey: bool,
+    sorted_by_pubkey: bool,
+    metadata: &[KeyValue],
+) -> WriterProperties {
+    let pubkey = ColumnPath::from("pubkey");
+    WriterProperties::builder()
+        .set_dictionary_enabled(false)
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(ROW_GROUP_SIZE)
+        .set_statistics_enabled(EnabledStatistics::Page)
+        .set_column_bloom_filter_enabled(pubkey.clone(), bloom_pubkey)
+        .set_column_bloom_filter_ndv(pubkey, ROW_GROUP_SIZE as u64)
+        .set_sorting_columns(sorted_by_pubkey.then(|| vec![SortingColumn::new(0, false, false)]))
+        .set_key_value_metadata(Some(metadata.to_vec()))
+        .build()
+}
+
+/// How a run's parquet files were produced, for readers without the
+/// manifest: `ssp.source` (the inputs, space-separated), `ssp.slot` (the
+/// newest slot in their names, when there is one), `ssp.filters` (as
+/// `ssp` flags) and `ssp.version`.
+pub fn run_metadata(inputs: &[String], filters: &ResolvedFilters) -> Vec<KeyValue> {
+    let slot = inputs.iter().filter_map(|i| rpc::snapshot_slot(i)).max();
+    let mut metadata = vec![KeyValue::new("ssp.source".into(), inputs.join(" "))];
+    if let Some(slot) = slot {
+        metadata.push(KeyValue::new("ssp.slot".into(), slot.to_string()));
+    }
+    metadata.push(KeyValue::new("ssp.filters".into(), filters.to_string()));
+    metadata.push(KeyValue::new(
+        "ssp.version".into(),
+        env!("CARGO_PKG_VERSION").to_string(),
+    ));
+    metadata
+}
+
+/// Open `{stem}.parquet` or `{stem}.arrow`, depending on `opts.format`.
+/// `sorted_by_pubkey` and `metadata` are passed on to `writer_props`.
+fn create_writer(
+    stem: &Path,
+    schema: SchemaRef,
+    opts: &PipelineOptions,
+    sorted_by_pubkey: bool,
+    metadata: &[KeyValue],
+) -> anyhow::Result<Box<dyn AccountWriter>> {
+    // Not `with_extension`: stems like `{name}.mismatch_0` already have a dot.
+    let mut path = stem.as_os_str().to_owned();
+    path.push(format!(".{}", opts.format.extension()));
+    let file = create_output(Path::new(&path), opts.discard_output)?;
+    Ok(match opts.format {
+        OutputFormat::Parquet => {
+            let props = writer_props(opts.bloom_pubkey, sorted_by_pubkey, metadata);
+            Box::new(ArrowWriter::try_new(file, schema, Some(props))?)
+        }
+        OutputFormat::ArrowIpc => Box::new(FileWriter::try_new(file, &schema)?),
+    })
+}
+
+pub const PARTITION_DIR: &str = "by_owner";
+/// Owner directories across all account writers. Owners seen after that
+/// share `by_owner/other/`, so a snapshot's long tail of programs can't
+/// exhaust file handles.
+const MAX_OWNER_PARTITIONS: usize = 512;
+const OTHER_PARTITION: &str = "other";
+
+/// One account writer thread's `--partition-by owner` output: a writer per
+/// owner under `by_owner/<owner>/`, for each owner in `admitted`.
+struct OwnerPartitions {
+    /// `by_owner` in the run's output directory.
+    root: PathBuf,
+    stem: String,
+    schema: SchemaRef,
+    /// Owners with their own directory, shared by every account writer: the
+    /// first `MAX_OWNER_PARTITIONS` any writer sees, whichever writer it was.
+    admitted: Arc<Mutex<HashSet<Pubkey>>>,
+    metadata: Arc<[KeyValue]>,
+    writers: HashMap<Pubkey, Box<dyn AccountWriter>>,
+    other: Option<Box<dyn AccountWriter>>,
+}
+
+impl OwnerPartitions {
+    fn new(
+        out_dir: &Path,
+        stem: String,
+        schema: SchemaRef,
+        admitted: Arc<Mutex<HashSet<Pubkey>>>,
+        metadata: Arc<[KeyValue]>,
+    ) -> Self {
+        Self {
+            root: out_dir.join(PARTITION_DIR),
+            stem,
+            schema,
+            admitted,
+            metadata,
+            writers: HashMap::new(),
+            other: None,
+        }
+    }
+
+    /// `by_owner/{dir}/{stem}`, creating the directory.
+    fn open(
+        root: &Path,
+        dir: &str,
+        stem: &str,
+        schema: &SchemaRef,
+        opts: &PipelineOptions,
+        metadata: &[KeyValue],
+    ) -> anyhow::Result<Box<dyn AccountWriter>> {
+        let dir = root.join(dir);
+        if !opts.discard_output {
+            std::fs::create_dir_all(&dir)?;
+        }
+        create_writer(&dir.join(stem), schema.clone(), opts, false, metadata)
+    }
+
+    fn write(&mut self, headers: &[AccountHeader], opts: &PipelineOptions) -> anyhow::Result<()> {
+        let mut by_owner: HashMap<Pubkey, Vec<AccountHeader>> = HashMap::new();
+        for header in headers {
+            by_owner.entry(header.owner).or_default().push(*header);
+        }
+
+        let mut other = Vec::new();
+        for (owner, rows) in by_owner {
+            let writer = match self.writers.entry(owner) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(_) if !admit(&self.admitted, owner) => {
+                    other.extend(rows);
+                    continue;
+                }
+                Entry::Vacant(e) => {
+                    let dir = owner.to_string();
+                    e.insert(Self::open(
+                        &self.root,
+                        &dir,
+                        &self.stem,
+                        &self.schema,
+                        opts,
+                        &self.metadata,
+                    )?)
+                }
+            };
+            let batch = record_batch::build_record_batch(&rows, opts.write_version)?;
+            writer.write(&batch)?;
+        }
+
+        if !other.is_empty() {
+            if self.other.is_none() {
+                let writer = Self::open(
+                    &self.root,
+                    OTHER_PARTITION,
+                    &self.stem,
+                    &self.schema,
+                    opts,
+                    &self.metadata,
+                )?;
+                self.other = Some(writer);
+            }
+            if let Some(writer) = &mut self.other {
+                let batch = record_batch::build_record_batch(&other, opts.write_version)?;
+                writer.write(&batch)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn close(self) -> anyhow::Result<()> {
+        for writer in self.writers.into_values().chain(self.other) {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `owner` gets its own directory: it already has one, or fewer than
+/// `MAX_OWNER_PARTITIONS` owners do.
+fn admit(admitted: &Mutex<HashSet<Pubkey>>, owner: Pubkey) -> bool {
+    let mut admitted = admitted.lock().unwrap();
+    admitted.contains(&owner) || (admitted.len() < MAX_OWNER_PARTITIONS && admitted.insert(owner))
+}
+
+type DecodedWriters = HashMap<&'static str, (SchemaRef, Box<dyn AccountWriter>)>;
+
+/// `name`'s writer in `writers`, created at `stem` with `batch`'s schema on
+/// first use. The schema is returned so callers can check later batches.
+fn writer_for<'a>(
+    writers: &'a mut DecodedWriters,
+    name: &'static str,
+    stem: PathBuf,
+    batch: &RecordBatch,
+    opts: &PipelineOptions,
+    metadata: &[KeyValue],
+) -> anyhow::Result<&'a mut (SchemaRef, Box<dyn AccountWriter>)> {
+    Ok(match writers.entry(name) {
+        Entry::Occupied(e) => e.into_mut(),
+        Entry::Vacant(e) => {
+            let writer = create_writer(&stem, batch.schema(), opts, false, metadata)?;
+            e.insert((batch.schema(), writer))
+        }
+    })
+}
+
+/// Close a decoded writer thread's outputs, including its schema-mismatch
+/// files.
+fn close_decoded(writers: Mutex<(DecodedWriters, DecodedWriters)>) -> anyhow::Result<()> {
+    let (writers, mismatched) = writers.into_inner().unwrap();
+    for (_, (_, writer)) in writers.into_iter().chain(mismatched) {
+        writer.close()?;
+    }
+    Ok(())
+}
+
+/// Concatenate `accounts_{0..parts}.parquet` in `out_dir` into
+/// `accounts.parquet` and remove the parts.
+///
+/// This runs after the writers close instead of sharing one `ArrowWriter`
+/// behind a mutex: the mutex would serialize parquet encoding on the hot path
+/// (with two writers, roughly half the write throughput for the whole run),
+/// while the merge is a single extra read + re-encode of the accounts output
+/// once parsing is already done.
+fn merge_accounts(
+    out_dir: &Path,
+    parts: usize,
+    schema: SchemaRef,
+    bloom_pubkey: bool,
+    metadata: &[KeyValue],
+) -> anyhow::Result<()> {
+    let file = File::create(out_dir.join("accounts.parquet"))?;
+    let props = writer_props(bloom_pubkey, false, metadata);
+    let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
+    for i in 0..parts {
+        let path = out_dir.join(format!("accounts_{i}.parquet"));
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?.build()?;
+        for batch in reader {
+            writer.write(&batch?)?;
+        }
+    }
+    writer.close()?;
+
+    for i in 0..parts {
+        std::fs::remove_file(out_dir.join(format!("accounts_{i}.parquet")))?;
+    }
+    Ok(())
+}
+
+/// Write `schemas.json`: the Arrow schema of `accounts` and of every decoder
+/// table, keyed by the parquet file stem, in arrow-schema's serde form.
+fn write_schemas(
+    out_dir: &Path,
+    decoders: &[Box<dyn decoders::Decoder>],
+    write_version: bool,
+) -> anyhow::Result<()> {
+    let mut out = serde_json::Map::new();
+    out.insert(
+        "accounts".into(),
+        serde_json::to_value(record_batch::account_schema(write_version))?,
+    );
+    for dec in decoders {
+        for (name, schema) in dec.schemas() {
+            out.insert(name.into(), serde_json::to_value(&*schema)?);
+        }
+    }
+    let file = File::create(out_dir.join("schemas.json"))?;
+    serde_json::to_writer_pretty(file, &out)?;
+    Ok(())
 }
 
 struct CountingReader<R> {
@@ -59,33 +551,269 @@ impl<R: Read> Read for CountingReader<R> {
     }
 }
 
-pub fn run(
-    reader: impl Read + Send + 'static,
+/// Tar entry names longer than this are cut in `--verbose` entry lines.
+const ENTRY_NAME_WIDTH: usize = 60;
+
+fn log_entry(name: &str, size: usize, accounts: bool) {
+    let name: String = name.chars().take(ENTRY_NAME_WIDTH).collect();
+    let kind = if accounts { "accounts" } else { "skipped" };
+    // Clear the progress line first so the two don't interleave.
+    eprintln!("\r\x1b[2K  {name:<ENTRY_NAME_WIDTH$} {size:>12}  {kind}");
+}
+
+/// Stage 1 thread: decompress `readers` one after another and send their
+/// accounts files on `raw_tx`.
+fn spawn_decompressor(
+    readers: Vec<Box<dyn Read + Send>>,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+    raw_tx: channel::Sender<(String, Vec<u8>)>,
+    recycle_rx: channel::Receiver<Vec<u8>>,
+) -> JoinHandle<anyhow::Result<()>> {
+    std::thread::spawn(move || -> anyhow::Result<()> {
+        let _span = info_span!("decompress").entered();
+        for (i, reader) in readers.into_iter().enumerate() {
+            let mut reader = CountingReader {
+                inner: reader,
+                stats: stats.clone(),
+            };
+            // Only the first input is skipped into.
+            let skip = if i == 0 { opts.skip_entries } else { 0 };
+            let mut seen = 0;
+            let on_entry = |name: &str, size: usize, accounts: bool| {
+                // The first entry past the skipped ones: progress restarts here.
+                if skip > 0 && seen == skip {
+                    let read = stats.bytes_read.load(Ordering::Relaxed);
+                    stats.bytes_skipped.store(read, Ordering::Relaxed);
+                }
+                seen += 1;
+                if opts.log_entries {
+                    log_entry(name, size, accounts);
+                }
+            };
+            let end = AccountHeader::stream_raw(
+                &mut reader,
+                opts.buf_size,
+                opts.zstd_window_log,
+                skip,
+                raw_tx.clone(),
+                recycle_rx.clone(),
+                (opts.log_entries || skip > 0).then_some(on_entry),
+            )?;
+            if end == StreamEnd::Stopped {
+                break;
+            }
+            // Read what follows the end-of-archive blocks (tar record padding,
+            // the rest of the zstd stream) so a `Sha256Reader` sees every byte.
+            std::io::copy(&mut reader, &mut std::io::sink())?;
+        }
+        Ok(())
+    })
+}
+
+/// Violations past this many are counted but not printed.
+const MAX_LOGGED_VIOLATIONS: u64 = 100;
+
+/// `--validate`: run every account header through `AccountHeader::check`
+/// without filtering, decoding or writing anything.
+/// Returns `(accounts checked, violations found)`.
+pub fn validate(
+    readers: Vec<Box<dyn Read + Send>>,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+) -> anyhow::Result<(u64, u64)> {
+    let (raw_tx, raw_rx) = channel::bounded::<(String, Vec<u8>)>(DEFAULT_CHANNEL_SIZE);
+    let (recycle_tx, recycle_rx) = channel::bounded(1024);
+    let decompress = spawn_decompressor(readers, stats.clone(), opts, raw_tx, recycle_rx);
+
+    // Header checks are far cheaper than decompression; one thread keeps up.
+    let mut checked = 0;
+    let mut violations = 0;
+    while let Ok((entry, buf)) = raw_rx.recv() {
+        stats
+            .bytes_decompressed
+            .fetch_add(buf.len() as u64, Ordering::Relaxed);
+        let n = AccountHeader::validate_accounts(&buf, |header, what| {
+            violations += 1;
+            if violations <= MAX_LOGGED_VIOLATIONS {
+                eprintln!("\r\x1b[2K  {entry}: {}: {what}", header.pubkey);
+            }
+        });
+        if let Err(e) = AccountHeader::verify_alignment(&buf, Some(n as usize)) {
+            violations += 1;
+            if violations <= MAX_LOGGED_VIOLATIONS {
+                eprintln!("\r\x1b[2K  {entry}: alignment: {e}");
+            }
+        }
+        checked += n;
+        stats.rows_parsed.fetch_add(n, Ordering::Relaxed);
+        let _ = recycle_tx.send(buf);
+    }
+
+    decompress.join().expect("decompressor panicked")?;
+    stats.finished.store(true, Ordering::Release);
+    Ok((checked, violations))
+}
+
+/// Walk every filtered account header across `num_parsers` threads, reading
+/// only headers — no decoding, no output files. Each thread folds into its
+/// own `T` with `record`; `merge` combines them once the threads exit.
+fn fold_headers<T: Default + Send + 'static>(
+    readers: Vec<Box<dyn Read + Send>>,
     filters: ResolvedFilters,
     stats: Arc<PipelineStats>,
-) -> anyhow::Result<()> {
-    let known_mints = Arc::new(known_mints::load());
+    opts: PipelineOptions,
+    record: fn(&mut T, &AccountHeader),
+    merge: fn(&mut T, T),
+) -> anyhow::Result<T> {
+    let (raw_tx, raw_rx) = channel::bounded::<(String, Vec<u8>)>(opts.channel_size);
+    let (recycle_tx, recycle_rx) = channel::bounded(1024);
+    let decompress = spawn_decompressor(readers, stats.clone(), opts, raw_tx, recycle_rx);
 
-    let reader = CountingReader {
-        inner: reader,
-        stats: stats.clone(),
-    };
+    let filters = Arc::new(filters);
+    let folders: Vec<_> = (0..opts.num_parsers)
+        .map(|_| {
+            let raw_rx = raw_rx.clone();
+            let recycle_tx = recycle_tx.clone();
+            let filters = filters.clone();
+            let stats = stats.clone();
 
-    // Stage 1: zstd → tar → raw buffers
-    let (raw_tx, raw_rx) = channel::bounded::<Vec<u8>>(128);
+            std::thread::spawn(move || {
+                let mut acc = T::default();
+                while let Ok((_, buf)) = raw_rx.recv() {
+                    stats
+                        .bytes_decompressed
+                        .fetch_add(buf.len() as u64, Ordering::Relaxed);
+                    let n = AccountHeader::walk_matching(&buf, &filters, |h| record(&mut acc, h));
+                    stats.rows_parsed.fetch_add(n, Ordering::Relaxed);
+                    let _ = recycle_tx.send(buf);
+                }
+                acc
+            })
+        })
+        .collect();
+    drop(raw_rx);
+
+    decompress.join().expect("decompressor panicked")?;
+    let mut total = T::default();
+    for h in folders {
+        merge(&mut total, h.join().expect("parser panicked"));
+    }
+
+    stats.finished.store(true, Ordering::Release);
+    Ok(total)
+}
+
+/// `--count-only`: every account header in `readers`, before filters (dead
+/// accounts and repeated writes included). The header walk of `--print-owners`
+/// with nothing folded; `filters` are evaluated but don't change the count.
+pub fn count_accounts(
+    readers: Vec<Box<dyn Read + Send>>,
+    filters: ResolvedFilters,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+) -> anyhow::Result<u64> {
+    fold_headers(
+        readers,
+        filters,
+        stats.clone(),
+        opts,
+        |_: &mut (), _| {},
+        |_, _| {},
+    )?;
+    Ok(stats.rows_parsed.load(Ordering::Relaxed))
+}
+
+/// `--print-owners`: accounts per owner.
+pub fn owner_counts(
+    readers: Vec<Box<dyn Read + Send>>,
+    filters: ResolvedFilters,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+) -> anyhow::Result<HashMap<Pubkey, u64>> {
+    fold_headers(
+        readers,
+        filters,
+        stats,
+        opts,
+        |owners, h| *owners.entry(h.owner).or_default() += 1,
+        |owners, part| {
+            for (owner, n) in part {
+                *owners.entry(owner).or_default() += n;
+            }
+        },
+    )
+}
+
+/// Accounts per exact `data_len`, per owner.
+pub type OwnerSizes = HashMap<Pubkey, HashMap<u64, u64>>;
+
+/// `--stats-only`: accounts per (owner, `data_len`).
+pub fn owner_size_counts(
+    readers: Vec<Box<dyn Read + Send>>,
+    filters: ResolvedFilters,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+) -> anyhow::Result<OwnerSizes> {
+    fold_headers(
+        readers,
+        filters,
+        stats,
+        opts,
+        |owners: &mut OwnerSizes, h| {
+            *owners
+                .entry(h.owner)
+                .or_default()
+                .entry(h.data_len)
+                .or_default() += 1;
+        },
+        |owners, part| {
+            for (owner, sizes) in part {
+                let into = owners.entry(owner).or_default();
+                for (size, n) in sizes {
+                    *into.entry(size).or_default() += n;
+                }
+            }
+        },
+    )
+}
+
+/// Stages 1 and 2 of a run, up and running: the decompressor, the parsers,
+/// and the receiving ends of their account and decoded channels.
+struct ParseStages {
+    decompress: JoinHandle<anyhow::Result<()>>,
+    parsers: Vec<JoinHandle<anyhow::Result<SizeHistogram>>>,
+    rx: channel::Receiver<Vec<AccountHeader>>,
+    decoded_rx: channel::Receiver<(&'static str, RecordBatch)>,
+}
+
+fn spawn_parse_stages(
+    readers: Vec<Box<dyn Read + Send>>,
+    filters: ResolvedFilters,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+    known_mints: Arc<HashSet<Pubkey>>,
+) -> ParseStages {
+    // Stage 1: zstd → tar → raw buffers, one input after another
+    let (raw_tx, raw_rx) = channel::bounded::<(String, Vec<u8>)>(opts.channel_size);
     let (recycle_tx, recycle_rx) = channel::bounded(1024);
 
-    let decompress =
-        std::thread::spawn(move || AccountHeader::stream_raw(reader, raw_tx, recycle_rx));
+    let decompress = spawn_decompressor(readers, stats.clone(), opts, raw_tx, recycle_rx);
 
     // Stage 2: parse raw buffers → account headers + decoded batches
-    let (tx, rx) = channel::bounded::<Vec<AccountHeader>>(128);
-    let (decoded_tx, decoded_rx) = channel::bounded::<(&'static str, RecordBatch)>(256);
+    let (tx, rx) = channel::bounded::<Vec<AccountHeader>>(opts.channel_size);
+    let (decoded_tx, decoded_rx) = match opts.decoded_channel {
+        DecodedChannel::Bounded(size) => channel::bounded::<(&'static str, RecordBatch)>(size),
+        // `is_full` is always false here, so the blocked-decoded counters
+        // stay at zero.
+        DecodedChannel::Unbounded => channel::unbounded(),
+    };
 
     let filters = Arc::new(filters);
 
-    let parsers: Vec<_> = (0..NUM_PARSERS)
-        .map(|_| {
+    *stats.send_blocked.lock().unwrap() = vec![(0.0, 0.0); opts.num_parsers];
+    let parsers: Vec<_> = (0..opts.num_parsers)
+        .map(|i| {
             let raw_rx = raw_rx.clone();
             let tx = tx.clone();
             let decoded_tx = decoded_tx.clone();
@@ -94,67 +822,189 @@ pub fn run(
             let recycle_tx = recycle_tx.clone();
             let known_mints = known_mints.clone();
 
-            std::thread::spawn(move || -> anyhow::Result<()> {
-                let mut decoders: Vec<Box<dyn Decoder>> = vec![
-                    Box::new(MintDecoder::new(known_mints.clone())),
-                    Box::new(TokenAccountDecoder::new(known_mints)),
-                ];
-
-                let mut decoder_map: HashMap<Pubkey, Vec<usize>> = HashMap::new();
-                for (i, dec) in decoders.iter().enumerate() {
-                    decoder_map.entry(dec.owner()).or_default().push(i);
+            std::thread::spawn(move || -> anyhow::Result<SizeHistogram> {
+                let _span = info_span!("parser", i).entered();
+                let mut counters = ParseCounters {
+                    sizes: opts.histogram.then(SizeHistogram::default),
+                    ..Default::default()
+                };
+                let mut decoders = decoders::build_default_registry(
+                    known_mints,
+                    opts.aggregate_by_mint,
+                    opts.nfts_only,
+                );
+                if let Some(size) = opts.batch_size {
+                    decoders.set_all_batch_sizes(size);
                 }
 
-                while let Ok(buf) = raw_rx.recv() {
-                    let batch = AccountHeader::parse_accounts(
+                let router = DecoderRouter::new(&decoders);
+                let mut acct_sends = SendWindow::default();
+
+                while let Ok((entry, buf)) = raw_rx.recv() {
+                    stats
+                        .raw_queue_len
+                        .store(raw_rx.len() as u64, Ordering::Relaxed);
+                    stats
+                        .bytes_decompressed
+                        .fetch_add(buf.len() as u64, Ordering::Relaxed);
+                    let seen = counters.accounts;
+                    let lamports = counters.lamports;
+                    let truncated = counters.truncated_entries;
+                    let mut batch = AccountHeader::parse_accounts(
                         &buf,
                         &filters,
                         &mut decoders,
-                        &decoder_map,
+                        &router,
                         &decoded_tx,
                         &stats.parser_blocked_decoded,
+                        &mut counters,
                     );
+                    if counters.truncated_entries > truncated {
+                        warn!("{entry}: account data overruns the entry; skipped the rest");
+                    }
+                    stats
+                        .accounts_seen
+                        .fetch_add(counters.accounts - seen, Ordering::Relaxed);
+                    stats
+                        .total_lamports
+                        .fetch_add(counters.lamports - lamports, Ordering::Relaxed);
+
+                    // Claim our share of the global budget; whoever crosses the
+                    // limit truncates its batch and every parser stops after that.
+                    let prev = stats
+                        .rows_matched
+                        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    let limit_hit = opts.max_accounts.is_some_and(|max| {
+                        batch.truncate(max.saturating_sub(prev) as usize);
+                        prev + batch.len() as u64 >= max
+                    });
+
                     if !batch.is_empty() {
-                        if tx.is_full() {
+                        let full = tx.is_full();
+                        if full {
                             stats.parser_blocked_tx.fetch_add(1, Ordering::Relaxed);
                         }
+                        acct_sends.record(full);
                         tx.send(batch)?;
+                        stats.batches_sent.fetch_add(1, Ordering::Relaxed);
                     }
+                    stats.send_blocked.lock().unwrap()[i] = (
+                        acct_sends.blocked_ratio(),
+                        counters.decoded_sends.blocked_ratio(),
+                    );
                     let _ = recycle_tx.send(buf);
+
+                    if limit_hit {
+                        break;
+                    }
                 }
 
                 for dec in decoders.iter_mut() {
-                    if let Some(batch) = dec.flush() {
-                        let _ = decoded_tx.send((dec.name(), batch));
+                    while let Some(batch) = dec.flush() {
+                        let _ = decoded_tx.send((dec.batch_name(&batch), batch));
                     }
                 }
 
-                Ok(())
+                let mut skipped = stats.decoder_skipped.lock().unwrap();
+                for dec in &decoders {
+                    if dec.skipped() > 0 {
+                        *skipped.entry(dec.name()).or_default() += dec.skipped();
+                    }
+                }
+                drop(skipped);
+
+                let mut errors = stats.decoder_errors.lock().unwrap();
+                for (name, n) in counters.decode_errors {
+                    *errors.entry(name).or_default() += n;
+                }
+                drop(errors);
+
+                Ok(counters.sizes.unwrap_or_default())
             })
         })
         .collect();
 
-    drop(raw_rx);
-    drop(tx);
-    drop(decoded_tx);
+    ParseStages {
+        decompress,
+        parsers,
+        rx,
+        decoded_rx,
+    }
+}
+
+/// Parse `readers` into parquet files in `out_dir`. `progress`, if given, is
+/// called from its own thread every `PROGRESS_INTERVAL` and once more after
+/// the last writer exits. `inputs` names where the readers came from, for
+/// `run_metadata`.
+pub fn run(
+    readers: Vec<Box<dyn Read + Send>>,
+    inputs: &[String],
+    filters: ResolvedFilters,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+    out_dir: &Path,
+    progress: Option<ProgressFn>,
+) -> anyhow::Result<Option<SizeHistogram>> {
+    let reporter = progress.map(|callback| spawn_progress(stats.clone(), callback));
+    let metadata: Arc<[KeyValue]> = run_metadata(inputs, &filters).into();
+    let known_mints = Arc::new(known_mints::load());
+    if !opts.discard_output {
+        write_schemas(
+            out_dir,
+            &decoders::build_default_registry(
+                known_mints.clone(),
+                opts.aggregate_by_mint,
+                opts.nfts_only,
+            ),
+            opts.write_version,
+        )?;
+    }
+
+    let ParseStages {
+        decompress,
+        parsers,
+        rx,
+        decoded_rx,
+    } = spawn_parse_stages(readers, filters, stats.clone(), opts, known_mints);
 
     // Stage 3: write parquet
-    let schema = Arc::new(record_batch::account_schema());
+    let schema = Arc::new(record_batch::account_schema(opts.write_version));
+    let sort_buffered = Arc::new(AtomicU64::new(0));
+    let admitted_owners: Arc<Mutex<HashSet<Pubkey>>> = Arc::default();
 
-    let writers: Vec<_> = (0..NUM_WRITERS)
+    let writers: Vec<_> = (0..opts.num_writers)
         .map(|i| {
             let rx = rx.clone();
             let schema = schema.clone();
             let stats = stats.clone();
+            let sort_buffered = sort_buffered.clone();
+            let metadata = metadata.clone();
+            let out_dir = out_dir.to_path_buf();
+            let admitted_owners = admitted_owners.clone();
 
             std::thread::spawn(move || -> anyhow::Result<()> {
-                let file = File::create(format!("accounts_{i}.parquet"))?;
-                let props = WriterProperties::builder()
-                    .set_dictionary_enabled(false)
-                    .set_compression(Compression::SNAPPY)
-                    .set_max_row_group_size(1_000_000)
-                    .build();
-                let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
+                let _span = info_span!("writer", i).entered();
+                let stem = format!("accounts{}_{i}", opts.run_tag());
+                let mut partitions = (opts.partition_by == Some(PartitionBy::Owner)).then(|| {
+                    OwnerPartitions::new(
+                        &out_dir,
+                        stem.clone(),
+                        schema.clone(),
+                        admitted_owners,
+                        metadata.clone(),
+                    )
+                });
+                let mut writer = match partitions {
+                    Some(_) => None,
+                    None => Some(create_writer(
+                        &out_dir.join(&stem),
+                        schema,
+                        &opts,
+                        opts.sort_by_pubkey,
+                        &metadata,
+                    )?),
+                };
+                let mut sorted = Vec::new();
 
                 while let Ok(batch) = {
                     if rx.is_empty() {
@@ -162,15 +1012,45 @@ pub fn run(
                     }
                     rx.recv()
                 } {
+                    stats.batches_written.fetch_add(1, Ordering::Relaxed);
+                    stats
+                        .acct_queue_len
+                        .store(rx.len() as u64, Ordering::Relaxed);
                     stats
                         .rows_parsed
                         .fetch_add(batch.len() as u64, Ordering::Relaxed);
-                    if !batch.is_empty() {
-                        let record_batch = record_batch::build_record_batch(&batch)?;
+                    if let Some(partitions) = &mut partitions {
+                        partitions.write(&batch, &opts)?;
+                    } else if opts.sort_by_pubkey {
+                        let bytes = (batch.len() * size_of::<AccountHeader>()) as u64;
+                        let prev = sort_buffered.fetch_add(bytes, Ordering::Relaxed);
+                        if prev < SORT_MEMORY_WARN && prev + bytes >= SORT_MEMORY_WARN {
+                            warn!(
+                                "--sort-by-pubkey is holding over {} GiB of accounts in memory",
+                                SORT_MEMORY_WARN >> 30
+                            );
+                        }
+                        sorted.extend_from_slice(&batch);
+                    } else if let Some(writer) = &mut writer
+                        && !batch.is_empty()
+                    {
+                        let record_batch =
+                            record_batch::build_record_batch(&batch, opts.write_version)?;
                         writer.write(&record_batch)?;
                     }
                 }
-                writer.close()?;
+                if let Some(mut writer) = writer {
+                    // Unsigned byte order, the same order parquet compares binary in.
+                    sorted.sort_unstable_by(|a, b| a.pubkey.as_bytes().cmp(b.pubkey.as_bytes()));
+                    for rows in sorted.chunks(ROW_GROUP_SIZE) {
+                        let batch = record_batch::build_record_batch(rows, opts.write_version)?;
+                        writer.write(&batch)?;
+                    }
+                    writer.close()?;
+                }
+                if let Some(partitions) = partitions {
+                    partitions.close()?;
+                }
                 Ok(())
             })
         })
@@ -178,13 +1058,23 @@ pub fn run(
 
     drop(rx);
 
+    // One set of writers for the run with `merge_decoded`, otherwise one per
+    // thread, whose lock is then never contended.
+    let merged_decoded: Option<Arc<Mutex<(DecodedWriters, DecodedWriters)>>> =
+        opts.merge_decoded.then(Arc::default);
     let decoded_writers: Vec<_> = (0..NUM_DECODED_WRITERS)
         .map(|i| {
             let decoded_rx = decoded_rx.clone();
             let stats = stats.clone();
+            let metadata = metadata.clone();
+            let out_dir = out_dir.to_path_buf();
+            let (shared, suffix) = match &merged_decoded {
+                Some(shared) => (shared.clone(), opts.run_tag()),
+                None => (Arc::default(), format!("{}_{i}", opts.run_tag())),
+            };
 
             std::thread::spawn(move || -> anyhow::Result<()> {
-                let mut writers: HashMap<&'static str, ArrowWriter<File>> = HashMap::new();
+                let _span = info_span!("decoded_writer", i).entered();
                 while let Ok((name, batch)) = {
                     if decoded_rx.is_empty() {
                         stats
@@ -193,22 +1083,48 @@ pub fn run(
                     }
                     decoded_rx.recv()
                 } {
-                    let writer = writers.entry(name).or_insert_with(|| {
-                        let file = File::create(format!("{name}_{i}.parquet")).unwrap();
-                        let props = WriterProperties::builder()
-                            .set_dictionary_enabled(false)
-                            .set_compression(Compression::SNAPPY)
-                            .set_max_row_group_size(1_000_000)
-                            .build();
-                        ArrowWriter::try_new(file, batch.schema(), Some(props)).unwrap()
-                    });
-                    writer.write(&batch)?;
+                    stats
+                        .decoded_batches_written
+                        .fetch_add(1, Ordering::Relaxed);
+                    // Held across the write: with `merge_decoded` this is
+                    // what serializes encoding between the writer threads.
+                    let mut guard = shared.lock().unwrap();
+                    let (writers, mismatched) = &mut *guard;
+                    let stem = out_dir.join(format!("{name}{suffix}"));
+                    let (schema, writer) =
+                        writer_for(writers, name, stem, &batch, &opts, &metadata)?;
+                    if batch.schema_ref() == schema {
+                        writer.write(&batch)?;
+                    } else {
+                        // A decoder changed its schema mid-run. Set its rows
+                        // aside rather than failing the writer and losing the rest.
+                        // Not `{name}_…`, so the DuckDB view globs skip it.
+                        let stem = out_dir.join(format!("{name}.mismatch{suffix}"));
+                        if !mismatched.contains_key(name) {
+                            error!(
+                                "{name} batch doesn't match the schema of its first batch; \
+                                 writing those rows to {}",
+                                stem.display()
+                            );
+                        }
+                        let (schema, writer) =
+                            writer_for(mismatched, name, stem, &batch, &opts, &metadata)?;
+                        if batch.schema_ref() != schema {
+                            error!("dropping a {name} batch with a third schema");
+                            continue;
+                        }
+                        writer.write(&batch)?;
+                    }
+                    drop(guard);
+                    *stats.decoded_rows.lock().unwrap().entry(name).or_default() +=
+                        batch.num_rows() as u64;
                 }
 
-                for (_, writer) in writers {
-                    writer.close()?;
+                // Only this thread's own set; a merged one is closed after the join.
+                match Arc::into_inner(shared) {
+                    Some(shared) => close_decoded(shared),
+                    None => Ok(()),
                 }
-                Ok(())
             })
         })
         .collect();
@@ -217,16 +1133,163 @@ pub fn run(
 
     // Join all threads
     decompress.join().expect("decompressor panicked")?;
+    let mut sizes = SizeHistogram::default();
     for h in parsers {
-        h.join().expect("parser panicked")?;
+        sizes.merge(&h.join().expect("parser panicked")?);
     }
+    stats
+        .rss_after_parse
+        .store(mem::current_rss(), Ordering::Relaxed);
     for h in writers {
         h.join().expect("writer panicked")?;
     }
     for h in decoded_writers {
         h.join().expect("decoded writer panicked")?;
     }
+    if let Some(shared) = merged_decoded.and_then(Arc::into_inner) {
+        close_decoded(shared)?;
+    }
+
+    if opts.single_file && !opts.discard_output {
+        merge_accounts(
+            out_dir,
+            opts.num_writers,
+            schema,
+            opts.bloom_pubkey,
+            &metadata,
+        )?;
+    }
+    stats
+        .rss_after_write
+        .store(mem::current_rss(), Ordering::Relaxed);
+
+    stats.finished.store(true, Ordering::Release);
+    if let Some(reporter) = reporter {
+        reporter.join().expect("progress callback panicked");
+    }
+    Ok(opts.histogram.then_some(sizes))
+}
+
+/// What `collect` kept: the accounts batches and each decoder's batches, in
+/// the order the collector threads received them.
+#[derive(Default)]
+pub struct CollectedBatches {
+    pub accounts: Vec<RecordBatch>,
+    pub decoded: BTreeMap<&'static str, Vec<RecordBatch>>,
+    /// Arrow buffer bytes held by all of the above.
+    pub bytes: u64,
+}
+
+/// Parse `readers` like `run`, but keep the record batches in memory instead
+/// of writing files. The collector threads stand in for the writers and push
+/// into shared `Vec`s. Fails once the batches held pass `max_bytes`, which
+/// stops the run: a full snapshot is far bigger than memory.
+///
+/// Only the parsing options apply; the output ones (`single_file`,
+/// `sort_by_pubkey`, `partition_by`, `format`, ...) are ignored.
+pub fn collect(
+    readers: Vec<Box<dyn Read + Send>>,
+    filters: ResolvedFilters,
+    stats: Arc<PipelineStats>,
+    opts: PipelineOptions,
+    max_bytes: u64,
+) -> anyhow::Result<CollectedBatches> {
+    let ParseStages {
+        decompress,
+        parsers,
+        rx,
+        decoded_rx,
+    } = spawn_parse_stages(
+        readers,
+        filters,
+        stats.clone(),
+        opts,
+        Arc::new(known_mints::load()),
+    );
+
+    let collected = Arc::new(Mutex::new(CollectedBatches::default()));
+
+    let mut collectors: Vec<JoinHandle<anyhow::Result<()>>> = (0..opts.num_writers)
+        .map(|i| {
+            let rx = rx.clone();
+            let stats = stats.clone();
+            let collected = collected.clone();
 
+            std::thread::spawn(move || {
+                let _span = info_span!("collector", i).entered();
+                while let Ok(batch) = rx.recv() {
+                    stats
+                        .rows_parsed
+                        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    if batch.is_empty() {
+                        continue;
+                    }
+                    let batch = record_batch::build_record_batch(&batch, opts.write_version)?;
+                    retain(&collected, None, batch, max_bytes)?;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    drop(rx);
+
+    collectors.extend((0..NUM_DECODED_WRITERS).map(|i| {
+        let decoded_rx = decoded_rx.clone();
+        let stats = stats.clone();
+        let collected = collected.clone();
+
+        std::thread::spawn(move || {
+            let _span = info_span!("decoded_collector", i).entered();
+            while let Ok((name, batch)) = decoded_rx.recv() {
+                *stats.decoded_rows.lock().unwrap().entry(name).or_default() +=
+                    batch.num_rows() as u64;
+                retain(&collected, Some(name), batch, max_bytes)?;
+            }
+            Ok(())
+        })
+    }));
+    drop(decoded_rx);
+
+    // A collector over the cap closes its channel, so the parsers and the
+    // decompressor then fail on their sends; report the cap, not those.
+    let decompressed = decompress.join().expect("decompressor panicked");
+    let parsed: Vec<_> = parsers
+        .into_iter()
+        .map(|h| h.join().expect("parser panicked"))
+        .collect();
+    for h in collectors {
+        h.join().expect("collector panicked")?;
+    }
+    decompressed?;
+    for result in parsed {
+        result?;
+    }
+    stats
+        .rss_after_write
+        .store(mem::current_rss(), Ordering::Relaxed);
     stats.finished.store(true, Ordering::Release);
+
+    let collected = Arc::into_inner(collected).expect("collectors joined");
+    Ok(collected.into_inner().unwrap())
+}
+
+/// Add `batch` to `collected`, under `decoded` if it's a decoder's, unless
+/// that takes the total past `max_bytes`.
+fn retain(
+    collected: &Mutex<CollectedBatches>,
+    decoded: Option<&'static str>,
+    batch: RecordBatch,
+    max_bytes: u64,
+) -> anyhow::Result<()> {
+    let mut collected = collected.lock().unwrap();
+    collected.bytes += batch.get_array_memory_size() as u64;
+    anyhow::ensure!(
+        collected.bytes <= max_bytes,
+        "collected batches passed the {max_bytes}-byte cap"
+    );
+    match decoded {
+        Some(name) => collected.decoded.entry(name).or_default().push(batch),
+        None => collected.accounts.push(batch),
+    }
     Ok(())
 }
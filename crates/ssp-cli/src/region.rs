@@ -0,0 +1,61 @@
+//! `--prefer-region`: coarse geolocation of snapshot nodes.
+//!
+//! There's no GeoIP database here, just the IANA IPv4 /8 allocations to the
+//! regional registries. It's wrong for legacy blocks and for hosts announced
+//! outside their registry's region, but it's free and needs no lookups.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Us,
+    Eu,
+    Ap,
+    Any,
+}
+
+/// ARIN /8 blocks.
+const ARIN: &[u8] = &[
+    23, 24, 50, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 96, 97, 98, 99, 100, 104,
+    107, 108, 142, 162, 173, 174, 184, 198, 199, 204, 205, 206, 207, 208, 209, 216,
+];
+
+/// RIPE NCC /8 blocks.
+const RIPE: &[u8] = &[
+    2, 5, 31, 37, 46, 62, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94,
+    95, 109, 141, 145, 151, 176, 178, 185, 188, 193, 194, 195, 212, 213, 217,
+];
+
+/// APNIC /8 blocks.
+const APNIC: &[u8] = &[
+    1, 14, 27, 36, 39, 42, 43, 49, 58, 59, 60, 61, 101, 103, 106, 110, 111, 112, 113, 114, 115,
+    116, 117, 118, 119, 120, 121, 122, 123, 124, 125, 126, 175, 180, 182, 183, 202, 203, 210, 211,
+    218, 219, 220, 221, 222, 223,
+];
+
+/// Best-guess region of an address, `None` when the /8 isn't in the table.
+pub fn region_of(ip: Ipv4Addr) -> Option<Region> {
+    let first = ip.octets()[0];
+    if ARIN.contains(&first) {
+        Some(Region::Us)
+    } else if RIPE.contains(&first) {
+        Some(Region::Eu)
+    } else if APNIC.contains(&first) {
+        Some(Region::Ap)
+    } else {
+        None
+    }
+}
+
+/// Region of the host in a `http://ip:port/...` snapshot URL.
+pub fn region_of_url(url: &str) -> Option<Region> {
+    let host = reqwest::Url::parse(url)
+        .ok()?
+        .host_str()?
+        .parse::<IpAddr>()
+        .ok()?;
+    match host {
+        IpAddr::V4(ip) => region_of(ip),
+        IpAddr::V6(_) => None,
+    }
+}
@@ -7,11 +7,21 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{debug, info};
 
-const DEFAULT_RPC: &str = "https://api.mainnet-beta.solana.com";
+use crate::region::{Region, region_of_url};
+
+pub const DEFAULT_RPC: &str = "https://api.mainnet-beta.solana.com";
+const DEVNET_RPC: &str = "https://api.devnet.solana.com";
+const TESTNET_RPC: &str = "https://api.testnet.solana.com";
 const FULL_SNAPSHOT_PATHS: &[&str] = &["/snapshot.tar.zst", "/snapshot.tar.bz2"];
 const INC_SNAPSHOT_PATHS: &[&str] = &["/incremental-snapshot.tar.zst", "/incremental-snapshot.tar.bz2"];
 const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Every full snapshot path at once.
+pub const DEFAULT_PROBE_CONCURRENCY_PER_NODE: usize = FULL_SNAPSHOT_PATHS.len();
+const ROUGH_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+const FINAL_TEST_TIMEOUT: Duration = Duration::from_secs(30);
 const MAX_CONCURRENT_PROBE: usize = 256;
 const ROUGH_TEST_BYTES: usize = 512 * 1024;
 const ROUGH_TEST_CONCURRENT: usize = 32;
@@ -29,15 +39,57 @@ struct RpcNode {
     pub rpc: Option<String>,
 }
 
+/// Client timeouts for discovery (`--timeout-probe-secs`,
+/// `--timeout-speed-test-secs`). `speed_test: None` keeps each phase's own
+/// limit: 5 s for the rough test, 30 s for the final one.
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    pub probe: Duration,
+    pub speed_test: Option<Duration>,
+    /// Snapshot paths probed at once on each node
+    /// (`--probe-concurrency-per-node`), so a dead node costs about one
+    /// `probe` timeout instead of one per path.
+    pub probe_concurrency_per_node: usize,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            probe: PROBE_TIMEOUT,
+            speed_test: None,
+            probe_concurrency_per_node: DEFAULT_PROBE_CONCURRENCY_PER_NODE,
+        }
+    }
+}
+
 pub struct SnapshotSource {
     pub url: String,
     pub size: Option<u64>,
     pub speed_mbps: f64,
+    /// Position by speed among the sources tested, starting at 1.
+    pub rank: usize,
 }
 
 struct SnapshotCandidate {
     url: String,
     size: Option<u64>,
+    /// How long the node took to answer the snapshot probe.
+    probe_time: Duration,
+}
+
+/// `--min-candidates` and `--max-candidates`: how many probed sources must
+/// be found, and how many of the quickest to answer the probe go on to the
+/// speed test (`None` = all of them).
+#[derive(Clone, Copy, Debug)]
+pub struct CandidateLimits {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Default for CandidateLimits {
+    fn default() -> Self {
+        Self { min: 1, max: None }
+    }
 }
 
 /// Fetches cluster nodes from Solana RPC and returns only nodes that serve RPC.
@@ -67,72 +119,212 @@ async fn get_rpc_nodes(rpc_url: Option<&str>) -> anyhow::Result<Vec<RpcNode>> {
         .filter(|n| n.rpc.is_some())
         .collect();
 
-    eprintln!("found {} RPC nodes out of cluster", rpc_nodes.len());
+    info!("found {} RPC nodes out of cluster", rpc_nodes.len());
     Ok(rpc_nodes)
 }
 
+#[derive(Debug, Deserialize)]
+struct SlotResponse {
+    result: u64,
+}
+
+/// Current confirmed slot of the cluster behind `rpc_url` (`getSlot`).
+pub async fn get_current_slot(rpc_url: &str) -> anyhow::Result<u64> {
+    let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getSlot"
+    });
+
+    let resp: SlotResponse = client
+        .post(rpc_url)
+        .json(&body)
+        .send()
+        .await
+        .context("failed to fetch current slot")?
+        .json()
+        .await
+        .context("failed to parse getSlot response")?;
+
+    Ok(resp.result)
+}
+
+/// Slot a snapshot was taken at, from its archive name:
+/// `snapshot-<slot>-<hash>.tar.zst` or
+/// `incremental-snapshot-<base_slot>-<slot>-<hash>.tar.zst`.
+pub fn snapshot_slot(url_or_path: &str) -> Option<u64> {
+    let name = url_or_path.rsplit('/').next()?;
+    let parts: Vec<&str> = name.split('-').collect();
+    match parts.as_slice() {
+        ["incremental", "snapshot", _base, slot, ..] => slot.parse().ok(),
+        ["snapshot", slot, ..] => slot.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Slot of the full snapshot an archive builds on: the base slot of an
+/// incremental, or a full snapshot's own slot.
+pub fn snapshot_base_slot(url_or_path: &str) -> Option<u64> {
+    let name = url_or_path.rsplit('/').next()?;
+    let parts: Vec<&str> = name.split('-').collect();
+    match parts.as_slice() {
+        ["incremental", "snapshot", base, ..] => base.parse().ok(),
+        ["snapshot", slot, ..] => slot.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Target of the per-probe discovery events, which `--verbose-discovery`
+/// turns on (or `RUST_LOG=ssp::discovery=debug`).
+pub const DISCOVERY_TARGET: &str = "ssp::discovery";
+
+/// How one snapshot path answered a probe.
+enum ProbeOutcome {
+    /// Redirected to this download URL, resolved against the probe URL.
+    Redirect(String),
+    /// Answered without a usable redirect: a 404, a 200, or a 3xx without a
+    /// valid `Location`.
+    Status(u16),
+    /// No answer: connection refused, timeout, TLS failure and the like.
+    Failed(reqwest::Error),
+}
+
 /// HEAD request without following redirects. Any 3xx with Location = snapshot found.
 /// Resolves relative Location headers against the original URL.
-async fn resolve_snapshot_url(client: &Client, base_url: &str) -> Option<String> {
-    let resp = client.head(base_url).send().await.ok()?;
+async fn resolve_snapshot_url(client: &Client, base_url: &str) -> ProbeOutcome {
+    let resp = match client.head(base_url).send().await {
+        Ok(resp) => resp,
+        Err(e) => return ProbeOutcome::Failed(e),
+    };
     let status = resp.status().as_u16();
 
     if (300..400).contains(&status) {
-        let location = resp.headers().get("location")?.to_str().ok()?;
         // Resolve relative redirects (e.g. "/snapshot-123.tar.zst") against original URL
-        let base = reqwest::Url::parse(base_url).ok()?;
-        let resolved = base.join(location).ok()?;
-        return Some(resolved.to_string());
+        let resolved = resp
+            .headers()
+            .get("location")
+            .and_then(|l| l.to_str().ok())
+            .zip(reqwest::Url::parse(base_url).ok())
+            .and_then(|(location, base)| base.join(location).ok());
+        if let Some(resolved) = resolved {
+            return ProbeOutcome::Redirect(resolved.to_string());
+        }
     }
 
-    None
+    ProbeOutcome::Status(status)
 }
 
-/// Probe a single node: try snapshot paths, return first hit.
+/// Probe a single node: try snapshot paths, return first hit. Up to
+/// `concurrency` paths are in flight at once, so a node that never answers
+/// holds its semaphore slot for about one probe timeout rather than one per
+/// path. With paths racing, a node serving several formats may return any of
+/// them; `concurrency` 1 tries them strictly in order.
 async fn probe_node(
     probe_client: &Client,
     size_client: &Client,
     node: &RpcNode,
-    paths: &[&str],
+    paths: &'static [&'static str],
+    concurrency: usize,
 ) -> Option<SnapshotCandidate> {
     let rpc_addr = node.rpc.as_ref()?;
+    // Gossip gives `host:port`; `--rpc-addrs` may carry a scheme.
+    let base = if rpc_addr.contains("://") {
+        rpc_addr.trim_end_matches('/').to_string()
+    } else {
+        format!("http://{rpc_addr}")
+    };
 
-    for path in paths {
-        let probe_url = format!("http://{}{}", rpc_addr, path);
-
-        if let Some(download_url) = resolve_snapshot_url(probe_client, &probe_url).await {
-            let size = size_client
-                .head(&download_url)
-                .send()
-                .await
-                .ok()
-                .and_then(|r| {
-                    r.headers()
-                        .get("content-length")
-                        .and_then(|v| v.to_str().ok())
-                        .and_then(|v| v.parse::<u64>().ok())
-                });
-
-            return Some(SnapshotCandidate {
-                url: download_url,
-                size,
-            });
+    // Dropping the set on return aborts the probes still running.
+    let mut probes = JoinSet::new();
+    let mut pending = paths.iter();
+    loop {
+        while probes.len() < concurrency.max(1) {
+            let Some(path) = pending.next() else {
+                break;
+            };
+            let probe_client = probe_client.clone();
+            let size_client = size_client.clone();
+            let probe_url = format!("{base}{path}");
+            probes.spawn(async move { probe_path(&probe_client, &size_client, &probe_url).await });
+        }
+        if let Ok(Some(candidate)) = probes.join_next().await? {
+            return Some(candidate);
         }
     }
+}
 
-    None
+/// One snapshot path on one node: resolve the redirect, then HEAD the
+/// download URL for its size.
+async fn probe_path(
+    probe_client: &Client,
+    size_client: &Client,
+    probe_url: &str,
+) -> Option<SnapshotCandidate> {
+    let start = Instant::now();
+    let outcome = resolve_snapshot_url(probe_client, probe_url).await;
+    let probe_time = start.elapsed();
+    let ms = probe_time.as_millis() as u64;
+    let download_url = match outcome {
+        ProbeOutcome::Redirect(url) => {
+            debug!(target: DISCOVERY_TARGET, probe = probe_url, ms, location = %url, "redirect");
+            url
+        }
+        ProbeOutcome::Status(status) => {
+            debug!(target: DISCOVERY_TARGET, probe = probe_url, ms, status, "no redirect");
+            return None;
+        }
+        ProbeOutcome::Failed(e) => {
+            let kind = if e.is_timeout() {
+                "timeout"
+            } else if e.is_connect() {
+                "connection error"
+            } else {
+                "request error"
+            };
+            // reqwest's own message is generic; the innermost source says why.
+            let error: &dyn std::error::Error = &e;
+            let cause = std::iter::successors(Some(error), |e| e.source())
+                .last()
+                .unwrap_or(error);
+            debug!(target: DISCOVERY_TARGET, probe = probe_url, ms, error = %cause, "{kind}");
+            return None;
+        }
+    };
+    let size = size_client
+        .head(&download_url)
+        .send()
+        .await
+        .ok()
+        .and_then(|r| {
+            r.headers()
+                .get("content-length")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+        });
+
+    Some(SnapshotCandidate {
+        url: download_url,
+        size,
+        probe_time,
+    })
 }
 
 /// Phase 1: Probe all nodes concurrently for snapshot availability.
-async fn probe_nodes(nodes: &[RpcNode], paths: &'static [&'static str]) -> Vec<SnapshotCandidate> {
+async fn probe_nodes(
+    nodes: &[RpcNode],
+    paths: &'static [&'static str],
+    timeouts: Timeouts,
+) -> Vec<SnapshotCandidate> {
     let probe_client = Client::builder()
-        .timeout(PROBE_TIMEOUT)
+        .timeout(timeouts.probe)
         .redirect(Policy::none())
         .build()
         .expect("failed to build probe client");
 
     let size_client = Client::builder()
-        .timeout(PROBE_TIMEOUT)
+        .timeout(timeouts.probe)
         .build()
         .expect("failed to build size client");
 
@@ -152,7 +344,15 @@ async fn probe_nodes(nodes: &[RpcNode], paths: &'static [&'static str]) -> Vec<S
 
         handles.push(tokio::spawn(async move {
             let _permit = sem.acquire().await.unwrap();
-            let result = probe_node(&probe_client, &size_client, &node, paths).await;
+            let concurrency = timeouts.probe_concurrency_per_node;
+            let result = probe_node(&probe_client, &size_client, &node, paths, concurrency).await;
+            let addr = node.rpc.as_deref().unwrap_or_default();
+            match &result {
+                Some(candidate) => {
+                    debug!(target: DISCOVERY_TARGET, node = addr, url = %candidate.url, "serves")
+                }
+                None => debug!(target: DISCOVERY_TARGET, node = addr, "no snapshot"),
+            }
             probed.fetch_add(1, Ordering::Relaxed);
             if result.is_some() {
                 found.fetch_add(1, Ordering::Relaxed);
@@ -186,8 +386,8 @@ async fn probe_nodes(nodes: &[RpcNode], paths: &'static [&'static str]) -> Vec<S
 
     progress_handle.abort();
     eprint!("\r\x1b[2K");
-    eprintln!(
-        "  probed {} nodes, {} serve snapshots",
+    info!(
+        "probed {} nodes, {} serve snapshots",
         total,
         candidates.len()
     );
@@ -209,12 +409,66 @@ async fn measure_download(client: &Client, url: &str, limit: usize) -> Option<(u
     (total > 0 && elapsed > 0.0).then_some((total, elapsed))
 }
 
-/// Phase 2a: Rough concurrent filter — rank all candidates by downloading a small sample.
-async fn rough_speed_filter(
+/// Move candidates in `region` to the front. When there are enough of them to
+/// fill the shortlist, the rest are dropped so only those get speed tested.
+fn prefer_region(mut candidates: Vec<SnapshotCandidate>, region: Region) -> Vec<SnapshotCandidate> {
+    if region == Region::Any {
+        return candidates;
+    }
+    candidates.sort_by_key(|c| region_of_url(&c.url) != Some(region));
+    let preferred = candidates
+        .iter()
+        .take_while(|c| region_of_url(&c.url) == Some(region))
+        .count();
+    info!(
+        "{preferred} of {} candidates in the preferred region",
+        candidates.len()
+    );
+    if preferred >= ROUGH_TOP_N {
+        candidates.truncate(preferred);
+    }
+    candidates
+}
+
+/// Apply `limits` to the probe results: fail when fewer than `limits.min`
+/// were found, otherwise keep the `limits.max` that answered the probe
+/// quickest.
+fn limit_candidates(
+    mut candidates: Vec<SnapshotCandidate>,
+    nodes: usize,
+    limits: CandidateLimits,
+) -> anyhow::Result<Vec<SnapshotCandidate>> {
+    if candidates.is_empty() {
+        bail!(
+            "no snapshot sources found among {nodes} RPC nodes \
+             (--verbose-discovery shows what each probe got)"
+        );
+    }
+    if candidates.len() < limits.min {
+        bail!(
+            "only {} snapshot sources found among {nodes} RPC nodes, --min-candidates is {}",
+            candidates.len(),
+            limits.min
+        );
+    }
+    if let Some(max) = limits.max
+        && candidates.len() > max
+    {
+        candidates.sort_by_key(|c| c.probe_time);
+        candidates.truncate(max);
+        info!("keeping the {max} candidates quickest to answer the probe");
+    }
+    Ok(candidates)
+}
+
+/// Phase 2a: Rough concurrent test — speed (bytes/s) of every candidate from a
+/// small sample, fastest first. Candidates that failed are dropped.
+async fn rough_speed_rank(
     candidates: Vec<SnapshotCandidate>,
-) -> Vec<SnapshotCandidate> {
+    timeout: Duration,
+) -> Vec<(SnapshotCandidate, f64)> {
     let client = Client::builder()
-        .timeout(Duration::from_secs(5))
+        .timeout(timeout)
         .build()
         .expect("failed to build speed test client");
 
@@ -230,6 +484,9 @@ async fn rough_speed_filter(
                 .await
                 .map(|(bytes, secs)| bytes as f64 / secs)
                 .unwrap_or(0.0);
+            if speed == 0.0 {
+                debug!("{}: rough speed test failed", candidate.url);
+            }
             (i, candidate, speed)
         }));
     }
@@ -244,18 +501,31 @@ async fn rough_speed_filter(
     }
 
     results.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
-    results.truncate(ROUGH_TOP_N);
+    results
+        .into_iter()
+        .map(|(_, c, speed)| (c, speed))
+        .collect()
+}
+
+/// Phase 2a: Rough concurrent filter — keep the `ROUGH_TOP_N` fastest.
+async fn rough_speed_filter(
+    candidates: Vec<SnapshotCandidate>,
+    timeout: Duration,
+) -> Vec<SnapshotCandidate> {
+    let mut ranked = rough_speed_rank(candidates, timeout).await;
+    ranked.truncate(ROUGH_TOP_N);
 
-    eprintln!("  narrowed to top {} candidates", results.len());
-    results.into_iter().map(|(_, c, _)| c).collect()
+    info!("narrowed to top {} candidates", ranked.len());
+    ranked.into_iter().map(|(c, _)| c).collect()
 }
 
 /// Phase 2b: Accurate sequential test — one at a time, large sample.
 async fn final_speed_test(
     candidates: Vec<SnapshotCandidate>,
+    timeout: Duration,
 ) -> Vec<(SnapshotCandidate, f64)> {
     let client = Client::builder()
-        .timeout(Duration::from_secs(30))
+        .timeout(timeout)
         .build()
         .expect("failed to build final speed test client");
 
@@ -264,6 +534,8 @@ async fn final_speed_test(
         if let Some((bytes, secs)) = measure_download(&client, &candidate.url, FINAL_TEST_BYTES).await {
             let mbps = (bytes as f64 / 1_048_576.0) / secs;
             results.push((candidate, mbps));
+        } else {
+            debug!("{}: final speed test failed", candidate.url);
         }
     }
 
@@ -276,43 +548,160 @@ async fn final_speed_test(
 /// 2. Probes all RPC nodes concurrently (HEAD requests)
 /// 3. Speed tests candidates (downloads 1MB sample)
 /// 4. Returns the fastest node
+///
+/// `region` narrows the speed test to nodes in that region when enough exist.
 pub async fn find_fastest_snapshot(
     rpc_url: Option<&str>,
     incremental: bool,
+    region: Region,
+    timeouts: Timeouts,
+    limits: CandidateLimits,
+) -> anyhow::Result<SnapshotSource> {
+    let nodes = get_rpc_nodes(rpc_url).await?;
+    fastest_among(&nodes, incremental, region, timeouts, limits).await
+}
+
+/// Like `find_fastest_snapshot`, but probes `addrs` instead of the cluster's
+/// gossip node list, for private clusters or devnet setups whose nodes
+/// `getClusterNodes` doesn't show. An address is `host:port` or a base URL
+/// such as `http://validator1.example.com`.
+pub async fn find_fastest_snapshot_from(
+    addrs: Vec<String>,
+    incremental: bool,
+    timeouts: Timeouts,
+    limits: CandidateLimits,
+) -> anyhow::Result<SnapshotSource> {
+    fastest_among(
+        &nodes_from_addrs(addrs),
+        incremental,
+        Region::Any,
+        timeouts,
+        limits,
+    )
+    .await
+}
+
+fn nodes_from_addrs(addrs: Vec<String>) -> Vec<RpcNode> {
+    addrs
+        .into_iter()
+        .map(|addr| RpcNode { rpc: Some(addr) })
+        .collect()
+}
+
+/// Discovery settings from the command line: the cluster RPC (`--rpc-url`)
+/// or an explicit node list (`--rpc-addrs`), plus region, timeouts and
+/// candidate limits.
+#[derive(Clone, Debug)]
+pub struct Discovery {
+    pub rpc_url: String,
+    /// When non-empty, probed instead of the cluster's node list.
+    pub addrs: Vec<String>,
+    pub region: Region,
+    pub timeouts: Timeouts,
+    pub candidates: CandidateLimits,
+}
+
+impl Discovery {
+    /// The fastest source, from `addrs` when given, otherwise the cluster.
+    pub async fn find(&self, incremental: bool) -> anyhow::Result<SnapshotSource> {
+        if self.addrs.is_empty() {
+            find_fastest_snapshot(
+                Some(&self.rpc_url),
+                incremental,
+                self.region,
+                self.timeouts,
+                self.candidates,
+            )
+            .await
+        } else {
+            find_fastest_snapshot_from(
+                self.addrs.clone(),
+                incremental,
+                self.timeouts,
+                self.candidates,
+            )
+            .await
+        }
+    }
+
+    /// Every source, ranked, from the same nodes as `find`.
+    pub async fn list(&self, incremental: bool) -> anyhow::Result<Vec<SnapshotSource>> {
+        if self.addrs.is_empty() {
+            list_snapshot_sources(
+                Some(&self.rpc_url),
+                incremental,
+                self.timeouts,
+                self.candidates,
+            )
+            .await
+        } else {
+            list_snapshot_sources_from(
+                self.addrs.clone(),
+                incremental,
+                self.timeouts,
+                self.candidates,
+            )
+            .await
+        }
+    }
+}
+
+/// Parse `--rpc-url`: `mainnet`, `devnet` or `testnet` for the official
+/// endpoints, or any http(s) URL.
+pub fn parse_rpc_url(s: &str) -> Result<String, String> {
+    let url = match s {
+        "mainnet" | "mainnet-beta" => return Ok(DEFAULT_RPC.to_string()),
+        "devnet" => return Ok(DEVNET_RPC.to_string()),
+        "testnet" => return Ok(TESTNET_RPC.to_string()),
+        _ => reqwest::Url::parse(s).map_err(|e| format!("{e}"))?,
+    };
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err("expected an http(s) URL or mainnet, devnet, testnet".into());
+    }
+    Ok(s.to_string())
+}
+
+/// Steps 2-4 of `find_fastest_snapshot` over a given node list.
+async fn fastest_among(
+    nodes: &[RpcNode],
+    incremental: bool,
+    region: Region,
+    timeouts: Timeouts,
+    limits: CandidateLimits,
 ) -> anyhow::Result<SnapshotSource> {
     let paths = if incremental {
         INC_SNAPSHOT_PATHS
     } else {
         FULL_SNAPSHOT_PATHS
     };
-    let nodes = get_rpc_nodes(rpc_url).await?;
-    let candidates = probe_nodes(&nodes, paths).await;
+    let candidates = probe_nodes(nodes, paths, timeouts).await;
+    let candidates = limit_candidates(candidates, nodes.len(), limits)?;
 
-    if candidates.is_empty() {
-        bail!("no snapshot sources found among {} RPC nodes", nodes.len());
-    }
+    let candidates = prefer_region(candidates, region);
 
-    eprintln!("rough speed test on {} candidates...", candidates.len());
-    let shortlist = rough_speed_filter(candidates).await;
+    info!("rough speed test on {} candidates...", candidates.len());
+    let rough_timeout = timeouts.speed_test.unwrap_or(ROUGH_TEST_TIMEOUT);
+    let shortlist = rough_speed_filter(candidates, rough_timeout).await;
 
     if shortlist.is_empty() {
         bail!("all speed tests failed");
     }
 
-    eprintln!("final speed test (sequential, 16MB each)...");
-    let ranked = final_speed_test(shortlist).await;
+    info!("final speed test (sequential, 16MB each)...");
+    let final_timeout = timeouts.speed_test.unwrap_or(FINAL_TEST_TIMEOUT);
+    let ranked = final_speed_test(shortlist, final_timeout).await;
 
     if ranked.is_empty() {
         bail!("all final speed tests failed");
     }
 
     for (i, (candidate, mbps)) in ranked.iter().enumerate() {
-        eprintln!("  #{}: {:.1} MB/s — {}", i + 1, mbps, candidate.url);
+        info!("#{}: {:.1} MB/s — {}", i + 1, mbps, candidate.url);
     }
 
     let (best, speed) = ranked.into_iter().next().unwrap();
 
-    eprintln!(
+    info!(
         "selected: {} ({:.1} MB/s, {:.1} GB)",
         best.url,
         speed,
@@ -323,5 +712,63 @@ pub async fn find_fastest_snapshot(
         url: best.url,
         size: best.size,
         speed_mbps: speed,
+        rank: 1,
     })
 }
+
+/// Every node serving a snapshot, fastest first, for callers that pick a
+/// source themselves. Speeds come from the rough test only (512 KB each),
+/// since the sequential 16 MB test would take minutes across all nodes.
+pub async fn list_snapshot_sources(
+    rpc_url: Option<&str>,
+    incremental: bool,
+    timeouts: Timeouts,
+    limits: CandidateLimits,
+) -> anyhow::Result<Vec<SnapshotSource>> {
+    let nodes = get_rpc_nodes(rpc_url).await?;
+    list_among(&nodes, incremental, timeouts, limits).await
+}
+
+/// `list_snapshot_sources` over `addrs`, as in `find_fastest_snapshot_from`.
+pub async fn list_snapshot_sources_from(
+    addrs: Vec<String>,
+    incremental: bool,
+    timeouts: Timeouts,
+    limits: CandidateLimits,
+) -> anyhow::Result<Vec<SnapshotSource>> {
+    list_among(&nodes_from_addrs(addrs), incremental, timeouts, limits).await
+}
+
+async fn list_among(
+    nodes: &[RpcNode],
+    incremental: bool,
+    timeouts: Timeouts,
+    limits: CandidateLimits,
+) -> anyhow::Result<Vec<SnapshotSource>> {
+    let paths = if incremental {
+        INC_SNAPSHOT_PATHS
+    } else {
+        FULL_SNAPSHOT_PATHS
+    };
+    let candidates = probe_nodes(nodes, paths, timeouts).await;
+    let candidates = limit_candidates(candidates, nodes.len(), limits)?;
+
+    info!("rough speed test on {} candidates...", candidates.len());
+    let rough_timeout = timeouts.speed_test.unwrap_or(ROUGH_TEST_TIMEOUT);
+    let ranked = rough_speed_rank(candidates, rough_timeout).await;
+
+    if ranked.is_empty() {
+        bail!("all speed tests failed");
+    }
+
+    Ok(ranked
+        .into_iter()
+        .enumerate()
+        .map(|(i, (candidate, speed))| SnapshotSource {
+            url: candidate.url,
+            size: candidate.size,
+            speed_mbps: speed / 1_048_576.0,
+            rank: i + 1,
+        })
+        .collect())
+}
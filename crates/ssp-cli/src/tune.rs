@@ -0,0 +1,102 @@
+//! `--tune-pipeline`: sample the pipeline while it runs with output
+//! discarded, then suggest thread counts and buffer sizes.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::pipeline::{PipelineOptions, PipelineStats};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_BUF_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Default)]
+pub struct Samples {
+    count: u64,
+    peak_mbps: f64,
+    /// Sums of per-sample channel fill (0.0–1.0), averaged in `report`.
+    raw_fill: f64,
+    acct_fill: f64,
+}
+
+impl Samples {
+    fn avg(&self, sum: f64) -> f64 {
+        sum / self.count.max(1) as f64
+    }
+}
+
+pub fn spawn_sampler(stats: Arc<PipelineStats>, channel_size: usize) -> JoinHandle<Samples> {
+    std::thread::spawn(move || {
+        let mut samples = Samples::default();
+        let mut last_bytes = 0;
+
+        while !stats.finished.load(Ordering::Acquire) {
+            std::thread::sleep(SAMPLE_INTERVAL);
+
+            let bytes = stats.bytes_read.load(Ordering::Relaxed);
+            let mbps = (bytes - last_bytes) as f64 / SAMPLE_INTERVAL.as_secs_f64() / 1e6;
+            last_bytes = bytes;
+
+            samples.count += 1;
+            samples.peak_mbps = samples.peak_mbps.max(mbps);
+            samples.raw_fill +=
+                stats.raw_queue_len.load(Ordering::Relaxed) as f64 / channel_size as f64;
+            samples.acct_fill +=
+                stats.acct_queue_len.load(Ordering::Relaxed) as f64 / channel_size as f64;
+        }
+        samples
+    })
+}
+
+/// Print per-stage utilization and a copy-pasteable flag suggestion.
+pub fn report(stats: &PipelineStats, samples: &Samples, opts: &PipelineOptions) {
+    let batches = stats.batches_sent.load(Ordering::Relaxed);
+    let blocked = stats.parser_blocked_tx.load(Ordering::Relaxed);
+    let received = stats.batches_written.load(Ordering::Relaxed)
+        + stats.decoded_batches_written.load(Ordering::Relaxed);
+    let starved = stats.writer_starved_acct.load(Ordering::Relaxed)
+        + stats.writer_starved_decoded.load(Ordering::Relaxed);
+    let raw_fill = samples.avg(samples.raw_fill);
+    let acct_fill = samples.avg(samples.acct_fill);
+
+    eprintln!("\npipeline tuning ({} samples):", samples.count);
+    eprintln!("  decompressor     peak {:.0} MB/s", samples.peak_mbps);
+    eprintln!(
+        "  raw channel      {:.0}% full on average",
+        raw_fill * 100.0
+    );
+    eprintln!(
+        "  account channel  {:.0}% full on average",
+        acct_fill * 100.0
+    );
+    eprintln!("  parsers blocked  {blocked} of {batches} batches");
+    eprintln!("  writers starved  {starved} of {received} receives");
+
+    let mut parsers = opts.num_parsers;
+    let mut writers = opts.num_writers;
+    let mut channel_size = opts.channel_size;
+    let mut buf_size = opts.buf_size;
+
+    // Parsers waiting on a full account channel: writers can't keep up.
+    if blocked as f64 > 0.1 * batches as f64 {
+        writers *= 2;
+    }
+    // Writers idle while raw buffers are queued: parsing is the bottleneck.
+    if starved as f64 > 0.5 * received as f64 && raw_fill >= 0.1 {
+        parsers *= 2;
+    }
+    // Queues that sit near capacity are absorbing bursts; give them room.
+    if raw_fill > 0.9 || acct_fill > 0.9 {
+        channel_size *= 2;
+    }
+    // Raw queue almost always empty: the decompressor is the bottleneck.
+    if raw_fill < 0.1 {
+        buf_size = (buf_size * 4).min(MAX_BUF_SIZE);
+    }
+
+    eprintln!(
+        "\nsuggested: --num-parsers {parsers} --num-writers {writers} \
+         --channel-size {channel_size} --buf-size {buf_size}"
+    );
+}
@@ -0,0 +1,312 @@
+//! `--watch` / `--tail`: after the initial run, poll for newer incremental
+//! snapshots and fold each one into the existing parquet output. `--watch`
+//! discovers them over RPC; `--tail` looks in a local directory, such as a
+//! validator's snapshot directory.
+//!
+//! An incremental holds every account written since its base full snapshot,
+//! so one whose base is at or below the slot we already have covers
+//! everything since then. Its output is staged in `STAGING_DIR`, then merged
+//! per table with `DuckDB::merge_incremental` into a single `{name}_0.parquet`
+//! that keeps only each touched account's latest write.
+//! Every `{name}_{i}.parquet` in the working directory is treated as ssp
+//! output and rewritten.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use ssp_core::filters::ResolvedFilters;
+use tracing::{info, warn};
+
+#[cfg(feature = "duckdb")]
+use crate::db;
+use crate::pipeline::{self, PipelineOptions, PipelineStats};
+use crate::{download, rpc};
+
+pub const DEFAULT_INTERVAL_SECS: u64 = 300;
+const STAGING_DIR: &str = ".ssp-watch";
+
+/// Ctrl-C sets the returned flag instead of killing the process, so a parse
+/// in progress finishes and its output is merged before the loop exits.
+pub fn install_ctrlc() -> anyhow::Result<Arc<AtomicBool>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    ctrlc::set_handler(move || {
+        info!("stopping after the current run...");
+        flag.store(true, Ordering::Release);
+    })?;
+    Ok(stop)
+}
+
+/// Where newer incrementals come from.
+pub enum Follow {
+    /// `--watch`: the fastest RPC node serving an incremental.
+    Discover {
+        discovery: rpc::Discovery,
+        download_timeout: Option<Duration>,
+        headers: Vec<(String, String)>,
+        max_retries: u32,
+    },
+    /// `--tail <dir>`: `incremental-snapshot-*.tar.zst` files in a directory.
+    Dir(PathBuf),
+}
+
+/// A newer incremental found by `Watch::poll`, not yet opened.
+struct Candidate {
+    location: String,
+    size: Option<u64>,
+    base: u64,
+    slot: u64,
+}
+
+pub struct Watch {
+    pub interval: Duration,
+    pub follow: Follow,
+    pub filters: ResolvedFilters,
+    pub opts: PipelineOptions,
+    /// The slot the output currently reflects.
+    pub slot: u64,
+    /// The progress line every incremental redraws (see `ProgressBar`).
+    pub bar: Arc<crate::ProgressBar>,
+}
+
+impl Watch {
+    pub fn run(mut self, stop: &AtomicBool) -> anyhow::Result<()> {
+        let rt = tokio::runtime::Runtime::new()?;
+
+        while !stop.load(Ordering::Acquire) {
+            info!(
+                "watching for incrementals after slot {} (every {}s, Ctrl-C to stop)",
+                self.slot,
+                self.interval.as_secs()
+            );
+            if sleep_unless_stopped(self.interval, stop) {
+                break;
+            }
+
+            let candidate = match self.poll(&rt) {
+                Ok(Some(candidate)) => candidate,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("looking for incrementals failed: {e}");
+                    continue;
+                }
+            };
+            if candidate.base > self.slot {
+                warn!(
+                    "incremental at slot {} builds on full snapshot {}, past slot {}; \
+                     accounts written in between would be missed, skipping",
+                    candidate.slot, candidate.base, self.slot
+                );
+                continue;
+            }
+
+            info!(
+                "streaming incremental at slot {} from {}",
+                candidate.slot, candidate.location
+            );
+            // A failed run leaves `self.slot` alone, so the next poll retries.
+            if let Err(e) = self.process(&candidate) {
+                warn!("incremental at slot {} failed: {e:#}", candidate.slot);
+                continue;
+            }
+            self.slot = candidate.slot;
+        }
+        Ok(())
+    }
+
+    /// The newest incremental past `self.slot`, if any. In a directory an
+    /// incremental whose base we can use is preferred over a newer one whose
+    /// base we can't, since the validator may still be writing that base.
+    fn poll(&self, rt: &tokio::runtime::Runtime) -> anyhow::Result<Option<Candidate>> {
+        let candidates = match &self.follow {
+            Follow::Discover { discovery, .. } => {
+                let source = rt.block_on(discovery.find(true))?;
+                vec![(source.url, source.size)]
+            }
+            Follow::Dir(dir) => {
+                let mut found = Vec::new();
+                for entry in std::fs::read_dir(dir)? {
+                    let entry = entry?;
+                    let path = entry.path();
+                    if path.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                        n.starts_with("incremental-snapshot-") && n.ends_with(".tar.zst")
+                    }) {
+                        found.push((
+                            path.to_string_lossy().into_owned(),
+                            Some(entry.metadata()?.len()),
+                        ));
+                    }
+                }
+                found
+            }
+        };
+
+        let mut newer: Vec<Candidate> = Vec::new();
+        for (location, size) in candidates {
+            let (Some(base), Some(slot)) = (
+                rpc::snapshot_base_slot(&location),
+                rpc::snapshot_slot(&location),
+            ) else {
+                warn!("can't read slots from {location}, skipping");
+                continue;
+            };
+            if slot > self.slot {
+                newer.push(Candidate {
+                    location,
+                    size,
+                    base,
+                    slot,
+                });
+            }
+        }
+        Ok(newer
+            .into_iter()
+            .max_by_key(|c| (c.base <= self.slot, c.slot)))
+    }
+
+    fn process(&self, candidate: &Candidate) -> anyhow::Result<()> {
+        let reader: Box<dyn Read + Send> = match &self.follow {
+            Follow::Discover {
+                headers,
+                download_timeout,
+                max_retries,
+                ..
+            } => Box::new(download::RetryingReader::open(
+                crate::download_client(headers, *download_timeout)?,
+                &candidate.location,
+                *max_retries,
+            )?),
+            Follow::Dir(_) => Box::new(std::fs::File::open(&candidate.location)?),
+        };
+        let readers = vec![reader];
+
+        // Dead accounts and every owner are kept so deletions replace the
+        // base rows too: a closed account belongs to the system program.
+        // `merge` applies these filters to each account's latest write.
+        let filters = ResolvedFilters {
+            owner: None,
+            exclude_owners: Default::default(),
+            include_dead: true,
+            ..self.filters.clone()
+        };
+
+        std::fs::create_dir_all(STAGING_DIR)?;
+        for entry in std::fs::read_dir(STAGING_DIR)? {
+            std::fs::remove_file(entry?.path())?;
+        }
+
+        let stats = Arc::new(PipelineStats::new());
+        self.bar.set_total(candidate.size);
+        pipeline::run(
+            readers,
+            std::slice::from_ref(&candidate.location),
+            filters,
+            stats,
+            PipelineOptions {
+                skip_entries: 0,
+                ..self.opts
+            },
+            Path::new(STAGING_DIR),
+            Some(self.bar.callback()),
+        )?;
+
+        self.merge()
+    }
+
+    /// `main` rejects `--watch` / `--tail` without DuckDB before the first run.
+    #[cfg(not(feature = "duckdb"))]
+    fn merge(&self) -> anyhow::Result<()> {
+        anyhow::bail!("merging incrementals needs the `duckdb` feature")
+    }
+
+    #[cfg(feature = "duckdb")]
+    fn merge(&self) -> anyhow::Result<()> {
+        let staging = Path::new(STAGING_DIR);
+        let db = db::DuckDB::open()?;
+        let touched = format!("{STAGING_DIR}/accounts_*.parquet");
+
+        // Tables only in the base still lose the rows of touched accounts,
+        // e.g. a closed multisig when no multisig changed.
+        let mut names: Vec<String> = parquet_files(staging, None)?
+            .iter()
+            .chain(&parquet_files(Path::new("."), None)?)
+            .filter_map(|p| table_name(p))
+            .collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let base = parquet_files(Path::new("."), Some(&name))?;
+            let staged = parquet_files(staging, Some(&name))?;
+            // A table the base run never produced: take the staged files as-is.
+            if base.is_empty() {
+                for path in staged {
+                    std::fs::rename(&path, path.file_name().expect("parquet file name"))?;
+                }
+                continue;
+            }
+
+            let merged = format!("{name}.merged.parquet");
+            let filters = (name == "accounts").then_some(&self.filters);
+            let inc = (!staged.is_empty()).then(|| format!("{STAGING_DIR}/{name}_*.parquet"));
+            let rows = db.merge_incremental(
+                &format!("{name}_*.parquet"),
+                inc.as_deref(),
+                &touched,
+                filters,
+                &merged,
+            )?;
+            for path in base {
+                std::fs::remove_file(path)?;
+            }
+            std::fs::rename(&merged, format!("{name}_0.parquet"))?;
+            info!(
+                "{name}: {} rows after merge",
+                crate::format_rows(rows as u64)
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Sleep for `interval`, waking early on Ctrl-C. True if stopped.
+fn sleep_unless_stopped(interval: Duration, stop: &AtomicBool) -> bool {
+    let step = Duration::from_millis(250);
+    let mut slept = Duration::ZERO;
+    while slept < interval {
+        if stop.load(Ordering::Acquire) {
+            return true;
+        }
+        std::thread::sleep(step);
+        slept += step;
+    }
+    stop.load(Ordering::Acquire)
+}
+
+/// `{name}_{i}.parquet` → `name`. Mismatch files and merged or normalized
+/// outputs don't match.
+#[cfg(feature = "duckdb")]
+fn table_name(path: &Path) -> Option<String> {
+    let stem = path.file_name()?.to_str()?.strip_suffix(".parquet")?;
+    let (name, index) = stem.rsplit_once('_')?;
+    (!name.contains('.') && !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| name.to_string())
+}
+
+/// Writer part files in `dir`, optionally only those of table `name`.
+#[cfg(feature = "duckdb")]
+fn parquet_files(dir: &Path, name: Option<&str>) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(table) = table_name(&path)
+            && name.is_none_or(|n| n == table)
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
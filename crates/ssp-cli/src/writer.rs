@@ -0,0 +1,52 @@
+//! Output file formats behind one trait, so the pipeline's writer threads
+//! don't care whether they're producing Parquet or Arrow IPC.
+
+use arrow::array::RecordBatch;
+use arrow::ipc::writer::FileWriter;
+use parquet::arrow::ArrowWriter;
+use std::io::Write;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Parquet,
+    /// Arrow IPC file (Feather v2): uncompressed, cheap to write and read back.
+    ArrowIpc,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::ArrowIpc => "arrow",
+        }
+    }
+}
+
+pub trait AccountWriter: Send {
+    fn write(&mut self, batch: &RecordBatch) -> anyhow::Result<()>;
+
+    /// Flush and write the footer; the file is unreadable without it.
+    fn close(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+impl<W: Write + Send> AccountWriter for ArrowWriter<W> {
+    fn write(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        Ok(ArrowWriter::write(self, batch)?)
+    }
+
+    fn close(self: Box<Self>) -> anyhow::Result<()> {
+        ArrowWriter::close(*self)?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> AccountWriter for FileWriter<W> {
+    fn write(&mut self, batch: &RecordBatch) -> anyhow::Result<()> {
+        Ok(FileWriter::write(self, batch)?)
+    }
+
+    fn close(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.finish()?;
+        Ok(())
+    }
+}
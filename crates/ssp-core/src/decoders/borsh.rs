@@ -0,0 +1,63 @@
+use crate::Pubkey;
+
+/// Minimal Borsh reader — governance and Metaplex accounts aren't
+/// fixed-size, so they can't be overlaid with bytemuck like the token
+/// program structs.
+pub struct Borsh<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Borsh<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        // Lengths come from the account data, so `n` can be anything.
+        let out = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(out)
+    }
+
+    pub fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn pubkey(&mut self) -> Option<Pubkey> {
+        self.take(32)
+            .map(|b| Pubkey::new(<[u8; 32]>::try_from(b).unwrap()))
+    }
+
+    pub fn option_pubkey(&mut self) -> Option<Option<Pubkey>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => self.pubkey().map(Some),
+            _ => None,
+        }
+    }
+
+    pub fn string(&mut self) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).ok()
+    }
+}
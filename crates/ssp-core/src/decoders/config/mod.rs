@@ -0,0 +1,195 @@
+use arrow::{
+    array::{BinaryBuilder, ListBuilder, RecordBatch, StringBuilder, UInt64Builder},
+    datatypes::{DataType, Field, Schema},
+};
+use std::sync::Arc;
+
+use super::borsh::Borsh;
+use super::{BATCH_THRESHOLD, SchemaRegistry};
+use crate::Pubkey;
+
+/// Config program (`Config1111111111111111111111111111111111111`).
+pub const CONFIG_PROGRAM: Pubkey = Pubkey::new([
+    3, 6, 74, 163, 0, 47, 116, 220, 200, 110, 67, 49, 15, 12, 5, 42, 248, 197, 218, 39, 246, 16,
+    64, 25, 163, 35, 239, 160, 0, 0, 0, 0,
+]);
+
+/// `Va1idator1nfo111111111111111111111111111111`, the first key of every
+/// validator info account. The validator's identity follows as a signer.
+pub const VALIDATOR_INFO_KEY: Pubkey = Pubkey::new([
+    7, 81, 151, 1, 116, 72, 242, 172, 93, 194, 60, 158, 188, 122, 199, 140, 10, 39, 37, 122, 198,
+    20, 69, 141, 224, 164, 241, 111, 128, 0, 0, 0,
+]);
+
+/// Smallest possible config account: an empty key list.
+const MIN_SIZE: u64 = 1;
+
+/// `ConfigKeys` length: a compact-u16 (`short_vec`), 7 bits per byte, low
+/// bits first, at most 3 bytes.
+fn short_vec_len(r: &mut Borsh<'_>) -> Option<usize> {
+    let mut len = 0;
+    for i in 0..3 {
+        let byte = r.u8()?;
+        len |= usize::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return u16::try_from(len).ok().map(usize::from);
+        }
+    }
+    None
+}
+
+/// Fields of one config account, read in full before anything is appended
+/// so a truncated account leaves the builders untouched.
+struct ConfigAccount<'a> {
+    keys: Vec<(Pubkey, bool)>,
+    /// The `ValidatorInfo` JSON, for validator info accounts.
+    info: Option<&'a str>,
+}
+
+impl<'a> ConfigAccount<'a> {
+    /// A bincode-serialized `ConfigKeys`, then the config state. Only the
+    /// validator info state, a `u64`-prefixed string, is read; the rest is
+    /// program-specific (e.g. stake config's rates) and left alone.
+    fn read(r: &mut Borsh<'a>) -> Option<Self> {
+        let n = short_vec_len(r)?;
+        let keys = (0..n)
+            .map(|_| Some((r.pubkey()?, r.u8()? != 0)))
+            .collect::<Option<Vec<_>>>()?;
+        let info = match keys.first() {
+            Some((key, _)) if *key == VALIDATOR_INFO_KEY => {
+                let len = usize::try_from(r.u64()?).ok()?;
+                Some(std::str::from_utf8(r.take(len)?).ok()?)
+            }
+            _ => None,
+        };
+        Some(Self { keys, info })
+    }
+}
+
+/// Decodes accounts of the Config program: validator info and the stake
+/// config. Each row has the account's keys and, for validator info, its
+/// JSON (`name`, `website`, `details`, `keybaseUsername`, ...) as `info`.
+/// Config accounts are allocated with room to spare, so their size says
+/// nothing about the contents; any size is accepted and the length
+/// prefixes are checked instead.
+pub struct ConfigDecoder {
+    schema: Arc<Schema>,
+    rows: usize,
+    batch_threshold: usize,
+    pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
+    keys_b: ListBuilder<BinaryBuilder>,
+    signers_b: ListBuilder<BinaryBuilder>,
+    info_b: StringBuilder,
+}
+
+impl ConfigDecoder {
+    pub fn new() -> Self {
+        let key_field = Field::new("item", DataType::Binary, false);
+        Self {
+            schema: SchemaRegistry::get_or_insert("config_accounts", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("keys", DataType::List(Arc::new(key_field.clone())), false),
+                    // The keys flagged as signers; a validator info's identity.
+                    Field::new(
+                        "signers",
+                        DataType::List(Arc::new(key_field.clone())),
+                        false,
+                    ),
+                    Field::new("info", DataType::Utf8, true),
+                ])
+            }),
+            rows: 0,
+            batch_threshold: BATCH_THRESHOLD,
+            pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
+            keys_b: ListBuilder::new(BinaryBuilder::new()).with_field(key_field.clone()),
+            signers_b: ListBuilder::new(BinaryBuilder::new()).with_field(key_field),
+            info_b: StringBuilder::new(),
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
+                Arc::new(self.keys_b.finish()),
+                Arc::new(self.signers_b.finish()),
+                Arc::new(self.info_b.finish()),
+            ],
+        )
+        .ok()
+    }
+}
+
+impl Default for ConfigDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::decoders::Decoder for ConfigDecoder {
+    fn name(&self) -> &'static str {
+        "config_accounts"
+    }
+
+    fn owner(&self) -> Pubkey {
+        CONFIG_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &CONFIG_PROGRAM && data_len >= MIN_SIZE
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        let config = ConfigAccount::read(&mut Borsh::new(data))
+            .ok_or_else(|| anyhow::anyhow!("truncated or malformed config account"))?;
+
+        self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
+        for (key, signer) in &config.keys {
+            self.keys_b.values().append_value(key);
+            if *signer {
+                self.signers_b.values().append_value(key);
+            }
+        }
+        self.keys_b.append(true);
+        self.signers_b.append(true);
+        self.info_b.append_option(config.info);
+
+        self.rows += 1;
+
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+}
@@ -0,0 +1,362 @@
+//! Declarative decoder for fixed-size accounts.
+//!
+//! Most fixed-layout decoders differ only in their fields: read a few values
+//! at known offsets and append each to a column. `FixedLayoutDecoder` does
+//! that from a list of `FieldSpec`s, so a simple program is a table name, an
+//! owner, a size and its fields:
+//!
+//! ```ignore
+//! FixedLayoutDecoder::new("mint_supplies", TOKEN_PROGRAM, 82, vec![
+//!     FieldSpec::new("mint_authority", 0, FieldType::COptionPubkey),
+//!     FieldSpec::new("supply", 36, FieldType::U64Le),
+//!     FieldSpec::new("decimals", 44, FieldType::U8),
+//! ])
+//! ```
+//!
+//! The name is the output table's, so it must not be one another decoder
+//! already uses with a different schema.
+//!
+//! Anything needing derived columns, spam filtering or a variable-length
+//! tail still wants a hand-written `Decoder`.
+
+use arrow::{
+    array::{ArrayRef, BinaryBuilder, RecordBatch, UInt8Builder, UInt64Builder},
+    datatypes::{DataType, Field, Schema},
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use super::{BATCH_THRESHOLD, COptionPubkey, SchemaRegistry};
+use crate::Pubkey;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    /// Little-endian `u64`, a `UInt64` column.
+    U64Le,
+    /// `UInt8` column.
+    U8,
+    /// 32 bytes, a `Binary` column.
+    Pubkey,
+    /// Solana's C-style `COption<Pubkey>`: a 4-byte tag then 32 bytes. A
+    /// nullable `Binary` column, NULL unless the tag is 1.
+    COptionPubkey,
+}
+
+impl FieldType {
+    /// Bytes the field occupies in the account data.
+    pub fn width(self) -> usize {
+        match self {
+            FieldType::U64Le => 8,
+            FieldType::U8 => 1,
+            FieldType::Pubkey => 32,
+            FieldType::COptionPubkey => size_of::<COptionPubkey>(),
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            FieldType::U64Le => DataType::UInt64,
+            FieldType::U8 => DataType::UInt8,
+            FieldType::Pubkey | FieldType::COptionPubkey => DataType::Binary,
+        }
+    }
+}
+
+/// One output column: `name`, read as `ty` at byte `offset` of the data.
+#[derive(Clone, Copy, Debug)]
+pub struct FieldSpec {
+    pub name: &'static str,
+    pub offset: usize,
+    pub ty: FieldType,
+}
+
+impl FieldSpec {
+    pub const fn new(name: &'static str, offset: usize, ty: FieldType) -> Self {
+        Self { name, offset, ty }
+    }
+}
+
+enum ColumnBuilder {
+    U64(UInt64Builder),
+    U8(UInt8Builder),
+    Binary(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(ty: FieldType) -> Self {
+        match ty {
+            FieldType::U64Le => ColumnBuilder::U64(UInt64Builder::new()),
+            FieldType::U8 => ColumnBuilder::U8(UInt8Builder::new()),
+            FieldType::Pubkey | FieldType::COptionPubkey => {
+                ColumnBuilder::Binary(BinaryBuilder::new())
+            }
+        }
+    }
+
+    /// Append the value of `field` in `data`, which `decode` has checked is
+    /// the layout's size, so this can't fail partway through a row.
+    fn append(&mut self, field: &FieldSpec, data: &[u8]) {
+        let bytes = &data[field.offset..field.offset + field.ty.width()];
+        match (self, field.ty) {
+            (ColumnBuilder::U64(b), FieldType::U64Le) => {
+                b.append_value(u64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            (ColumnBuilder::U8(b), FieldType::U8) => b.append_value(bytes[0]),
+            (ColumnBuilder::Binary(b), FieldType::Pubkey) => b.append_value(bytes),
+            (ColumnBuilder::Binary(b), FieldType::COptionPubkey) => {
+                match bytemuck::from_bytes::<COptionPubkey>(bytes).get() {
+                    Some(pk) => b.append_value(pk),
+                    None => b.append_null(),
+                }
+            }
+            _ => unreachable!("column builder doesn't match its field type"),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::U64(b) => Arc::new(b.finish()),
+            ColumnBuilder::U8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Binary(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// `Decoder::fixed_sizes` wants a `&'static` slice; each distinct size is
+/// leaked once and shared by every decoder with that size.
+fn static_sizes(size: u64) -> &'static [u64] {
+    static SIZES: OnceLock<Mutex<HashMap<u64, &'static [u64]>>> = OnceLock::new();
+    SIZES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(size)
+        .or_insert_with(|| Box::leak(Box::new([size])))
+}
+
+/// A `Decoder` for accounts of exactly `size` bytes owned by `owner`. Each
+/// row is `pubkey`, `write_version`, then one column per `FieldSpec`.
+pub struct FixedLayoutDecoder {
+    name: &'static str,
+    owner: Pubkey,
+    size: usize,
+    fields: Vec<FieldSpec>,
+    schema: Arc<Schema>,
+    rows: usize,
+    batch_threshold: usize,
+    pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
+    columns: Vec<ColumnBuilder>,
+}
+
+impl FixedLayoutDecoder {
+    /// Panics if a field runs past `size`, two fields share a name, or
+    /// `name` is already registered with a different schema; all are
+    /// mistakes in the spec, not in the data.
+    pub fn new(name: &'static str, owner: Pubkey, size: usize, fields: Vec<FieldSpec>) -> Self {
+        for (i, field) in fields.iter().enumerate() {
+            assert!(
+                field.offset + field.ty.width() <= size,
+                "{name}.{}: {} bytes at offset {} run past the {size}-byte layout",
+                field.name,
+                field.ty.width(),
+                field.offset
+            );
+            assert!(
+                !["pubkey", "write_version"].contains(&field.name)
+                    && fields[..i].iter().all(|f| f.name != field.name),
+                "{name}.{}: duplicate column name",
+                field.name
+            );
+        }
+        let mut schema = vec![
+            Field::new("pubkey", DataType::Binary, false),
+            Field::new("write_version", DataType::UInt64, false),
+        ];
+        schema.extend(
+            fields
+                .iter()
+                .map(|f| Field::new(f.name, f.ty.data_type(), f.ty == FieldType::COptionPubkey)),
+        );
+        let schema = Schema::new(schema);
+        let registered = SchemaRegistry::get_or_insert(name, || schema.clone());
+        assert!(
+            *registered == schema,
+            "{name}: another decoder registered this table with a different schema"
+        );
+        Self {
+            name,
+            owner,
+            size,
+            schema: registered,
+            rows: 0,
+            batch_threshold: BATCH_THRESHOLD,
+            pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
+            columns: fields.iter().map(|f| ColumnBuilder::new(f.ty)).collect(),
+            fields,
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.pubkey_b.finish()),
+            Arc::new(self.write_version_b.finish()),
+        ];
+        columns.extend(self.columns.iter_mut().map(ColumnBuilder::finish));
+        // `new` checked the schema against the fields, so this can't fail.
+        Some(RecordBatch::try_new(self.schema.clone(), columns).expect("fixed layout batch"))
+    }
+}
+
+impl crate::decoders::Decoder for FixedLayoutDecoder {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn owner(&self) -> Pubkey {
+        self.owner
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &self.owner && data_len == self.size as u64
+    }
+
+    fn fixed_sizes(&self) -> &'static [u64] {
+        static_sizes(self.size as u64)
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        anyhow::ensure!(
+            data.len() == self.size,
+            "{} bytes for a {}-byte {}",
+            data.len(),
+            self.size,
+            self.name
+        );
+
+        self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
+        for (column, field) in self.columns.iter_mut().zip(&self.fields) {
+            column.append(field, data);
+        }
+
+        self.rows += 1;
+
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoders::Decoder;
+    use crate::decoders::token_program::TOKEN_PROGRAM;
+    use crate::decoders::token_program::mint::MintDecoder;
+
+    /// An 82-byte SPL Token mint.
+    fn mint_data(authority: Option<Pubkey>, supply: u64, decimals: u8) -> Vec<u8> {
+        let mut data = vec![0u8; 82];
+        if let Some(authority) = authority {
+            data[..4].copy_from_slice(&1u32.to_le_bytes());
+            data[4..36].copy_from_slice(authority.as_bytes());
+        }
+        data[36..44].copy_from_slice(&supply.to_le_bytes());
+        data[44] = decimals;
+        data[45] = 1;
+        data[46..50].copy_from_slice(&1u32.to_le_bytes());
+        data[50..82].copy_from_slice(&[7; 32]);
+        data
+    }
+
+    #[test]
+    fn mint_layout_matches_mint_decoder() {
+        let mut fixed = FixedLayoutDecoder::new(
+            "test_mint_layout",
+            TOKEN_PROGRAM,
+            82,
+            vec![
+                FieldSpec::new("mint_authority", 0, FieldType::COptionPubkey),
+                FieldSpec::new("freeze_authority", 46, FieldType::COptionPubkey),
+                FieldSpec::new("supply", 36, FieldType::U64Le),
+                FieldSpec::new("decimals", 44, FieldType::U8),
+            ],
+        );
+        let mut mints = MintDecoder::new(Default::default());
+
+        let rows = [
+            (
+                Pubkey::new([1; 32]),
+                mint_data(Some(Pubkey::new([2; 32])), 1_000_000, 6),
+            ),
+            (Pubkey::new([3; 32]), mint_data(None, 1, 0)),
+        ];
+        for (i, (pubkey, data)) in rows.iter().enumerate() {
+            assert!(fixed.matches(&TOKEN_PROGRAM, data.len() as u64));
+            assert!(
+                fixed
+                    .decode(*pubkey, i as u64, data, true)
+                    .unwrap()
+                    .is_none()
+            );
+            assert!(
+                mints
+                    .decode(*pubkey, i as u64, data, true)
+                    .unwrap()
+                    .is_none()
+            );
+        }
+        let fixed = fixed.flush().unwrap();
+        let mints = mints.flush().unwrap();
+
+        assert_eq!(fixed.num_rows(), 2);
+        for field in fixed.schema().fields() {
+            let name = field.name();
+            assert_eq!(
+                fixed.column_by_name(name).unwrap().as_ref(),
+                mints.column_by_name(name).unwrap().as_ref(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "different schema")]
+    fn name_taken_by_another_schema_panics() {
+        // Registers `mints` with `MintDecoder`'s columns first.
+        MintDecoder::new(Default::default());
+        FixedLayoutDecoder::new(
+            "mints",
+            TOKEN_PROGRAM,
+            82,
+            vec![FieldSpec::new("supply", 36, FieldType::U64Le)],
+        );
+    }
+}
@@ -0,0 +1,404 @@
+use arrow::{
+    array::{
+        ArrayBuilder, ArrayRef, BinaryBuilder, BooleanBuilder, RecordBatch, StringBuilder,
+        UInt8Builder, UInt64Builder,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef},
+};
+use std::sync::Arc;
+
+use super::borsh::Borsh;
+use super::{BATCH_THRESHOLD, SchemaRegistry};
+use crate::Pubkey;
+
+/// SPL Governance (Realms) mainnet deployment. DAOs can run their own
+/// instance, so `GovernanceDecoder::new` takes the program ID explicitly.
+pub const GOVERNANCE_PROGRAM: Pubkey = Pubkey::new([
+    234, 228, 53, 189, 238, 117, 183, 52, 205, 89, 62, 207, 154, 48, 75, 128, 36, 186, 40, 152,
+    103, 183, 105, 177, 249, 60, 167, 187, 184, 142, 70, 254,
+]);
+
+// `GovernanceAccountType` discriminants (first byte of every account).
+const REALM_V1: u8 = 1;
+const TOKEN_OWNER_RECORD_V1: u8 = 2;
+const GOVERNANCE_V1: [u8; 4] = [3, 4, 9, 10];
+const VOTE_RECORD_V2: u8 = 12;
+const PROPOSAL_V2: u8 = 14;
+const REALM_V2: u8 = 16;
+const TOKEN_OWNER_RECORD_V2: u8 = 17;
+const GOVERNANCE_V2: [u8; 4] = [18, 19, 20, 21];
+
+type DecodeFn = fn(&mut Table, &mut Borsh) -> Option<()>;
+
+/// One output table: schema + column builders for a single account type.
+struct Table {
+    name: &'static str,
+    schema: Arc<Schema>,
+    builders: Vec<Box<dyn ArrayBuilder>>,
+    rows: usize,
+}
+
+impl Table {
+    fn new(name: &'static str, fields: Vec<Field>) -> Self {
+        let schema = SchemaRegistry::get_or_insert(name, || Schema::new(fields));
+        let builders = schema
+            .fields()
+            .iter()
+            .map(|f| arrow::array::make_builder(f.data_type(), BATCH_THRESHOLD))
+            .collect();
+        Self {
+            name,
+            schema,
+            builders,
+            rows: 0,
+        }
+    }
+
+    fn col<T: ArrayBuilder>(&mut self, i: usize) -> &mut T {
+        self.builders[i].as_any_mut().downcast_mut::<T>().unwrap()
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        let columns: Vec<ArrayRef> = self.builders.iter_mut().map(|b| b.finish()).collect();
+        RecordBatch::try_new(self.schema.clone(), columns).ok()
+    }
+}
+
+fn append_pubkey(b: &mut BinaryBuilder, pk: Option<Pubkey>) {
+    match pk {
+        Some(pk) => b.append_value(pk),
+        None => b.append_null(),
+    }
+}
+
+/// Decodes Realm, Governance, ProposalV2, VoteRecordV2 and TokenOwnerRecord
+/// accounts into one table each. Only the fixed-position fields are
+/// extracted; variable-length tails (vote options, configs) are skipped.
+pub struct GovernanceDecoder {
+    program_id: Pubkey,
+    realms: Table,
+    governances: Table,
+    proposals: Table,
+    vote_records: Table,
+    token_owner_records: Table,
+    batch_threshold: usize,
+}
+
+impl GovernanceDecoder {
+    pub fn new(program_id: Pubkey) -> Self {
+        let key = |name| Field::new(name, DataType::Binary, false);
+        let head = || {
+            vec![
+                key("pubkey"),
+                Field::new("write_version", DataType::UInt64, false),
+                Field::new("account_type", DataType::UInt8, false),
+            ]
+        };
+
+        let realms = Table::new(
+            "governance_realms",
+            [
+                head(),
+                vec![
+                    key("community_mint"),
+                    Field::new("council_mint", DataType::Binary, true),
+                    Field::new("authority", DataType::Binary, true),
+                    Field::new("name", DataType::Utf8, false),
+                ],
+            ]
+            .concat(),
+        );
+        let governances = Table::new(
+            "governance_governances",
+            [head(), vec![key("realm"), key("governed_account")]].concat(),
+        );
+        let proposals = Table::new(
+            "governance_proposals",
+            [
+                head(),
+                vec![
+                    key("governance"),
+                    key("governing_token_mint"),
+                    Field::new("state", DataType::UInt8, false),
+                    key("token_owner_record"),
+                    Field::new("signatories_count", DataType::UInt8, false),
+                    Field::new("signatories_signed_off_count", DataType::UInt8, false),
+                ],
+            ]
+            .concat(),
+        );
+        let vote_records = Table::new(
+            "governance_vote_records",
+            [
+                head(),
+                vec![
+                    key("proposal"),
+                    key("governing_token_owner"),
+                    Field::new("is_relinquished", DataType::Boolean, false),
+                    Field::new("voter_weight", DataType::UInt64, false),
+                ],
+            ]
+            .concat(),
+        );
+        let token_owner_records = Table::new(
+            "governance_token_owner_records",
+            [
+                head(),
+                vec![
+                    key("realm"),
+                    key("governing_token_mint"),
+                    key("governing_token_owner"),
+                    Field::new("governing_token_deposit_amount", DataType::UInt64, false),
+                ],
+            ]
+            .concat(),
+        );
+
+        Self {
+            program_id,
+            realms,
+            governances,
+            proposals,
+            vote_records,
+            token_owner_records,
+            batch_threshold: BATCH_THRESHOLD,
+        }
+    }
+
+    fn tables(&self) -> [&Table; 5] {
+        [
+            &self.realms,
+            &self.governances,
+            &self.proposals,
+            &self.vote_records,
+            &self.token_owner_records,
+        ]
+    }
+
+    fn decode_realm(t: &mut Table, r: &mut Borsh) -> Option<()> {
+        let community_mint = r.pubkey()?;
+        // RealmConfig: legacy1, legacy2, reserved[6], min_weight u64, max_voter_weight_source (tag + u64)
+        r.skip(1 + 1 + 6 + 8 + 1 + 8)?;
+        let council_mint = r.option_pubkey()?;
+        // reserved[6], legacy1 u16
+        r.skip(6 + 2)?;
+        let authority = r.option_pubkey()?;
+        let name = r.string()?;
+
+        t.col::<BinaryBuilder>(3).append_value(community_mint);
+        append_pubkey(t.col::<BinaryBuilder>(4), council_mint);
+        append_pubkey(t.col::<BinaryBuilder>(5), authority);
+        t.col::<StringBuilder>(6).append_value(name);
+        Some(())
+    }
+
+    fn decode_governance(t: &mut Table, r: &mut Borsh) -> Option<()> {
+        let realm = r.pubkey()?;
+        let governed_account = r.pubkey()?;
+
+        t.col::<BinaryBuilder>(3).append_value(realm);
+        t.col::<BinaryBuilder>(4).append_value(governed_account);
+        Some(())
+    }
+
+    fn decode_proposal(t: &mut Table, r: &mut Borsh) -> Option<()> {
+        let governance = r.pubkey()?;
+        let governing_token_mint = r.pubkey()?;
+        let state = r.u8()?;
+        let token_owner_record = r.pubkey()?;
+        let signatories_count = r.u8()?;
+        let signatories_signed_off_count = r.u8()?;
+
+        t.col::<BinaryBuilder>(3).append_value(governance);
+        t.col::<BinaryBuilder>(4).append_value(governing_token_mint);
+        t.col::<UInt8Builder>(5).append_value(state);
+        t.col::<BinaryBuilder>(6).append_value(token_owner_record);
+        t.col::<UInt8Builder>(7).append_value(signatories_count);
+        t.col::<UInt8Builder>(8)
+            .append_value(signatories_signed_off_count);
+        Some(())
+    }
+
+    fn decode_vote_record(t: &mut Table, r: &mut Borsh) -> Option<()> {
+        let proposal = r.pubkey()?;
+        let governing_token_owner = r.pubkey()?;
+        let is_relinquished = r.u8()? != 0;
+        let voter_weight = r.u64()?;
+
+        t.col::<BinaryBuilder>(3).append_value(proposal);
+        t.col::<BinaryBuilder>(4)
+            .append_value(governing_token_owner);
+        t.col::<BooleanBuilder>(5).append_value(is_relinquished);
+        t.col::<UInt64Builder>(6).append_value(voter_weight);
+        Some(())
+    }
+
+    fn decode_token_owner_record(t: &mut Table, r: &mut Borsh) -> Option<()> {
+        let realm = r.pubkey()?;
+        let governing_token_mint = r.pubkey()?;
+        let governing_token_owner = r.pubkey()?;
+        let deposit_amount = r.u64()?;
+
+        t.col::<BinaryBuilder>(3).append_value(realm);
+        t.col::<BinaryBuilder>(4).append_value(governing_token_mint);
+        t.col::<BinaryBuilder>(5)
+            .append_value(governing_token_owner);
+        t.col::<UInt64Builder>(6).append_value(deposit_amount);
+        Some(())
+    }
+}
+
+impl crate::decoders::Decoder for GovernanceDecoder {
+    /// The decoder's name; its tables are listed by `schemas`.
+    fn name(&self) -> &'static str {
+        "governance"
+    }
+
+    fn owner(&self) -> Pubkey {
+        self.program_id
+    }
+
+    /// The `governance_realms` schema; `schemas` lists all five tables.
+    fn schema(&self) -> &Schema {
+        &self.realms.schema
+    }
+
+    fn schemas(&self) -> Vec<(&'static str, SchemaRef)> {
+        self.tables()
+            .iter()
+            .map(|t| (t.name, t.schema.clone()))
+            .collect()
+    }
+
+    /// Every table's batches share its registered `Arc<Schema>`.
+    fn batch_name(&self, batch: &RecordBatch) -> &'static str {
+        self.tables()
+            .iter()
+            .find(|t| Arc::ptr_eq(&t.schema, batch.schema_ref()))
+            .map_or(self.realms.name, |t| t.name)
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &self.program_id && data_len > 0
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        let account_type = data[0];
+        let mut r = Borsh::new(&data[1..]);
+
+        let (table, decode): (&mut Table, DecodeFn) = match account_type {
+            REALM_V1 | REALM_V2 => (&mut self.realms, Self::decode_realm),
+            t if GOVERNANCE_V1.contains(&t) || GOVERNANCE_V2.contains(&t) => {
+                (&mut self.governances, Self::decode_governance)
+            }
+            PROPOSAL_V2 => (&mut self.proposals, Self::decode_proposal),
+            VOTE_RECORD_V2 => (&mut self.vote_records, Self::decode_vote_record),
+            TOKEN_OWNER_RECORD_V1 | TOKEN_OWNER_RECORD_V2 => (
+                &mut self.token_owner_records,
+                Self::decode_token_owner_record,
+            ),
+            _ => return Ok(None),
+        };
+
+        // Each decode_* reads every field before appending, so a truncated
+        // account leaves the builders untouched.
+        if decode(table, &mut r).is_none() {
+            anyhow::bail!("truncated {} account (type {account_type})", table.name);
+        }
+        table.col::<BinaryBuilder>(0).append_value(pubkey);
+        table.col::<UInt64Builder>(1).append_value(write_version);
+        table.col::<UInt8Builder>(2).append_value(account_type);
+        table.rows += 1;
+
+        if table.rows >= self.batch_threshold {
+            Ok(table.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns one pending table per call; the pipeline keeps calling until `None`.
+    fn flush(&mut self) -> Option<RecordBatch> {
+        for table in [
+            &mut self.realms,
+            &mut self.governances,
+            &mut self.proposals,
+            &mut self.vote_records,
+            &mut self.token_owner_records,
+        ] {
+            if let Some(batch) = table.build_batch() {
+                return Some(batch);
+            }
+        }
+        None
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_id_matches_its_base58() {
+        let b58 = Pubkey::from_b58("GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZw").unwrap();
+        assert_eq!(GOVERNANCE_PROGRAM, b58);
+    }
+
+    #[test]
+    fn tables_are_named_by_batch_not_by_last_decode() {
+        use crate::decoders::DecoderRegistry;
+
+        let mut proposal = vec![PROPOSAL_V2];
+        proposal.extend([1; 32]);
+        proposal.extend([2; 32]);
+        proposal.push(3);
+        proposal.extend([4; 32]);
+        proposal.extend([5, 6]);
+        let mut governance = vec![GOVERNANCE_V2[0]];
+        governance.extend([7; 64]);
+
+        let mut registry =
+            DecoderRegistry::new(vec![Box::new(GovernanceDecoder::new(GOVERNANCE_PROGRAM))]);
+        registry.set_batch_size("governance_proposals", 1).unwrap();
+        let dec = &mut registry[0];
+        let names: Vec<_> = dec.schemas().iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "governance_realms",
+                "governance_governances",
+                "governance_proposals",
+                "governance_vote_records",
+                "governance_token_owner_records",
+            ]
+        );
+
+        let batch = dec
+            .decode(Pubkey::new([8; 32]), 0, &proposal, false)
+            .unwrap()
+            .unwrap();
+        // Another table's decode in between doesn't relabel the batch.
+        let other = dec
+            .decode(Pubkey::new([9; 32]), 1, &governance, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(dec.batch_name(&batch), "governance_proposals");
+        assert_eq!(dec.batch_name(&other), "governance_governances");
+        assert_eq!(batch.num_rows(), 1);
+    }
+}
@@ -0,0 +1,233 @@
+use arrow::{
+    array::{
+        BinaryBuilder, BooleanBuilder, LargeStringBuilder, RecordBatch, StringBuilder,
+        UInt8Builder, UInt16Builder, UInt64Builder,
+    },
+    datatypes::{DataType, Field, Schema},
+};
+use std::sync::Arc;
+
+use super::borsh::Borsh;
+use super::{BATCH_THRESHOLD, SchemaRegistry};
+use crate::Pubkey;
+
+pub const METADATA_PROGRAM: Pubkey = Pubkey::METAPLEX_METADATA;
+
+/// `Key::MetadataV1`, the first byte of every metadata account. Editions,
+/// master editions and the other account kinds are skipped.
+const METADATA_V1: u8 = 4;
+
+/// Smallest possible metadata account: key, update authority, mint, three
+/// empty strings, seller fee, no creators, primary sale and mutability flags.
+const MIN_SIZE: u64 = 1 + 32 + 32 + 3 * 4 + 2 + 1 + 1 + 1;
+
+/// Strings in metadata accounts are written padded to their max length
+/// (32 for name, 10 for symbol, 200 for uri) with NULs.
+fn unpad(s: &str) -> &str {
+    s.trim_end_matches('\0')
+}
+
+/// Decodes the `Metadata` account of Token Metadata up to `is_mutable`.
+/// The optional fields after it (edition nonce, token standard, collection,
+/// uses, ...) aren't decoded.
+pub struct MetadataDecoder {
+    schema: Arc<Schema>,
+    rows: usize,
+    batch_threshold: usize,
+    skipped: u64,
+    pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
+    mint_b: BinaryBuilder,
+    update_authority_b: BinaryBuilder,
+    name_b: StringBuilder,
+    symbol_b: StringBuilder,
+    uri_b: LargeStringBuilder,
+    seller_fee_basis_points_b: UInt16Builder,
+    creators_b: UInt8Builder,
+    primary_sale_happened_b: BooleanBuilder,
+    is_mutable_b: BooleanBuilder,
+}
+
+impl MetadataDecoder {
+    pub fn new() -> Self {
+        Self {
+            schema: SchemaRegistry::get_or_insert("metaplex_metadata", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("mint", DataType::Binary, false),
+                    Field::new("update_authority", DataType::Binary, false),
+                    // name and symbol are capped at 32 and 10 bytes; uri at 200,
+                    // but it's most of the string data, so it gets 64-bit offsets.
+                    Field::new("name", DataType::Utf8, false),
+                    Field::new("symbol", DataType::Utf8, false),
+                    Field::new("uri", DataType::LargeUtf8, false),
+                    Field::new("seller_fee_basis_points", DataType::UInt16, false),
+                    Field::new("creators", DataType::UInt8, false),
+                    Field::new("primary_sale_happened", DataType::Boolean, false),
+                    Field::new("is_mutable", DataType::Boolean, false),
+                ])
+            }),
+            rows: 0,
+            batch_threshold: BATCH_THRESHOLD,
+            skipped: 0,
+            pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
+            mint_b: BinaryBuilder::new(),
+            update_authority_b: BinaryBuilder::new(),
+            name_b: StringBuilder::new(),
+            symbol_b: StringBuilder::new(),
+            uri_b: LargeStringBuilder::new(),
+            seller_fee_basis_points_b: UInt16Builder::new(),
+            creators_b: UInt8Builder::new(),
+            primary_sale_happened_b: BooleanBuilder::new(),
+            is_mutable_b: BooleanBuilder::new(),
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
+                Arc::new(self.mint_b.finish()),
+                Arc::new(self.update_authority_b.finish()),
+                Arc::new(self.name_b.finish()),
+                Arc::new(self.symbol_b.finish()),
+                Arc::new(self.uri_b.finish()),
+                Arc::new(self.seller_fee_basis_points_b.finish()),
+                Arc::new(self.creators_b.finish()),
+                Arc::new(self.primary_sale_happened_b.finish()),
+                Arc::new(self.is_mutable_b.finish()),
+            ],
+        )
+        .ok()
+    }
+}
+
+impl Default for MetadataDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fields of one metadata account, read in full before anything is appended
+/// so a truncated account leaves the builders untouched.
+struct Metadata<'a> {
+    update_authority: Pubkey,
+    mint: Pubkey,
+    name: &'a str,
+    symbol: &'a str,
+    uri: &'a str,
+    seller_fee_basis_points: u16,
+    creators: u8,
+    primary_sale_happened: bool,
+    is_mutable: bool,
+}
+
+impl<'a> Metadata<'a> {
+    fn read(r: &mut Borsh<'a>) -> Option<Self> {
+        let update_authority = r.pubkey()?;
+        let mint = r.pubkey()?;
+        let name = unpad(r.string()?);
+        let symbol = unpad(r.string()?);
+        let uri = unpad(r.string()?);
+        let seller_fee_basis_points = r.u16()?;
+        // Option<Vec<Creator>>: address, verified, share. At most 5 creators.
+        let creators = match r.u8()? {
+            0 => 0,
+            1 => {
+                let n = r.u32()?;
+                r.skip(n as usize * (32 + 1 + 1))?;
+                u8::try_from(n).ok()?
+            }
+            _ => return None,
+        };
+        let primary_sale_happened = r.u8()? != 0;
+        let is_mutable = r.u8()? != 0;
+        Some(Self {
+            update_authority,
+            mint,
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points,
+            creators,
+            primary_sale_happened,
+            is_mutable,
+        })
+    }
+}
+
+impl crate::decoders::Decoder for MetadataDecoder {
+    fn name(&self) -> &'static str {
+        "metaplex_metadata"
+    }
+
+    fn owner(&self) -> Pubkey {
+        METADATA_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &METADATA_PROGRAM && data_len >= MIN_SIZE
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        if data[0] != METADATA_V1 {
+            self.skipped += 1;
+            return Ok(None);
+        }
+        let meta = Metadata::read(&mut Borsh::new(&data[1..]))
+            .ok_or_else(|| anyhow::anyhow!("truncated or malformed metadata account"))?;
+
+        self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
+        self.mint_b.append_value(meta.mint);
+        self.update_authority_b.append_value(meta.update_authority);
+        self.name_b.append_value(meta.name);
+        self.symbol_b.append_value(meta.symbol);
+        self.uri_b.append_value(meta.uri);
+        self.seller_fee_basis_points_b
+            .append_value(meta.seller_fee_basis_points);
+        self.creators_b.append_value(meta.creators);
+        self.primary_sale_happened_b
+            .append_value(meta.primary_sale_happened);
+        self.is_mutable_b.append_value(meta.is_mutable);
+
+        self.rows += 1;
+
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+
+    fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
@@ -1,18 +1,285 @@
-use arrow::{array::RecordBatch, datatypes::Schema};
+use arrow::{
+    array::RecordBatch,
+    datatypes::{Schema, SchemaRef},
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use crate::Pubkey;
 use bytemuck::{Pod, Zeroable};
 
+mod borsh;
+pub mod config;
+pub mod fixed_layout;
+pub mod governance;
 pub mod known_mints;
+pub mod metaplex;
+pub mod nft;
+pub mod pyth;
+pub mod system;
 pub mod token_program;
 
+use config::ConfigDecoder;
+use governance::{GOVERNANCE_PROGRAM, GovernanceDecoder};
+use metaplex::MetadataDecoder;
+use nft::NftMintDecoder;
+use pyth::PythDecoder;
+use system::SystemDecoder;
+use token_program::{
+    aggregate::AggregateMintDecoder, mint::MintDecoder, multisig::MultisigDecoder,
+    token_2022_mint::Token2022MintDecoder, token_account::TokenAccountDecoder,
+};
+
+pub const BATCH_THRESHOLD: usize = 16_384;
+
+/// Overlay `T` on `data`, or a decode error if the length doesn't match.
+/// The router keys on the header's `data_len`, but a corrupt AppendVec can
+/// still hand over a short slice; that must not panic the parser thread.
+pub fn cast<T: Pod>(data: &[u8]) -> anyhow::Result<&T> {
+    bytemuck::try_from_bytes(data).map_err(|e| {
+        let name = std::any::type_name::<T>()
+            .rsplit("::")
+            .next()
+            .unwrap_or("?");
+        anyhow::anyhow!(
+            "{} bytes for a {}-byte {name}: {e:?}",
+            data.len(),
+            size_of::<T>()
+        )
+    })
+}
+
+/// Output schemas shared across parser threads, keyed by table name. Every
+/// thread builds its own decoders, but a table's schema never changes, so
+/// they all hand the same `Arc<Schema>` to each `RecordBatch` they build.
+pub struct SchemaRegistry;
+
+impl SchemaRegistry {
+    /// The schema registered as `name`, built with `build` on first use.
+    pub fn get_or_insert(name: &'static str, build: impl FnOnce() -> Schema) -> Arc<Schema> {
+        static SCHEMAS: OnceLock<Mutex<HashMap<&'static str, Arc<Schema>>>> = OnceLock::new();
+        SCHEMAS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .entry(name)
+            .or_insert_with(|| Arc::new(build()))
+            .clone()
+    }
+}
+
 pub trait Decoder: Send {
     fn name(&self) -> &'static str;
     fn owner(&self) -> Pubkey;
     fn schema(&self) -> &Schema;
+    /// Every output table, by name. One table named `name()` unless the
+    /// decoder splits its accounts across several.
+    fn schemas(&self) -> Vec<(&'static str, SchemaRef)> {
+        vec![(self.name(), Arc::new(self.schema().clone()))]
+    }
+    /// The table a batch from this decoder's `decode` or `flush` belongs to.
+    fn batch_name(&self, _batch: &RecordBatch) -> &'static str {
+        self.name()
+    }
+    /// Cheap applicability check on owner + size; `decode` is only called when this is true.
     fn matches(&self, owner: &Pubkey, data_len: u64) -> bool;
-    fn decode(&mut self, pubkey: Pubkey, data: &[u8], include_spam: bool) -> Option<RecordBatch>;
+    /// Every `data_len` `matches` accepts, for decoders with fixed-size layouts.
+    /// These are routed by an (owner, size) lookup without calling `matches`.
+    /// Empty means variable-size: routing scans those decoders with `matches`.
+    fn fixed_sizes(&self) -> &'static [u64] {
+        &[]
+    }
+    /// `Ok(Some)` when a batch filled up, `Ok(None)` when the row was buffered
+    /// or deliberately skipped (e.g. spam). `Err` means the data was malformed.
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>>;
     fn flush(&mut self) -> Option<RecordBatch>;
+    /// Rows buffered before `decode` returns a batch (`BATCH_THRESHOLD` by
+    /// default). Wide rows may want fewer, tiny ones more.
+    fn set_batch_size(&mut self, size: usize);
+
+    /// Accounts this decoder matched but intentionally didn't emit a row for.
+    fn skipped(&self) -> u64 {
+        0
+    }
+}
+
+/// A parser thread's decoders. Derefs to the `Vec`, so it can be iterated
+/// and passed as a slice like one.
+pub struct DecoderRegistry(Vec<Box<dyn Decoder>>);
+
+impl DecoderRegistry {
+    pub fn new(decoders: Vec<Box<dyn Decoder>>) -> Self {
+        Self(decoders)
+    }
+
+    /// Override the batch size of the decoder called `name`, or of the one
+    /// writing table `name`. A multi-table decoder has one size for all.
+    pub fn set_batch_size(&mut self, name: &str, size: usize) -> anyhow::Result<()> {
+        let dec = self
+            .0
+            .iter_mut()
+            .find(|d| d.name() == name || d.schemas().iter().any(|(table, _)| *table == name))
+            .ok_or_else(|| anyhow::anyhow!("no decoder named {name}"))?;
+        dec.set_batch_size(size);
+        Ok(())
+    }
+
+    /// Override the batch size of every decoder.
+    pub fn set_all_batch_sizes(&mut self, size: usize) {
+        for dec in self {
+            dec.set_batch_size(size);
+        }
+    }
+}
+
+impl std::ops::Deref for DecoderRegistry {
+    type Target = Vec<Box<dyn Decoder>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for DecoderRegistry {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'a> IntoIterator for &'a DecoderRegistry {
+    type Item = &'a Box<dyn Decoder>;
+    type IntoIter = std::slice::Iter<'a, Box<dyn Decoder>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut DecoderRegistry {
+    type Item = &'a mut Box<dyn Decoder>;
+    type IntoIter = std::slice::IterMut<'a, Box<dyn Decoder>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+/// Every built-in decoder, fresh. Each parser thread builds its own set.
+/// `aggregate_by_mint` swaps per-account token rows for per-mint totals.
+/// `nfts_only` swaps `mints` for `nft_mints`. Never register both: they
+/// claim the same (owner, size), and the router only uses the first.
+pub fn build_default_registry(
+    known_mints: Arc<HashSet<Pubkey>>,
+    aggregate_by_mint: bool,
+    nfts_only: bool,
+) -> DecoderRegistry {
+    let mints: Box<dyn Decoder> = if nfts_only {
+        Box::new(NftMintDecoder::new())
+    } else {
+        Box::new(MintDecoder::new(known_mints.clone()))
+    };
+    let token_accounts: Box<dyn Decoder> = if aggregate_by_mint {
+        Box::new(AggregateMintDecoder::new(known_mints.clone()))
+    } else {
+        Box::new(TokenAccountDecoder::new(known_mints.clone()))
+    };
+    DecoderRegistry::new(vec![
+        mints,
+        token_accounts,
+        Box::new(Token2022MintDecoder::new(known_mints)),
+        Box::new(MultisigDecoder::new()),
+        Box::new(SystemDecoder::new()),
+        Box::new(GovernanceDecoder::new(GOVERNANCE_PROGRAM)),
+        Box::new(PythDecoder::new()),
+        Box::new(MetadataDecoder::new()),
+        Box::new(ConfigDecoder::new()),
+    ])
+}
+
+/// Owners that hold most accounts in a snapshot. The router checks these
+/// by plain comparison before hashing anything.
+const HOT_OWNERS: [Pubkey; 2] = [Pubkey::SYSTEM_PROGRAM, Pubkey::TOKEN_PROGRAM];
+
+/// One hot owner's routes: the same entries as the maps, in short `Vec`s.
+struct HotRoutes {
+    owner: Pubkey,
+    fixed: Vec<(u64, usize)>,
+    variable: Vec<usize>,
+}
+
+/// Picks the decoder for an account. Fixed-size decoders resolve with one
+/// (owner, data_len) lookup; only variable-size decoders need a scan.
+/// Accounts of `HOT_OWNERS` skip the lookup: their routes are a few entries
+/// compared directly.
+#[derive(Default)]
+pub struct DecoderRouter {
+    hot: Vec<HotRoutes>,
+    fixed: HashMap<(Pubkey, u64), usize>,
+    variable: HashMap<Pubkey, Vec<usize>>,
+}
+
+impl DecoderRouter {
+    pub fn new(decoders: &[Box<dyn Decoder>]) -> Self {
+        let mut router = Self::default();
+        for (i, dec) in decoders.iter().enumerate() {
+            if dec.fixed_sizes().is_empty() {
+                router.variable.entry(dec.owner()).or_default().push(i);
+            }
+            for &size in dec.fixed_sizes() {
+                // First registered decoder wins, same as the old linear scan.
+                router.fixed.entry((dec.owner(), size)).or_insert(i);
+            }
+        }
+        router.hot = HOT_OWNERS
+            .iter()
+            .map(|&owner| HotRoutes {
+                owner,
+                fixed: router
+                    .fixed
+                    .iter()
+                    .filter(|&(&(o, _), _)| o == owner)
+                    .map(|(&(_, size), &idx)| (size, idx))
+                    .collect(),
+                variable: router.variable.get(&owner).cloned().unwrap_or_default(),
+            })
+            .collect();
+        router
+    }
+
+    /// Index into `decoders` of the decoder for this account, if any.
+    #[inline]
+    pub fn route(
+        &self,
+        decoders: &[Box<dyn Decoder>],
+        owner: &Pubkey,
+        data_len: u64,
+    ) -> Option<usize> {
+        let (fixed, variable) = match self.hot.iter().find(|h| h.owner == *owner) {
+            Some(hot) => (
+                hot.fixed
+                    .iter()
+                    .find(|&&(size, _)| size == data_len)
+                    .map(|&(_, idx)| idx),
+                hot.variable.as_slice(),
+            ),
+            None => (
+                self.fixed.get(&(*owner, data_len)).copied(),
+                self.variable.get(owner).map_or(&[][..], Vec::as_slice),
+            ),
+        };
+        fixed.or_else(|| {
+            variable
+                .iter()
+                .copied()
+                .find(|&idx| decoders[idx].matches(owner, data_len))
+        })
+    }
 }
 
 #[derive(Zeroable, Clone, Copy, Debug)]
@@ -0,0 +1,80 @@
+//! NFT mints only: SPL Token mints with a supply of 1 and no decimals.
+//!
+//! `NftMintDecoder` claims the same (owner, size) as `MintDecoder` and hands
+//! the NFTs to one, so its `nft_mints` output has the `mints` columns. The
+//! router gives an account to the first decoder registered for its size, so
+//! a registry holds one or the other, never both; `build_default_registry`
+//! picks with `nfts_only`.
+
+use arrow::{array::RecordBatch, datatypes::Schema};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::Pubkey;
+use crate::decoders::token_program::{Mint, TOKEN_PROGRAM, mint::MintDecoder};
+use crate::decoders::{Decoder, cast};
+
+pub struct NftMintDecoder {
+    inner: MintDecoder,
+}
+
+impl NftMintDecoder {
+    pub fn new() -> Self {
+        // The known-mints list is fungible tokens, so it has no say here;
+        // `decode` bypasses it.
+        Self {
+            inner: MintDecoder::new(Arc::new(HashSet::new())),
+        }
+    }
+}
+
+impl Default for NftMintDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for NftMintDecoder {
+    fn name(&self) -> &'static str {
+        "nft_mints"
+    }
+
+    fn owner(&self) -> Pubkey {
+        TOKEN_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        self.inner.schema()
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        self.inner.matches(owner, data_len)
+    }
+
+    fn fixed_sizes(&self) -> &'static [u64] {
+        self.inner.fixed_sizes()
+    }
+
+    /// Fungible mints are filtered out, not counted as skipped: leaving them
+    /// out is what this decoder is for.
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        if !cast::<Mint>(data)?.is_nft() {
+            return Ok(None);
+        }
+        self.inner.decode(pubkey, write_version, data, true)
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.inner.flush()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.inner.set_batch_size(size);
+    }
+}
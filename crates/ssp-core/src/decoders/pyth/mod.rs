@@ -0,0 +1,219 @@
+use arrow::{
+    array::{
+        BinaryBuilder, Float64Builder, Int32Builder, Int64Builder, RecordBatch, UInt32Builder,
+        UInt64Builder,
+    },
+    datatypes::{DataType, Field, Schema},
+};
+use bytemuck::{Pod, Zeroable};
+use std::sync::Arc;
+
+use super::{BATCH_THRESHOLD, SchemaRegistry, cast};
+use crate::Pubkey;
+
+/// Pyth oracle (`FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH`).
+pub const PYTH_PROGRAM: Pubkey = Pubkey::new([
+    220, 229, 235, 225, 228, 156, 59, 159, 17, 76, 181, 84, 76, 80, 169, 158, 192, 214, 146, 214,
+    63, 86, 121, 90, 224, 41, 172, 131, 217, 234, 139, 226,
+]);
+
+const MAGIC: u32 = 0xa1b2_c3d4;
+const ACCOUNT_TYPE_PRICE: u32 = 3;
+
+/// `pc_price_info_t`: one price / confidence pair and its status.
+#[derive(Zeroable, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct PriceInfo {
+    pub price: i64,
+    pub conf: u64,
+    pub status: u32, // 0 = Unknown, 1 = Trading, 2 = Halted, 3 = Auction
+    pub corp_act: u32,
+    pub publish_slot: u64,
+}
+unsafe impl Pod for PriceInfo {}
+
+/// `pc_price_t` up to the aggregate price. The per-publisher components that
+/// follow aren't decoded.
+#[derive(Zeroable, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct PriceAccount {
+    pub magic: u32,
+    pub version: u32,
+    pub account_type: u32,
+    pub size: u32,
+    pub price_type: u32,
+    pub exponent: i32,
+    pub num_components: u32,
+    pub num_quoters: u32,
+    pub last_slot: u64,
+    pub valid_slot: u64,
+    pub ema_price: [i64; 3], // value, numerator, denominator
+    pub ema_conf: [i64; 3],
+    pub timestamp: i64,
+    pub min_publishers: u8,
+    pub reserved: [u8; 7],
+    pub product: Pubkey,
+    pub next: Pubkey,
+    pub prev_slot: u64,
+    pub prev_price: i64,
+    pub prev_conf: u64,
+    pub prev_timestamp: i64,
+    pub agg: PriceInfo,
+}
+unsafe impl Pod for PriceAccount {}
+
+impl PriceAccount {
+    /// Bytes covered by this struct.
+    pub const HEADER_SIZE: usize = 240;
+    /// Full account: the header plus 32 publisher components of 96 bytes.
+    pub const SIZE: usize = 3312;
+}
+
+const _: () = assert!(size_of::<PriceInfo>() == 32);
+const _: () = assert!(size_of::<PriceAccount>() == PriceAccount::HEADER_SIZE);
+
+/// Decodes the aggregate price of v2 price accounts. Mapping and product
+/// accounts have other sizes and never reach this decoder.
+pub struct PythDecoder {
+    schema: Arc<Schema>,
+    rows: usize,
+    batch_threshold: usize,
+    pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
+    price_raw_b: Int64Builder,
+    confidence_b: UInt64Builder,
+    exponent_b: Int32Builder,
+    status_b: UInt32Builder,
+    publish_slot_b: UInt64Builder,
+    timestamp_b: Int64Builder,
+    price_normalized_b: Float64Builder,
+}
+
+impl PythDecoder {
+    pub fn new() -> Self {
+        Self {
+            schema: SchemaRegistry::get_or_insert("pyth_prices", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("price_raw", DataType::Int64, false),
+                    Field::new("confidence", DataType::UInt64, false),
+                    Field::new("exponent", DataType::Int32, false),
+                    Field::new("status", DataType::UInt32, false),
+                    Field::new("publish_slot", DataType::UInt64, false),
+                    Field::new("timestamp", DataType::Int64, false),
+                    Field::new("price_normalized", DataType::Float64, false),
+                ])
+            }),
+            rows: 0,
+            batch_threshold: BATCH_THRESHOLD,
+            pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
+            price_raw_b: Int64Builder::new(),
+            confidence_b: UInt64Builder::new(),
+            exponent_b: Int32Builder::new(),
+            status_b: UInt32Builder::new(),
+            publish_slot_b: UInt64Builder::new(),
+            timestamp_b: Int64Builder::new(),
+            price_normalized_b: Float64Builder::new(),
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
+                Arc::new(self.price_raw_b.finish()),
+                Arc::new(self.confidence_b.finish()),
+                Arc::new(self.exponent_b.finish()),
+                Arc::new(self.status_b.finish()),
+                Arc::new(self.publish_slot_b.finish()),
+                Arc::new(self.timestamp_b.finish()),
+                Arc::new(self.price_normalized_b.finish()),
+            ],
+        )
+        .ok()
+    }
+}
+
+impl Default for PythDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::decoders::Decoder for PythDecoder {
+    fn name(&self) -> &'static str {
+        "pyth_prices"
+    }
+
+    fn owner(&self) -> Pubkey {
+        PYTH_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &PYTH_PROGRAM && data_len == PriceAccount::SIZE as u64
+    }
+
+    fn fixed_sizes(&self) -> &'static [u64] {
+        &[PriceAccount::SIZE as u64]
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        let header = data
+            .get(..PriceAccount::HEADER_SIZE)
+            .ok_or_else(|| anyhow::anyhow!("{} bytes, shorter than a price header", data.len()))?;
+        let price = cast::<PriceAccount>(header)?;
+        if price.magic != MAGIC {
+            anyhow::bail!("bad magic {:#x}", { price.magic });
+        }
+        if price.account_type != ACCOUNT_TYPE_PRICE {
+            anyhow::bail!("account type {} isn't a price", { price.account_type });
+        }
+
+        let agg = price.agg;
+        self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
+        self.price_raw_b.append_value(agg.price);
+        self.confidence_b.append_value(agg.conf);
+        self.exponent_b.append_value(price.exponent);
+        self.status_b.append_value(agg.status);
+        self.publish_slot_b.append_value(agg.publish_slot);
+        self.timestamp_b.append_value(price.timestamp);
+        self.price_normalized_b
+            .append_value(agg.price as f64 * 10_f64.powi(price.exponent));
+
+        self.rows += 1;
+
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+}
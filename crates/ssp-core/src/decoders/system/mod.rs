@@ -0,0 +1,163 @@
+use arrow::{
+    array::{BinaryBuilder, RecordBatch, UInt8Builder, UInt64Builder},
+    datatypes::{DataType, Field, Schema},
+};
+use bytemuck::{Pod, Zeroable};
+use std::sync::Arc;
+
+use super::{BATCH_THRESHOLD, SchemaRegistry, cast};
+use crate::Pubkey;
+
+pub const SYSTEM_PROGRAM: Pubkey = Pubkey::SYSTEM_PROGRAM;
+
+/// Durable nonce account (`nonce::state::Versions`), bincode layout.
+#[derive(Zeroable, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct NonceAccount {
+    pub version: u32, // 0 = Legacy, 1 = Current
+    pub state: u32,   // 0 = Uninitialized, 1 = Initialized
+    pub authority: Pubkey,
+    pub blockhash: [u8; 32],
+    pub lamports_per_signature: u64,
+}
+unsafe impl Pod for NonceAccount {}
+
+impl NonceAccount {
+    pub const SIZE: usize = 80;
+}
+
+const _: () = assert!(size_of::<NonceAccount>() == NonceAccount::SIZE);
+
+/// Decodes nonce accounts. Plain wallets (no data) are only counted.
+pub struct SystemDecoder {
+    schema: Arc<Schema>,
+    rows: usize,
+    batch_threshold: usize,
+    plain_accounts: u64,
+    pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
+    authority_b: BinaryBuilder,
+    blockhash_b: BinaryBuilder,
+    lamports_per_signature_b: UInt64Builder,
+    state_b: UInt8Builder,
+}
+
+impl SystemDecoder {
+    pub fn new() -> Self {
+        Self {
+            schema: SchemaRegistry::get_or_insert("nonce_accounts", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("authority", DataType::Binary, false),
+                    Field::new("blockhash", DataType::Binary, false),
+                    Field::new("lamports_per_signature", DataType::UInt64, false),
+                    Field::new("state", DataType::UInt8, false),
+                ])
+            }),
+            rows: 0,
+            batch_threshold: BATCH_THRESHOLD,
+            plain_accounts: 0,
+            pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
+            authority_b: BinaryBuilder::new(),
+            blockhash_b: BinaryBuilder::new(),
+            lamports_per_signature_b: UInt64Builder::new(),
+            state_b: UInt8Builder::new(),
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
+                Arc::new(self.authority_b.finish()),
+                Arc::new(self.blockhash_b.finish()),
+                Arc::new(self.lamports_per_signature_b.finish()),
+                Arc::new(self.state_b.finish()),
+            ],
+        )
+        .ok()
+    }
+}
+
+impl Default for SystemDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::decoders::Decoder for SystemDecoder {
+    fn name(&self) -> &'static str {
+        "nonce_accounts"
+    }
+
+    fn owner(&self) -> Pubkey {
+        SYSTEM_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &SYSTEM_PROGRAM && (data_len == 0 || data_len == NonceAccount::SIZE as u64)
+    }
+
+    fn fixed_sizes(&self) -> &'static [u64] {
+        &[0, NonceAccount::SIZE as u64]
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        if data.is_empty() {
+            self.plain_accounts += 1;
+            return Ok(None);
+        }
+
+        let nonce = cast::<NonceAccount>(data)?;
+        if nonce.version > 1 {
+            anyhow::bail!("unknown nonce version {}", { nonce.version });
+        }
+
+        self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
+        self.authority_b.append_value(nonce.authority);
+        self.blockhash_b.append_value(nonce.blockhash);
+        self.lamports_per_signature_b
+            .append_value(nonce.lamports_per_signature);
+        self.state_b.append_value(nonce.state as u8);
+
+        self.rows += 1;
+
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+
+    fn skipped(&self) -> u64 {
+        self.plain_accounts
+    }
+}
@@ -0,0 +1,163 @@
+use arrow::{
+    array::{BinaryBuilder, Decimal128Builder, RecordBatch},
+    datatypes::{DataType, Field, Schema},
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use super::{BATCH_THRESHOLD, TOKEN_PROGRAM, TokenAccount};
+use crate::Pubkey;
+use crate::decoders::{SchemaRegistry, cast};
+
+/// Arrow has no UInt128; Decimal128(38, 0) holds any sum of u64 amounts.
+const TOTAL_PRECISION: u8 = 38;
+
+/// Sums `TokenAccount::amount` per mint instead of writing a row per account
+/// (`--aggregate-by-mint`).
+///
+/// Every parser thread keeps its own totals and flushes them whenever more
+/// than `BATCH_THRESHOLD` mints are buffered, so one mint can show up in
+/// several rows: `SUM(total_amount) ... GROUP BY mint` gives the final figure.
+pub struct AggregateMintDecoder {
+    schema: Arc<Schema>,
+    known_mints: Arc<HashSet<Pubkey>>,
+    totals: HashMap<Pubkey, u128>,
+    batch_threshold: usize,
+}
+
+impl AggregateMintDecoder {
+    pub fn new(known_mints: Arc<HashSet<Pubkey>>) -> Self {
+        Self {
+            schema: SchemaRegistry::get_or_insert("mint_aggregates", || {
+                Schema::new(vec![
+                    Field::new("mint", DataType::Binary, false),
+                    Field::new(
+                        "total_amount",
+                        DataType::Decimal128(TOTAL_PRECISION, 0),
+                        false,
+                    ),
+                ])
+            }),
+            known_mints,
+            totals: HashMap::new(),
+            batch_threshold: BATCH_THRESHOLD,
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.totals.is_empty() {
+            return None;
+        }
+
+        let mut mint_b = BinaryBuilder::with_capacity(self.totals.len(), self.totals.len() * 32);
+        let mut total_b = Decimal128Builder::with_capacity(self.totals.len())
+            .with_precision_and_scale(TOTAL_PRECISION, 0)
+            .ok()?;
+        for (mint, total) in self.totals.drain() {
+            mint_b.append_value(mint);
+            total_b.append_value(total as i128);
+        }
+
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![Arc::new(mint_b.finish()), Arc::new(total_b.finish())],
+        )
+        .ok()
+    }
+}
+
+impl crate::decoders::Decoder for AggregateMintDecoder {
+    fn name(&self) -> &'static str {
+        "mint_aggregates"
+    }
+
+    fn owner(&self) -> Pubkey {
+        TOKEN_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &TOKEN_PROGRAM && data_len == TokenAccount::SIZE as u64
+    }
+
+    fn fixed_sizes(&self) -> &'static [u64] {
+        &[TokenAccount::SIZE as u64]
+    }
+
+    fn decode(
+        &mut self,
+        _pubkey: Pubkey,
+        _write_version: u64,
+        data: &[u8],
+        include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        let acc = cast::<TokenAccount>(data)?;
+
+        if !include_spam && !self.known_mints.contains(&acc.mint) {
+            return Ok(None);
+        }
+
+        *self.totals.entry(acc.mint).or_default() += acc.amount as u128;
+
+        if self.totals.len() >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoders::Decoder;
+    use arrow::array::AsArray;
+    use arrow::datatypes::Decimal128Type;
+
+    fn token_account(mint: Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; TokenAccount::SIZE];
+        data[..32].copy_from_slice(mint.as_bytes());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1;
+        data
+    }
+
+    #[test]
+    fn sums_known_mints_past_u64() {
+        let (known, spam) = (Pubkey::new([1; 32]), Pubkey::new([2; 32]));
+        let mut decoder = AggregateMintDecoder::new(Arc::new(HashSet::from([known])));
+        for (mint, amount) in [(known, u64::MAX), (known, 2), (spam, 5)] {
+            let data = token_account(mint, amount);
+            assert!(decoder.matches(&TOKEN_PROGRAM, data.len() as u64));
+            assert!(
+                decoder
+                    .decode(Pubkey::new([9; 32]), 0, &data, false)
+                    .unwrap()
+                    .is_none()
+            );
+        }
+        let batch = decoder.flush().unwrap();
+
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(
+            batch.column(0).as_binary::<i32>().value(0),
+            known.as_bytes()
+        );
+        assert_eq!(
+            batch.column(1).as_primitive::<Decimal128Type>().value(0),
+            u64::MAX as i128 + 2
+        );
+        assert!(decoder.flush().is_none());
+    }
+}
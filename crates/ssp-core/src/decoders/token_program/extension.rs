@@ -0,0 +1,212 @@
+//! Token-2022 extensions.
+//!
+//! A Token-2022 mint or account with extensions is its base layout, zero
+//! padding up to `TokenAccount::SIZE`, one `AccountType` byte, and then a TLV
+//! sequence: a `u16` extension type, a `u16` length and that many bytes of
+//! value, repeated. Mints without extensions are the plain 82-byte `Mint`.
+
+use super::TokenAccount;
+use crate::Pubkey;
+use crate::decoders::borsh::Borsh;
+
+/// Offset of the `AccountType` byte, right after the largest base layout.
+pub const ACCOUNT_TYPE_OFFSET: usize = TokenAccount::SIZE;
+/// Offset of the first TLV entry.
+pub const TLV_OFFSET: usize = ACCOUNT_TYPE_OFFSET + 1;
+
+/// `AccountType` values.
+pub const ACCOUNT_TYPE_MINT: u8 = 1;
+pub const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// `ExtensionType` values decoded into their own variants.
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const MINT_CLOSE_AUTHORITY: u16 = 3;
+const CONFIDENTIAL_TRANSFER_MINT: u16 = 4;
+const NON_TRANSFERABLE: u16 = 9;
+const INTEREST_BEARING_CONFIG: u16 = 10;
+const PERMANENT_DELEGATE: u16 = 12;
+const METADATA_POINTER: u16 = 18;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransferFee {
+    /// First epoch this fee applies in.
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub transfer_fee_basis_points: u16,
+}
+
+/// One TLV entry. Pubkeys are Token-2022's `OptionalNonZeroPubkey`: all
+/// zeros means unset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Extension {
+    TransferFeeConfig {
+        transfer_fee_config_authority: Option<Pubkey>,
+        withdraw_withheld_authority: Option<Pubkey>,
+        withheld_amount: u64,
+        older_transfer_fee: TransferFee,
+        /// Takes over from `older_transfer_fee` at its `epoch`.
+        newer_transfer_fee: TransferFee,
+    },
+    MintCloseAuthority {
+        close_authority: Option<Pubkey>,
+    },
+    ConfidentialTransferMint {
+        authority: Option<Pubkey>,
+        auto_approve_new_accounts: bool,
+        /// ElGamal public key, all zeros when there's no auditor.
+        auditor_elgamal_pubkey: Option<[u8; 32]>,
+    },
+    InterestBearingConfig {
+        rate_authority: Option<Pubkey>,
+        initialization_timestamp: i64,
+        pre_update_average_rate: i16,
+        last_update_timestamp: i64,
+        /// Basis points per year.
+        current_rate: i16,
+    },
+    PermanentDelegate {
+        delegate: Option<Pubkey>,
+    },
+    NonTransferable,
+    MetadataPointer {
+        authority: Option<Pubkey>,
+        metadata_address: Option<Pubkey>,
+    },
+    /// Any other type, or a known one whose value has the wrong length.
+    Unknown(u16, Vec<u8>),
+}
+
+/// The extensions in `data`, the TLV region from `TLV_OFFSET` on. Stops at
+/// the first zero type (`Uninitialized`, i.e. padding) or at an entry that
+/// runs past the end of `data`.
+pub fn parse_extensions(data: &[u8]) -> Vec<Extension> {
+    let mut tlv = Borsh::new(data);
+    let mut extensions = Vec::new();
+    while let (Some(ty), Some(len)) = (tlv.u16(), tlv.u16()) {
+        if ty == 0 {
+            break;
+        }
+        let Some(value) = tlv.take(len as usize) else {
+            break;
+        };
+        extensions
+            .push(parse_value(ty, value).unwrap_or_else(|| Extension::Unknown(ty, value.to_vec())));
+    }
+    extensions
+}
+
+fn parse_value(ty: u16, value: &[u8]) -> Option<Extension> {
+    let mut r = Borsh::new(value);
+    let extension = match ty {
+        TRANSFER_FEE_CONFIG => Extension::TransferFeeConfig {
+            transfer_fee_config_authority: optional_pubkey(&mut r)?,
+            withdraw_withheld_authority: optional_pubkey(&mut r)?,
+            withheld_amount: r.u64()?,
+            older_transfer_fee: transfer_fee(&mut r)?,
+            newer_transfer_fee: transfer_fee(&mut r)?,
+        },
+        MINT_CLOSE_AUTHORITY => Extension::MintCloseAuthority {
+            close_authority: optional_pubkey(&mut r)?,
+        },
+        CONFIDENTIAL_TRANSFER_MINT => Extension::ConfidentialTransferMint {
+            authority: optional_pubkey(&mut r)?,
+            auto_approve_new_accounts: r.u8()? != 0,
+            auditor_elgamal_pubkey: optional_pubkey(&mut r)?.map(Pubkey::into_array),
+        },
+        INTEREST_BEARING_CONFIG => Extension::InterestBearingConfig {
+            rate_authority: optional_pubkey(&mut r)?,
+            initialization_timestamp: r.u64()? as i64,
+            pre_update_average_rate: r.u16()? as i16,
+            last_update_timestamp: r.u64()? as i64,
+            current_rate: r.u16()? as i16,
+        },
+        PERMANENT_DELEGATE => Extension::PermanentDelegate {
+            delegate: optional_pubkey(&mut r)?,
+        },
+        NON_TRANSFERABLE => Extension::NonTransferable,
+        METADATA_POINTER => Extension::MetadataPointer {
+            authority: optional_pubkey(&mut r)?,
+            metadata_address: optional_pubkey(&mut r)?,
+        },
+        _ => return None,
+    };
+    // A known type with trailing bytes is a layout we don't understand.
+    r.take(1).is_none().then_some(extension)
+}
+
+fn optional_pubkey(r: &mut Borsh) -> Option<Option<Pubkey>> {
+    let key = r.pubkey()?;
+    Some((!key.is_zero()).then_some(key))
+}
+
+fn transfer_fee(r: &mut Borsh) -> Option<TransferFee> {
+    Some(TransferFee {
+        epoch: r.u64()?,
+        maximum_fee: r.u64()?,
+        transfer_fee_basis_points: r.u16()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(ty: u16, value: &[u8]) -> Vec<u8> {
+        let mut entry = ty.to_le_bytes().to_vec();
+        entry.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        entry.extend_from_slice(value);
+        entry
+    }
+
+    #[test]
+    fn account_type_follows_the_token_account_layout() {
+        assert_eq!(ACCOUNT_TYPE_OFFSET, 165);
+        assert_eq!(TLV_OFFSET, 166);
+    }
+
+    #[test]
+    fn unknown_types_keep_their_value() {
+        let mut data = tlv(99, &[1, 2, 3]);
+        // A known type with a value of the wrong length.
+        data.extend(tlv(PERMANENT_DELEGATE, &[5; 31]));
+        data.extend(tlv(NON_TRANSFERABLE, &[]));
+        assert_eq!(
+            parse_extensions(&data),
+            [
+                Extension::Unknown(99, vec![1, 2, 3]),
+                Extension::Unknown(PERMANENT_DELEGATE, vec![5; 31]),
+                Extension::NonTransferable,
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_padding_and_truncated_entries() {
+        let mut data = tlv(NON_TRANSFERABLE, &[]);
+        data.extend([0; 4]);
+        data.extend(tlv(NON_TRANSFERABLE, &[]));
+        assert_eq!(parse_extensions(&data), [Extension::NonTransferable]);
+
+        // The length says 32 bytes, only 10 follow.
+        let mut data = tlv(NON_TRANSFERABLE, &[]);
+        data.extend(tlv(MINT_CLOSE_AUTHORITY, &[7; 32])[..14].iter());
+        assert_eq!(parse_extensions(&data), [Extension::NonTransferable]);
+
+        // The length itself is cut short.
+        let mut data = tlv(NON_TRANSFERABLE, &[]);
+        data.extend([MINT_CLOSE_AUTHORITY as u8, 0, 32]);
+        assert_eq!(parse_extensions(&data), [Extension::NonTransferable]);
+    }
+
+    #[test]
+    fn zero_pubkeys_are_unset() {
+        let data = tlv(METADATA_POINTER, &[[0; 32], [4; 32]].concat());
+        assert_eq!(
+            parse_extensions(&data),
+            [Extension::MetadataPointer {
+                authority: None,
+                metadata_address: Some(Pubkey::new([4; 32])),
+            }]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use arrow::{
-    array::{BinaryBuilder, BooleanBuilder, RecordBatch, UInt8Builder, UInt64Builder},
+    array::{
+        BinaryBuilder, BooleanBuilder, Float64Builder, RecordBatch, UInt8Builder, UInt64Builder,
+    },
     datatypes::{DataType, Field, Schema},
 };
 use std::collections::HashSet;
@@ -7,38 +9,57 @@ use std::sync::Arc;
 
 use super::{BATCH_THRESHOLD, Mint, TOKEN_PROGRAM};
 use crate::Pubkey;
+use crate::decoders::{SchemaRegistry, cast};
 
 pub struct MintDecoder {
-    schema: Schema,
+    schema: Arc<Schema>,
     rows: usize,
+    batch_threshold: usize,
     known_mints: Arc<HashSet<Pubkey>>,
     pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
     mint_authority_b: BinaryBuilder,
     freeze_authority_b: BinaryBuilder,
     supply_b: UInt64Builder,
     decimals_b: UInt8Builder,
     is_initialized_b: BooleanBuilder,
+    is_nft_b: BooleanBuilder,
+    total_supply_normalized_b: Float64Builder,
 }
 
 impl MintDecoder {
     pub fn new(known_mints: Arc<HashSet<Pubkey>>) -> Self {
+        Self::with_batch_size(known_mints, BATCH_THRESHOLD)
+    }
+
+    /// Emit a batch every `batch_size` rows instead of every `BATCH_THRESHOLD`.
+    pub fn with_batch_size(known_mints: Arc<HashSet<Pubkey>>, batch_size: usize) -> Self {
         Self {
             known_mints,
-            schema: Schema::new(vec![
-                Field::new("pubkey", DataType::Binary, false),
-                Field::new("mint_authority", DataType::Binary, true),
-                Field::new("freeze_authority", DataType::Binary, true),
-                Field::new("supply", DataType::UInt64, false),
-                Field::new("decimals", DataType::UInt8, false),
-                Field::new("is_initialized", DataType::Boolean, false),
-            ]),
+            schema: SchemaRegistry::get_or_insert("mints", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("mint_authority", DataType::Binary, true),
+                    Field::new("freeze_authority", DataType::Binary, true),
+                    Field::new("supply", DataType::UInt64, false),
+                    Field::new("decimals", DataType::UInt8, false),
+                    Field::new("is_initialized", DataType::Boolean, false),
+                    Field::new("is_nft", DataType::Boolean, false),
+                    Field::new("total_supply_normalized", DataType::Float64, false),
+                ])
+            }),
             rows: 0,
+            batch_threshold: batch_size,
             pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
             mint_authority_b: BinaryBuilder::new(),
             freeze_authority_b: BinaryBuilder::new(),
             supply_b: UInt64Builder::new(),
             decimals_b: UInt8Builder::new(),
             is_initialized_b: BooleanBuilder::new(),
+            is_nft_b: BooleanBuilder::new(),
+            total_supply_normalized_b: Float64Builder::new(),
         }
     }
 
@@ -49,14 +70,17 @@ impl MintDecoder {
         self.rows = 0;
 
         RecordBatch::try_new(
-            Arc::new(self.schema.clone()),
+            self.schema.clone(),
             vec![
                 Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
                 Arc::new(self.mint_authority_b.finish()),
                 Arc::new(self.freeze_authority_b.finish()),
                 Arc::new(self.supply_b.finish()),
                 Arc::new(self.decimals_b.finish()),
                 Arc::new(self.is_initialized_b.finish()),
+                Arc::new(self.is_nft_b.finish()),
+                Arc::new(self.total_supply_normalized_b.finish()),
             ],
         )
         .ok()
@@ -80,14 +104,25 @@ impl crate::decoders::Decoder for MintDecoder {
         owner == &TOKEN_PROGRAM && data_len == Mint::SIZE as u64
     }
 
-    fn decode(&mut self, pubkey: Pubkey, data: &[u8], include_spam: bool) -> Option<RecordBatch> {
+    fn fixed_sizes(&self) -> &'static [u64] {
+        &[Mint::SIZE as u64]
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
         if !include_spam && !self.known_mints.contains(&pubkey) {
-            return None;
+            return Ok(None);
         }
 
-        let mint = bytemuck::from_bytes::<Mint>(data);
+        let mint = cast::<Mint>(data)?;
 
         self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
 
         match mint.mint_authority.get() {
             Some(pk) => self.mint_authority_b.append_value(pk),
@@ -101,17 +136,24 @@ impl crate::decoders::Decoder for MintDecoder {
         self.supply_b.append_value(mint.supply);
         self.decimals_b.append_value(mint.decimals);
         self.is_initialized_b.append_value(mint.is_initialized != 0);
+        self.is_nft_b.append_value(mint.is_nft());
+        self.total_supply_normalized_b
+            .append_value(mint.supply as f64 / 10_f64.powi(mint.decimals as i32));
 
         self.rows += 1;
 
-        if self.rows >= BATCH_THRESHOLD {
-            self.build_batch()
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
         } else {
-            None
+            Ok(None)
         }
     }
 
     fn flush(&mut self) -> Option<RecordBatch> {
         self.build_batch()
     }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
 }
@@ -1,16 +1,27 @@
+//! SPL Token (`Tokenkeg…`) account layouts and decoders.
+//!
+//! Token-2022 (`TokenzQd…`) is a separate program: its mints and accounts
+//! share the base layouts here but may carry extensions after them, and
+//! they are owned by `TOKEN_2022_PROGRAM`. `DecoderRouter` keys on
+//! `Decoder::owner()`, so these decoders never see Token-2022 accounts;
+//! `Token2022MintDecoder` handles its mints, with `extension` parsing the TLV
+//! tail.
+
+pub mod aggregate;
+pub mod extension;
 pub mod mint;
+pub mod multisig;
+pub mod token_2022_mint;
 pub mod token_account;
 
+pub use super::BATCH_THRESHOLD;
 use super::COptionPubkey;
 use bytemuck::{Pod, Zeroable};
 
 use crate::Pubkey;
 
-pub const BATCH_THRESHOLD: usize = 16_384;
-pub const TOKEN_PROGRAM: Pubkey = Pubkey::new([
-    6, 221, 246, 225, 215, 101, 161, 147, 217, 203, 225, 70, 206, 235, 121, 172, 28, 180, 133, 237,
-    95, 91, 55, 145, 58, 140, 245, 133, 126, 255, 0, 169,
-]);
+pub const TOKEN_PROGRAM: Pubkey = Pubkey::TOKEN_PROGRAM;
+pub const TOKEN_2022_PROGRAM: Pubkey = Pubkey::TOKEN_2022_PROGRAM;
 
 //Solana specific C like OptionPubkey struct for C compatability.
 
@@ -67,8 +78,40 @@ unsafe impl Pod for TokenAccount {}
 
 impl TokenAccount {
     pub const SIZE: usize = 165;
+
+    /// `AccountState` name, `None` for values outside the enum.
+    pub fn state_name(&self) -> Option<&'static str> {
+        match self.state {
+            0 => Some("Uninitialized"),
+            1 => Some("Initialized"),
+            2 => Some("Frozen"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Zeroable, Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct Multisig {
+    pub m: u8, // signatures required
+    pub n: u8, // valid signers
+    pub is_initialized: u8,
+    pub signers: [Pubkey; Multisig::MAX_SIGNERS],
+}
+unsafe impl Pod for Multisig {}
+
+impl Multisig {
+    pub const SIZE: usize = 355;
+    pub const MAX_SIGNERS: usize = 11;
+
+    /// The first `n` signer slots; the rest are unused.
+    pub fn valid_signers(&self) -> &[Pubkey] {
+        let signers = &self.signers;
+        &signers[..(self.n as usize).min(Self::MAX_SIGNERS)]
+    }
 }
 
 //Comptime size checks.
 const _: () = assert!(size_of::<Mint>() == Mint::SIZE);
 const _: () = assert!(size_of::<TokenAccount>() == TokenAccount::SIZE);
+const _: () = assert!(size_of::<Multisig>() == Multisig::SIZE);
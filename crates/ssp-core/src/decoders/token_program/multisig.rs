@@ -0,0 +1,200 @@
+use arrow::{
+    array::{BinaryBuilder, BooleanBuilder, ListBuilder, RecordBatch, UInt8Builder, UInt64Builder},
+    datatypes::{DataType, Field, Schema},
+};
+use std::sync::Arc;
+
+use super::{BATCH_THRESHOLD, Multisig, TOKEN_PROGRAM};
+use crate::Pubkey;
+use crate::decoders::{SchemaRegistry, cast};
+
+/// Decodes 355-byte token-program multisig accounts. Not spam-filtered:
+/// multisigs aren't tied to a mint.
+pub struct MultisigDecoder {
+    schema: Arc<Schema>,
+    rows: usize,
+    batch_threshold: usize,
+    pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
+    m_b: UInt8Builder,
+    n_b: UInt8Builder,
+    is_initialized_b: BooleanBuilder,
+    signers_b: ListBuilder<BinaryBuilder>,
+}
+
+impl MultisigDecoder {
+    pub fn new() -> Self {
+        let signer_field = Field::new("item", DataType::Binary, false);
+        Self {
+            schema: SchemaRegistry::get_or_insert("multisigs", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("m", DataType::UInt8, false),
+                    Field::new("n", DataType::UInt8, false),
+                    Field::new("is_initialized", DataType::Boolean, false),
+                    Field::new(
+                        "signers",
+                        DataType::List(Arc::new(signer_field.clone())),
+                        false,
+                    ),
+                ])
+            }),
+            rows: 0,
+            batch_threshold: BATCH_THRESHOLD,
+            pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
+            m_b: UInt8Builder::new(),
+            n_b: UInt8Builder::new(),
+            is_initialized_b: BooleanBuilder::new(),
+            signers_b: ListBuilder::new(BinaryBuilder::new()).with_field(signer_field),
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
+                Arc::new(self.m_b.finish()),
+                Arc::new(self.n_b.finish()),
+                Arc::new(self.is_initialized_b.finish()),
+                Arc::new(self.signers_b.finish()),
+            ],
+        )
+        .ok()
+    }
+}
+
+impl Default for MultisigDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl crate::decoders::Decoder for MultisigDecoder {
+    fn name(&self) -> &'static str {
+        "multisigs"
+    }
+
+    fn owner(&self) -> Pubkey {
+        TOKEN_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &TOKEN_PROGRAM && data_len == Multisig::SIZE as u64
+    }
+
+    fn fixed_sizes(&self) -> &'static [u64] {
+        &[Multisig::SIZE as u64]
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        _include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        let multisig = cast::<Multisig>(data)?;
+        if multisig.n as usize > Multisig::MAX_SIGNERS || multisig.m > multisig.n {
+            anyhow::bail!("invalid multisig m={} n={}", multisig.m, multisig.n);
+        }
+
+        self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
+        self.m_b.append_value(multisig.m);
+        self.n_b.append_value(multisig.n);
+        self.is_initialized_b
+            .append_value(multisig.is_initialized != 0);
+        for signer in multisig.valid_signers() {
+            self.signers_b.values().append_value(signer);
+        }
+        self.signers_b.append(true);
+
+        self.rows += 1;
+
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoders::Decoder;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::UInt8Type;
+
+    fn multisig_data(m: u8, n: u8) -> Vec<u8> {
+        let mut data = vec![0u8; Multisig::SIZE];
+        data[0] = m;
+        data[1] = n;
+        data[2] = 1;
+        // Every slot is filled; only the first `n` are signers.
+        for (i, slot) in data[3..].chunks_mut(32).enumerate() {
+            slot.fill(i as u8 + 1);
+        }
+        data
+    }
+
+    #[test]
+    fn decodes_the_first_n_signers() {
+        let mut decoder = MultisigDecoder::new();
+        let data = multisig_data(2, 3);
+        assert!(decoder.matches(&TOKEN_PROGRAM, data.len() as u64));
+        assert!(
+            decoder
+                .decode(Pubkey::new([9; 32]), 0, &data, false)
+                .unwrap()
+                .is_none()
+        );
+        let batch = decoder.flush().unwrap();
+
+        let col = |name: &str| batch.column_by_name(name).unwrap();
+        assert_eq!(col("m").as_primitive::<UInt8Type>().value(0), 2);
+        assert_eq!(col("n").as_primitive::<UInt8Type>().value(0), 3);
+        assert!(col("is_initialized").as_boolean().value(0));
+        let signers = col("signers").as_list::<i32>().value(0);
+        let signers = signers.as_binary::<i32>();
+        assert_eq!(signers.len(), 3);
+        for (i, signer) in signers.iter().enumerate() {
+            assert_eq!(signer.unwrap(), [i as u8 + 1; 32]);
+        }
+    }
+
+    #[test]
+    fn rejects_impossible_thresholds() {
+        let mut decoder = MultisigDecoder::new();
+        for (m, n) in [(3, 2), (1, 12)] {
+            let data = multisig_data(m, n);
+            assert!(
+                decoder
+                    .decode(Pubkey::new([9; 32]), 0, &data, false)
+                    .is_err()
+            );
+        }
+        assert!(decoder.flush().is_none());
+    }
+}
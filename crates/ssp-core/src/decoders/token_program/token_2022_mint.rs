@@ -0,0 +1,440 @@
+use arrow::{
+    array::{
+        BinaryBuilder, BooleanBuilder, Int16Builder, RecordBatch, UInt8Builder, UInt16Builder,
+        UInt64Builder,
+    },
+    datatypes::{DataType, Field, Schema},
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::extension::{
+    ACCOUNT_TYPE_MINT, ACCOUNT_TYPE_OFFSET, Extension, TLV_OFFSET, parse_extensions,
+};
+use super::{BATCH_THRESHOLD, Mint, Multisig, TOKEN_2022_PROGRAM};
+use crate::Pubkey;
+use crate::decoders::{SchemaRegistry, cast};
+
+/// Token-2022 mints: the base `Mint` columns plus one nullable column per
+/// extension field we surface. A column is NULL when the mint doesn't carry
+/// that extension; `other_extensions` counts the ones without columns.
+pub struct Token2022MintDecoder {
+    schema: Arc<Schema>,
+    rows: usize,
+    batch_threshold: usize,
+    skipped: u64,
+    known_mints: Arc<HashSet<Pubkey>>,
+    pubkey_b: BinaryBuilder,
+    write_version_b: UInt64Builder,
+    mint_authority_b: BinaryBuilder,
+    freeze_authority_b: BinaryBuilder,
+    supply_b: UInt64Builder,
+    decimals_b: UInt8Builder,
+    is_initialized_b: BooleanBuilder,
+    transfer_fee_basis_points_b: UInt16Builder,
+    transfer_fee_maximum_b: UInt64Builder,
+    withheld_amount_b: UInt64Builder,
+    close_authority_b: BinaryBuilder,
+    confidential_transfer_authority_b: BinaryBuilder,
+    interest_rate_b: Int16Builder,
+    permanent_delegate_b: BinaryBuilder,
+    non_transferable_b: BooleanBuilder,
+    metadata_address_b: BinaryBuilder,
+    other_extensions_b: UInt16Builder,
+}
+
+impl Token2022MintDecoder {
+    pub fn new(known_mints: Arc<HashSet<Pubkey>>) -> Self {
+        Self::with_batch_size(known_mints, BATCH_THRESHOLD)
+    }
+
+    /// Emit a batch every `batch_size` rows instead of every `BATCH_THRESHOLD`.
+    pub fn with_batch_size(known_mints: Arc<HashSet<Pubkey>>, batch_size: usize) -> Self {
+        Self {
+            known_mints,
+            schema: SchemaRegistry::get_or_insert("token_2022_mints", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("mint_authority", DataType::Binary, true),
+                    Field::new("freeze_authority", DataType::Binary, true),
+                    Field::new("supply", DataType::UInt64, false),
+                    Field::new("decimals", DataType::UInt8, false),
+                    Field::new("is_initialized", DataType::Boolean, false),
+                    Field::new("transfer_fee_basis_points", DataType::UInt16, true),
+                    Field::new("transfer_fee_maximum", DataType::UInt64, true),
+                    Field::new("withheld_amount", DataType::UInt64, true),
+                    Field::new("close_authority", DataType::Binary, true),
+                    Field::new("confidential_transfer_authority", DataType::Binary, true),
+                    Field::new("interest_rate", DataType::Int16, true),
+                    Field::new("permanent_delegate", DataType::Binary, true),
+                    Field::new("non_transferable", DataType::Boolean, false),
+                    Field::new("metadata_address", DataType::Binary, true),
+                    Field::new("other_extensions", DataType::UInt16, false),
+                ])
+            }),
+            rows: 0,
+            batch_threshold: batch_size,
+            skipped: 0,
+            pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
+            mint_authority_b: BinaryBuilder::new(),
+            freeze_authority_b: BinaryBuilder::new(),
+            supply_b: UInt64Builder::new(),
+            decimals_b: UInt8Builder::new(),
+            is_initialized_b: BooleanBuilder::new(),
+            transfer_fee_basis_points_b: UInt16Builder::new(),
+            transfer_fee_maximum_b: UInt64Builder::new(),
+            withheld_amount_b: UInt64Builder::new(),
+            close_authority_b: BinaryBuilder::new(),
+            confidential_transfer_authority_b: BinaryBuilder::new(),
+            interest_rate_b: Int16Builder::new(),
+            permanent_delegate_b: BinaryBuilder::new(),
+            non_transferable_b: BooleanBuilder::new(),
+            metadata_address_b: BinaryBuilder::new(),
+            other_extensions_b: UInt16Builder::new(),
+        }
+    }
+
+    fn build_batch(&mut self) -> Option<RecordBatch> {
+        if self.rows == 0 {
+            return None;
+        }
+        self.rows = 0;
+
+        RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
+                Arc::new(self.mint_authority_b.finish()),
+                Arc::new(self.freeze_authority_b.finish()),
+                Arc::new(self.supply_b.finish()),
+                Arc::new(self.decimals_b.finish()),
+                Arc::new(self.is_initialized_b.finish()),
+                Arc::new(self.transfer_fee_basis_points_b.finish()),
+                Arc::new(self.transfer_fee_maximum_b.finish()),
+                Arc::new(self.withheld_amount_b.finish()),
+                Arc::new(self.close_authority_b.finish()),
+                Arc::new(self.confidential_transfer_authority_b.finish()),
+                Arc::new(self.interest_rate_b.finish()),
+                Arc::new(self.permanent_delegate_b.finish()),
+                Arc::new(self.non_transferable_b.finish()),
+                Arc::new(self.metadata_address_b.finish()),
+                Arc::new(self.other_extensions_b.finish()),
+            ],
+        )
+        .ok()
+    }
+}
+
+/// The per-row extension columns, NULL/false/0 until an extension sets them.
+#[derive(Default)]
+struct ExtensionColumns {
+    transfer_fee_basis_points: Option<u16>,
+    transfer_fee_maximum: Option<u64>,
+    withheld_amount: Option<u64>,
+    close_authority: Option<Pubkey>,
+    confidential_transfer_authority: Option<Pubkey>,
+    interest_rate: Option<i16>,
+    permanent_delegate: Option<Pubkey>,
+    non_transferable: bool,
+    metadata_address: Option<Pubkey>,
+    other_extensions: u16,
+}
+
+impl ExtensionColumns {
+    fn new(extensions: Vec<Extension>) -> Self {
+        let mut cols = Self::default();
+        for ext in extensions {
+            match ext {
+                // The newer fee is the one in force from its epoch on; the
+                // older one only matters for transfers before it.
+                Extension::TransferFeeConfig {
+                    withheld_amount,
+                    newer_transfer_fee,
+                    ..
+                } => {
+                    cols.transfer_fee_basis_points =
+                        Some(newer_transfer_fee.transfer_fee_basis_points);
+                    cols.transfer_fee_maximum = Some(newer_transfer_fee.maximum_fee);
+                    cols.withheld_amount = Some(withheld_amount);
+                }
+                Extension::MintCloseAuthority { close_authority } => {
+                    cols.close_authority = close_authority;
+                }
+                Extension::ConfidentialTransferMint { authority, .. } => {
+                    cols.confidential_transfer_authority = authority;
+                }
+                Extension::InterestBearingConfig { current_rate, .. } => {
+                    cols.interest_rate = Some(current_rate);
+                }
+                Extension::PermanentDelegate { delegate } => cols.permanent_delegate = delegate,
+                Extension::NonTransferable => cols.non_transferable = true,
+                Extension::MetadataPointer {
+                    metadata_address, ..
+                } => cols.metadata_address = metadata_address,
+                Extension::Unknown(..) => cols.other_extensions += 1,
+            }
+        }
+        cols
+    }
+}
+
+impl crate::decoders::Decoder for Token2022MintDecoder {
+    fn name(&self) -> &'static str {
+        "token_2022_mints"
+    }
+
+    fn owner(&self) -> Pubkey {
+        TOKEN_2022_PROGRAM
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// A plain 82-byte mint, or anything past the base token account layout,
+    /// whose `AccountType` byte `decode` checks. Token-2022 pads extended
+    /// accounts so they're never `Multisig::SIZE`.
+    fn matches(&self, owner: &Pubkey, data_len: u64) -> bool {
+        owner == &TOKEN_2022_PROGRAM
+            && (data_len == Mint::SIZE as u64
+                || (data_len > ACCOUNT_TYPE_OFFSET as u64 && data_len != Multisig::SIZE as u64))
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        let extended = data.len() > Mint::SIZE;
+        // Extended token accounts land here too; those aren't mints.
+        if extended && data[ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_MINT {
+            self.skipped += 1;
+            return Ok(None);
+        }
+        if !include_spam && !self.known_mints.contains(&pubkey) {
+            return Ok(None);
+        }
+
+        let mint = cast::<Mint>(&data[..Mint::SIZE])?;
+        let ext = if extended {
+            ExtensionColumns::new(parse_extensions(&data[TLV_OFFSET..]))
+        } else {
+            ExtensionColumns::default()
+        };
+
+        self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
+
+        match mint.mint_authority.get() {
+            Some(pk) => self.mint_authority_b.append_value(pk),
+            None => self.mint_authority_b.append_null(),
+        }
+        match mint.freeze_authority.get() {
+            Some(pk) => self.freeze_authority_b.append_value(pk),
+            None => self.freeze_authority_b.append_null(),
+        }
+
+        self.supply_b.append_value(mint.supply);
+        self.decimals_b.append_value(mint.decimals);
+        self.is_initialized_b.append_value(mint.is_initialized != 0);
+
+        self.transfer_fee_basis_points_b
+            .append_option(ext.transfer_fee_basis_points);
+        self.transfer_fee_maximum_b
+            .append_option(ext.transfer_fee_maximum);
+        self.withheld_amount_b.append_option(ext.withheld_amount);
+        self.close_authority_b.append_option(ext.close_authority);
+        self.confidential_transfer_authority_b
+            .append_option(ext.confidential_transfer_authority);
+        self.interest_rate_b.append_option(ext.interest_rate);
+        self.permanent_delegate_b
+            .append_option(ext.permanent_delegate);
+        self.non_transferable_b.append_value(ext.non_transferable);
+        self.metadata_address_b.append_option(ext.metadata_address);
+        self.other_extensions_b.append_value(ext.other_extensions);
+
+        self.rows += 1;
+
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Option<RecordBatch> {
+        self.build_batch()
+    }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
+
+    fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoders::Decoder;
+    use arrow::array::AsArray;
+    use arrow::datatypes::{Int16Type, UInt16Type, UInt64Type};
+
+    fn tlv(ty: u16, value: &[u8]) -> Vec<u8> {
+        let mut entry = ty.to_le_bytes().to_vec();
+        entry.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        entry.extend_from_slice(value);
+        entry
+    }
+
+    /// An initialized mint with `decimals` 6, padded to the base token
+    /// account layout, then `account_type` and `extensions`.
+    fn extended(account_type: u8, extensions: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0u8; ACCOUNT_TYPE_OFFSET];
+        data[36..44].copy_from_slice(&1_000u64.to_le_bytes());
+        data[44] = 6;
+        data[45] = 1;
+        data.push(account_type);
+        data.extend(extensions.concat());
+        data
+    }
+
+    fn decode_one(data: &[u8]) -> RecordBatch {
+        let mut decoder = Token2022MintDecoder::new(Default::default());
+        assert!(decoder.matches(&TOKEN_2022_PROGRAM, data.len() as u64));
+        assert!(
+            decoder
+                .decode(Pubkey::new([1; 32]), 0, data, true)
+                .unwrap()
+                .is_none()
+        );
+        decoder.flush().unwrap()
+    }
+
+    #[test]
+    fn extended_token_accounts_are_skipped() {
+        let mut decoder = Token2022MintDecoder::new(Default::default());
+        let data = extended(ACCOUNT_TYPE_MINT + 1, &[]);
+        assert!(
+            decoder
+                .decode(Pubkey::new([1; 32]), 0, &data, true)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(decoder.skipped(), 1);
+        assert!(decoder.flush().is_none());
+    }
+
+    #[test]
+    fn plain_mint_has_no_extension_columns() {
+        let mut data = extended(ACCOUNT_TYPE_MINT, &[]);
+        data.truncate(Mint::SIZE);
+        let batch = decode_one(&data);
+        assert_eq!(
+            batch
+                .column_by_name("supply")
+                .unwrap()
+                .as_primitive::<UInt64Type>()
+                .value(0),
+            1_000
+        );
+        for name in [
+            "transfer_fee_basis_points",
+            "close_authority",
+            "metadata_address",
+        ] {
+            assert!(batch.column_by_name(name).unwrap().is_null(0), "{name}");
+        }
+        assert!(
+            !batch
+                .column_by_name("non_transferable")
+                .unwrap()
+                .as_boolean()
+                .value(0)
+        );
+    }
+
+    #[test]
+    fn each_extension_fills_its_columns() {
+        let fee = |epoch: u64, max: u64, bps: u16| {
+            [
+                &epoch.to_le_bytes()[..],
+                &max.to_le_bytes(),
+                &bps.to_le_bytes(),
+            ]
+            .concat()
+        };
+        let transfer_fee = [
+            &[2; 32][..],
+            &[3; 32],
+            &77u64.to_le_bytes(),
+            &fee(1, 10, 5),
+            &fee(9, 500, 25),
+        ]
+        .concat();
+        let confidential = [&[5; 32][..], &[1], &[0; 32]].concat();
+        let interest = [
+            &[6; 32][..],
+            &1u64.to_le_bytes(),
+            &0i16.to_le_bytes(),
+            &2u64.to_le_bytes(),
+            &(-150i16).to_le_bytes(),
+        ]
+        .concat();
+        let data = extended(
+            ACCOUNT_TYPE_MINT,
+            &[
+                tlv(1, &transfer_fee),
+                tlv(3, &[4; 32]),
+                tlv(4, &confidential),
+                tlv(10, &interest),
+                tlv(12, &[7; 32]),
+                tlv(9, &[]),
+                tlv(18, &[[0; 32], [8; 32]].concat()),
+                tlv(99, &[1, 2]),
+            ],
+        );
+        let batch = decode_one(&data);
+        let col = |name: &str| batch.column_by_name(name).unwrap();
+        let binary = |name: &str| col(name).as_binary::<i32>().value(0).to_vec();
+
+        assert_eq!(
+            col("transfer_fee_basis_points")
+                .as_primitive::<UInt16Type>()
+                .value(0),
+            25
+        );
+        assert_eq!(
+            col("transfer_fee_maximum")
+                .as_primitive::<UInt64Type>()
+                .value(0),
+            500
+        );
+        assert_eq!(
+            col("withheld_amount").as_primitive::<UInt64Type>().value(0),
+            77
+        );
+        assert_eq!(binary("close_authority"), [4; 32]);
+        assert_eq!(binary("confidential_transfer_authority"), [5; 32]);
+        assert_eq!(
+            col("interest_rate").as_primitive::<Int16Type>().value(0),
+            -150
+        );
+        assert_eq!(binary("permanent_delegate"), [7; 32]);
+        assert!(col("non_transferable").as_boolean().value(0));
+        assert_eq!(binary("metadata_address"), [8; 32]);
+        assert_eq!(
+            col("other_extensions")
+                .as_primitive::<UInt16Type>()
+                .value(0),
+            1
+        );
+    }
+}
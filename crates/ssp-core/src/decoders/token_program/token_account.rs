@@ -1,5 +1,5 @@
 use arrow::{
-    array::{BinaryBuilder, RecordBatch, UInt8Builder, UInt64Builder},
+    array::{BinaryBuilder, RecordBatch, StringBuilder, UInt8Builder, UInt64Builder},
     datatypes::{DataType, Field, Schema},
 };
 use std::collections::HashSet;
@@ -7,17 +7,21 @@ use std::sync::Arc;
 
 use super::{BATCH_THRESHOLD, TOKEN_PROGRAM, TokenAccount};
 use crate::Pubkey;
+use crate::decoders::{SchemaRegistry, cast};
 
 pub struct TokenAccountDecoder {
-    pub schema: Schema,
+    pub schema: Arc<Schema>,
     pub rows: usize,
+    pub batch_threshold: usize,
     known_mints: Arc<HashSet<Pubkey>>,
     pub pubkey_b: BinaryBuilder,
+    pub write_version_b: UInt64Builder,
     pub mint_b: BinaryBuilder,
     pub owner_b: BinaryBuilder,
     pub amount_b: UInt64Builder,
     pub delegate_b: BinaryBuilder,
     pub state_b: UInt8Builder,
+    pub state_str_b: StringBuilder,
     pub is_native_b: UInt64Builder,
     pub delegated_amount_b: UInt64Builder,
     pub close_authority_b: BinaryBuilder,
@@ -31,14 +35,16 @@ impl TokenAccountDecoder {
         self.rows = 0;
 
         RecordBatch::try_new(
-            Arc::new(self.schema.clone()),
+            self.schema.clone(),
             vec![
                 Arc::new(self.pubkey_b.finish()),
+                Arc::new(self.write_version_b.finish()),
                 Arc::new(self.mint_b.finish()),
                 Arc::new(self.owner_b.finish()),
                 Arc::new(self.amount_b.finish()),
                 Arc::new(self.delegate_b.finish()),
                 Arc::new(self.state_b.finish()),
+                Arc::new(self.state_str_b.finish()),
                 Arc::new(self.is_native_b.finish()),
                 Arc::new(self.delegated_amount_b.finish()),
                 Arc::new(self.close_authority_b.finish()),
@@ -65,14 +71,25 @@ impl crate::decoders::Decoder for TokenAccountDecoder {
         owner == &TOKEN_PROGRAM && data_len == TokenAccount::SIZE as u64
     }
 
-    fn decode(&mut self, pubkey: Pubkey, data: &[u8], include_spam: bool) -> Option<RecordBatch> {
-        let acc = bytemuck::from_bytes::<TokenAccount>(data);
+    fn fixed_sizes(&self) -> &'static [u64] {
+        &[TokenAccount::SIZE as u64]
+    }
+
+    fn decode(
+        &mut self,
+        pubkey: Pubkey,
+        write_version: u64,
+        data: &[u8],
+        include_spam: bool,
+    ) -> anyhow::Result<Option<RecordBatch>> {
+        let acc = cast::<TokenAccount>(data)?;
 
         if !include_spam && !self.known_mints.contains(&acc.mint) {
-            return None;
+            return Ok(None);
         }
 
         self.pubkey_b.append_value(pubkey);
+        self.write_version_b.append_value(write_version);
         self.mint_b.append_value(acc.mint);
         self.owner_b.append_value(acc.owner);
         self.amount_b.append_value(acc.amount);
@@ -83,6 +100,12 @@ impl crate::decoders::Decoder for TokenAccountDecoder {
         }
 
         self.state_b.append_value(acc.state);
+        match acc.state_name() {
+            Some(name) => self.state_str_b.append_value(name),
+            None => self
+                .state_str_b
+                .append_value(format!("Unknown({})", acc.state)),
+        }
 
         match acc.is_native.get() {
             Some(v) => self.is_native_b.append_value(v),
@@ -98,41 +121,57 @@ impl crate::decoders::Decoder for TokenAccountDecoder {
 
         self.rows += 1;
 
-        if self.rows >= BATCH_THRESHOLD {
-            self.build_batch()
+        if self.rows >= self.batch_threshold {
+            Ok(self.build_batch())
         } else {
-            None
+            Ok(None)
         }
     }
 
     fn flush(&mut self) -> Option<RecordBatch> {
         self.build_batch()
     }
+
+    fn set_batch_size(&mut self, size: usize) {
+        self.batch_threshold = size;
+    }
 }
 
 impl TokenAccountDecoder {
     pub fn new(known_mints: Arc<HashSet<Pubkey>>) -> Self {
+        Self::with_batch_size(known_mints, BATCH_THRESHOLD)
+    }
+
+    /// Emit a batch every `batch_size` rows instead of every `BATCH_THRESHOLD`.
+    pub fn with_batch_size(known_mints: Arc<HashSet<Pubkey>>, batch_size: usize) -> Self {
         Self {
-            schema: Schema::new(vec![
-                Field::new("pubkey", DataType::Binary, false),
-                Field::new("mint", DataType::Binary, false),
-                Field::new("owner", DataType::Binary, false),
-                Field::new("amount", DataType::UInt64, false),
-                Field::new("delegate", DataType::Binary, true),
-                Field::new("state", DataType::UInt8, false),
-                Field::new("is_native", DataType::UInt64, true),
-                Field::new("delegated_amount", DataType::UInt64, false),
-                Field::new("close_authority", DataType::Binary, true),
-            ]),
+            schema: SchemaRegistry::get_or_insert("token_accounts", || {
+                Schema::new(vec![
+                    Field::new("pubkey", DataType::Binary, false),
+                    Field::new("write_version", DataType::UInt64, false),
+                    Field::new("mint", DataType::Binary, false),
+                    Field::new("owner", DataType::Binary, false),
+                    Field::new("amount", DataType::UInt64, false),
+                    Field::new("delegate", DataType::Binary, true),
+                    Field::new("state", DataType::UInt8, false),
+                    Field::new("state_str", DataType::Utf8, false),
+                    Field::new("is_native", DataType::UInt64, true),
+                    Field::new("delegated_amount", DataType::UInt64, false),
+                    Field::new("close_authority", DataType::Binary, true),
+                ])
+            }),
 
             known_mints,
             rows: 0,
+            batch_threshold: batch_size,
             pubkey_b: BinaryBuilder::new(),
+            write_version_b: UInt64Builder::new(),
             mint_b: BinaryBuilder::new(),
             owner_b: BinaryBuilder::new(),
             amount_b: UInt64Builder::new(),
             delegate_b: BinaryBuilder::new(),
             state_b: UInt8Builder::new(),
+            state_str_b: StringBuilder::new(),
             is_native_b: UInt64Builder::new(),
             delegated_amount_b: UInt64Builder::new(),
             close_authority_b: BinaryBuilder::new(),
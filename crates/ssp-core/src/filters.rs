@@ -1,24 +1,141 @@
+use std::collections::HashSet;
+use std::fmt;
+
 use crate::Pubkey;
 use crate::parser::AccountHeader;
 
+#[derive(Clone)]
 pub struct ResolvedFilters {
     pub owner: Option<Pubkey>,
+    /// Owners whose accounts are dropped. Only consulted when `owner` is
+    /// unset: an explicit owner is a whitelist and wins.
+    pub exclude_owners: HashSet<Pubkey>,
     pub hash: Option<[u8; 32]>,
+    /// Keep only accounts whose hash starts with these bytes. Hashes are
+    /// uniform, so a one-byte prefix keeps a reproducible ~1/256 of them.
+    pub hash_prefix: Option<Vec<u8>>,
     pub pubkey: Option<Pubkey>,
+    /// Inclusive bounds on `AccountHeader::write_version`.
+    ///
+    /// `write_version` is not a slot number — it's an internal, monotonically
+    /// increasing counter assigned by the validator when an account is stored.
+    /// Comparing it across snapshots only makes sense if they come from the
+    /// same validator lineage; treat it as an ordering key, not a timestamp.
+    /// How it relates to slots depends on the client: some derive it from
+    /// the slot being stored, others from a global store counter, and the
+    /// scheme has changed between releases. A cutoff taken from one snapshot
+    /// ("everything written after this") only carries over to snapshots from
+    /// the same client and version.
+    pub min_write_version: Option<u64>,
+    pub max_write_version: Option<u64>,
     pub include_dead: bool,
     pub include_spam: bool,
+    /// Keep `num` out of every `den` accounts. Chosen by pubkey, so repeated
+    /// runs over the same snapshot sample the same accounts.
+    pub sample: Option<(u64, u64)>,
+    /// Keep only accounts whose data starts with these bytes
+    /// (e.g. an 8-byte Anchor discriminator).
+    pub data_prefix: Option<Vec<u8>>,
 }
 
 impl ResolvedFilters {
-    pub fn matches(&self, header: &AccountHeader) -> bool {
+    /// Sampling gate, checked before decoders and `matches` so every output
+    /// sees the same reduced stream.
+    #[inline]
+    pub fn sampled(&self, pubkey: &Pubkey) -> bool {
+        self.sample.is_none_or(|(num, den)| {
+            // Pubkeys are hash/curve outputs, so their low bytes are already uniform.
+            let h = u64::from_le_bytes(pubkey.as_bytes()[..8].try_into().unwrap());
+            h % den < num
+        })
+    }
+
+    /// No filter beyond the dead-account check is set, so `matches` reduces
+    /// to `include_dead || lamports != 0`. `sample` isn't considered: it is
+    /// checked separately, through `sampled`.
+    pub fn is_trivial(&self) -> bool {
+        self.owner.is_none()
+            && self.exclude_owners.is_empty()
+            && self.hash.is_none()
+            && self.hash_prefix.is_none()
+            && self.pubkey.is_none()
+            && self.min_write_version.is_none()
+            && self.max_write_version.is_none()
+            && self.data_prefix.is_none()
+    }
+
+    /// `data` is only inspected when `data_prefix` is set.
+    pub fn matches(&self, header: &AccountHeader, data: &[u8]) -> bool {
         if !self.include_dead && header.lamports == 0 {
             return false;
         }
 
-        let owner = self.owner.is_none_or(|o| o == header.owner);
+        // Whitelist first; the blacklist only applies without one.
+        let owner = match self.owner {
+            Some(o) => o == header.owner,
+            None => self.exclude_owners.is_empty() || !self.exclude_owners.contains(&header.owner),
+        };
         let hash = self.hash.is_none_or(|h| h == header.hash);
+        let hash_prefix = self
+            .hash_prefix
+            .as_deref()
+            .is_none_or(|p| header.hash.starts_with(p));
         let pubkey = self.pubkey.is_none_or(|pk| pk == header.pubkey);
+        let min_wv = self
+            .min_write_version
+            .is_none_or(|v| header.write_version >= v);
+        let max_wv = self
+            .max_write_version
+            .is_none_or(|v| header.write_version <= v);
+        let prefix = self
+            .data_prefix
+            .as_deref()
+            .is_none_or(|p| data.starts_with(p));
 
-        owner && hash && pubkey
+        owner && hash && hash_prefix && pubkey && min_wv && max_wv && prefix
+    }
+}
+
+/// The filters as the `ssp` flags that select them, e.g.
+/// `--owner <pubkey> --sample 1/100`; empty when nothing is filtered.
+impl fmt::Display for ResolvedFilters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let mut flags = Vec::new();
+        if let Some(owner) = self.owner {
+            flags.push(format!("--owner {owner}"));
+        }
+        // Sorted, so the same run always prints the same text.
+        let mut excluded: Vec<_> = self.exclude_owners.iter().map(Pubkey::to_string).collect();
+        excluded.sort();
+        flags.extend(excluded.into_iter().map(|o| format!("--exclude-owner {o}")));
+        if let Some(hash) = self.hash {
+            flags.push(format!("--hash {}", Pubkey::from(hash)));
+        }
+        if let Some(prefix) = &self.hash_prefix {
+            flags.push(format!("--hash-prefix {}", hex(prefix)));
+        }
+        if let Some(pubkey) = self.pubkey {
+            flags.push(format!("--pubkey {pubkey}"));
+        }
+        if let Some(v) = self.min_write_version {
+            flags.push(format!("--min-write-version {v}"));
+        }
+        if let Some(v) = self.max_write_version {
+            flags.push(format!("--max-write-version {v}"));
+        }
+        if self.include_dead {
+            flags.push("--include-dead".into());
+        }
+        if self.include_spam {
+            flags.push("--include-spam".into());
+        }
+        if let Some((num, den)) = self.sample {
+            flags.push(format!("--sample {num}/{den}"));
+        }
+        if let Some(prefix) = &self.data_prefix {
+            flags.push(format!("--data-prefix {}", hex(prefix)));
+        }
+        f.write_str(&flags.join(" "))
     }
 }
@@ -0,0 +1,64 @@
+//! Snapshot compression detection from magic bytes, so `--path` and network
+//! streams work regardless of file extension.
+
+use std::io::Read;
+
+use anyhow::{Context, bail};
+
+/// Bytes read to identify the format; zstd's magic is the longest.
+pub const MAGIC_LEN: usize = 4;
+
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionFormat {
+    Zstd,
+    Bzip2,
+    Gzip,
+}
+
+/// Consume the first `MAGIC_LEN` bytes of `reader` and identify the
+/// compression. The bytes are returned so the caller can put them back in
+/// front of the stream (see `open_decoder`).
+pub fn detect_compression_format(
+    reader: &mut impl Read,
+) -> anyhow::Result<(CompressionFormat, [u8; MAGIC_LEN])> {
+    let mut magic = [0u8; MAGIC_LEN];
+    reader
+        .read_exact(&mut magic)
+        .context("input too short to be a snapshot archive")?;
+
+    let format = if magic.starts_with(ZSTD_MAGIC) {
+        CompressionFormat::Zstd
+    } else if magic.starts_with(BZIP2_MAGIC) {
+        CompressionFormat::Bzip2
+    } else if magic.starts_with(GZIP_MAGIC) {
+        CompressionFormat::Gzip
+    } else {
+        bail!("unrecognized compression (magic bytes {magic:02x?})");
+    };
+    Ok((format, magic))
+}
+
+/// Detect the format of `reader` and wrap it in the matching decoder, with
+/// the magic bytes chained back in front. `zstd_window_log_max` caps the zstd
+/// window (see `zstd_frames::DEFAULT_WINDOW_LOG_MAX`); other formats ignore it.
+pub fn open_decoder<'a>(
+    mut reader: impl Read + Send + 'a,
+    zstd_window_log_max: u32,
+) -> anyhow::Result<Box<dyn Read + Send + 'a>> {
+    let (format, magic) = detect_compression_format(&mut reader)?;
+    let reader = std::io::Cursor::new(magic).chain(reader);
+
+    Ok(match format {
+        // Frame-parallel when the stream is many small frames, otherwise the
+        // plain streaming decoder; see `zstd_frames`.
+        CompressionFormat::Zstd => crate::zstd_frames::open(reader, zstd_window_log_max)?,
+        // Multi-stream variants: parallel compressors (pbzip2, pigz) emit
+        // several concatenated streams.
+        CompressionFormat::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        CompressionFormat::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+    })
+}
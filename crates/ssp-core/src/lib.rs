@@ -1,7 +1,9 @@
 pub mod decoders;
 pub mod filters;
+pub mod format;
 pub mod parser;
 pub mod pubkey;
 pub mod record_batch;
+pub mod zstd_frames;
 
 pub use pubkey::Pubkey;
@@ -1,9 +1,11 @@
 use {
     crate::{Pubkey, filters::ResolvedFilters},
+    anyhow::{Context, bail},
     arrow::array::RecordBatch,
     bytemuck::{Pod, Zeroable},
     crossbeam::channel::{Receiver, Sender},
     std::{
+        borrow::Cow,
         collections::HashMap,
         fmt,
         io::{BufReader, Read},
@@ -44,6 +46,98 @@ impl fmt::Display for AccountHeader {
 
 pub const TAR_BLOCK: usize = 512;
 
+/// Default `BufReader` capacity in front of the zstd decoder (4 MiB).
+pub const DEFAULT_BUF_SIZE: usize = 4 * 1024 * 1024;
+
+/// Log-scale `data_len` buckets: 0, 1–8, 9–64, 65–512, 513–4096, 4097–32768, 32769+.
+pub const SIZE_BUCKET_LABELS: [&str; 7] = [
+    "0",
+    "1-8",
+    "9-64",
+    "65-512",
+    "513-4096",
+    "4097-32768",
+    "32769+",
+];
+
+/// Per-thread account size counters. Plain array on the parser's stack,
+/// merged after the threads are joined — no atomics on the hot path.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SizeHistogram(pub [u64; SIZE_BUCKET_LABELS.len()]);
+
+impl SizeHistogram {
+    #[inline]
+    pub fn record(&mut self, data_len: u64) {
+        let bucket = match data_len {
+            0 => 0,
+            1..=8 => 1,
+            9..=64 => 2,
+            65..=512 => 3,
+            513..=4096 => 4,
+            4097..=32768 => 5,
+            _ => 6,
+        };
+        self.0[bucket] += 1;
+    }
+
+    pub fn merge(&mut self, other: &SizeHistogram) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+}
+
+/// Send attempts remembered by a `SendWindow`.
+pub const SEND_WINDOW: usize = 100;
+
+/// Whether each of the last `SEND_WINDOW` sends on a channel found it full,
+/// as a circular buffer. Per thread, like `SizeHistogram`.
+#[derive(Clone, Copy, Debug)]
+pub struct SendWindow {
+    slots: [bool; SEND_WINDOW],
+    next: usize,
+    filled: usize,
+    blocked: usize,
+}
+
+impl Default for SendWindow {
+    fn default() -> Self {
+        Self {
+            slots: [false; SEND_WINDOW],
+            next: 0,
+            filled: 0,
+            blocked: 0,
+        }
+    }
+}
+
+impl SendWindow {
+    #[inline]
+    pub fn record(&mut self, blocked: bool) {
+        if self.filled == SEND_WINDOW {
+            self.blocked -= usize::from(self.slots[self.next]);
+        } else {
+            self.filled += 1;
+        }
+        self.slots[self.next] = blocked;
+        self.blocked += usize::from(blocked);
+        self.next = (self.next + 1) % SEND_WINDOW;
+    }
+
+    /// Fraction of the remembered sends that were blocked; 0 before any.
+    pub fn blocked_ratio(&self) -> f64 {
+        if self.filled == 0 {
+            0.0
+        } else {
+            self.blocked as f64 / self.filled as f64
+        }
+    }
+}
+
 /// Parse octal ASCII (tar stores sizes as octal strings).
 pub fn parse_octal(bytes: &[u8]) -> u64 {
     // GNU tar extension: if the high bit is set, it's binary big-endian
@@ -62,73 +156,314 @@ pub fn parse_octal(bytes: &[u8]) -> u64 {
     n
 }
 
-/// Check if tar header path (bytes 0..100) contains "accounts/".
+/// POSIX ustar magic and version (bytes 257..265). GNU tar writes
+/// `"ustar  \0"` there instead, and uses bytes 345..500 for other fields.
+const POSIX_USTAR_MAGIC: &[u8] = b"ustar\x0000";
+
+/// Full path of a tar entry: the name field (bytes 0..100), behind the POSIX
+/// ustar prefix field (bytes 345..500) when that is set. Paths over 100
+/// bytes are split across the two.
+pub fn entry_path(header: &[u8; TAR_BLOCK]) -> Cow<'_, [u8]> {
+    let name = nul_terminated(&header[..100]);
+    let prefix = nul_terminated(&header[345..500]);
+    if header[257..265] != *POSIX_USTAR_MAGIC || prefix.is_empty() {
+        return Cow::Borrowed(name);
+    }
+    Cow::Owned([prefix, b"/", name].concat())
+}
+
+/// `entry_path` as text, for messages about the entry.
+pub fn tar_entry_name(header: &[u8; TAR_BLOCK]) -> String {
+    String::from_utf8_lossy(&entry_path(header)).into_owned()
+}
+
+/// A tar string field, up to its first NUL (fields that fill their width
+/// have none).
+fn nul_terminated(field: &[u8]) -> &[u8] {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    &field[..end]
+}
+
+/// Check if the tar entry's path (`entry_path`) contains "accounts/".
 pub fn is_accounts_entry(header: &[u8; TAR_BLOCK]) -> bool {
     // type flag: byte 156, '0' or '\0' = regular file
     let type_flag = header[156];
     if type_flag != b'0' && type_flag != 0 {
         return false;
     }
-    header[..100].windows(9).any(|w| w == b"accounts/")
+    entry_path(header).windows(9).any(|w| w == b"accounts/")
+}
+
+/// The `data_len` bytes of account data at `offset`, unless they run past
+/// the end of `buf`.
+fn account_data(buf: &[u8], offset: usize, data_len: u64) -> Option<&[u8]> {
+    let end = offset.checked_add(usize::try_from(data_len).ok()?)?;
+    buf.get(offset..end)
+}
+
+/// How `stream_raw` stopped, when it didn't fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamEnd {
+    /// Read both end-of-archive zero blocks; anything after them is padding.
+    Clean,
+    /// Every receiver hung up (e.g. `--max-accounts` reached) before the end.
+    Stopped,
+}
+
+/// Per-parser-thread counters filled in by `parse_accounts`.
+#[derive(Default)]
+pub struct ParseCounters {
+    /// Every account header walked, before sampling and filters.
+    pub accounts: u64,
+    /// Lamports summed over the same headers as `accounts`.
+    pub lamports: u64,
+    /// `Some` when the size histogram is enabled.
+    pub sizes: Option<SizeHistogram>,
+    /// Accounts a decoder matched but failed to decode, keyed by decoder name.
+    pub decode_errors: HashMap<&'static str, u64>,
+    /// Recent decoded-channel sends, for the live blocked percentage.
+    pub decoded_sends: SendWindow,
+    /// Buffers abandoned at a header whose `data_len` ran past their end.
+    pub truncated_entries: u64,
 }
 
 impl AccountHeader {
-    /// Stage 1: zstd → lightweight tar → send raw buffers.
+    /// Largest account Solana allows (`MAX_PERMITTED_DATA_LENGTH`, 10 MiB).
+    pub const MAX_DATA_LEN: u64 = 10 * 1024 * 1024;
+    /// Max SOL supply in lamports (500M SOL). No single account can hold more.
+    pub const MAX_LAMPORTS: u64 = 500_000_000_000_000_000;
+    /// Upper bound on a real `rent_epoch`. Rent-exempt accounts store
+    /// `u64::MAX`, which is allowed.
+    pub const MAX_RENT_EPOCH: u64 = 1_000_000;
+
+    /// Report every layout invariant this header violates.
+    pub fn check(&self, mut violation: impl FnMut(&'static str)) {
+        if self.data_len > Self::MAX_DATA_LEN {
+            violation("data_len exceeds 10 MiB");
+        }
+        if self.lamports >= Self::MAX_LAMPORTS {
+            violation("lamports exceed total supply");
+        }
+        if self.executable > 1 {
+            violation("executable is not 0 or 1");
+        }
+        if self.rent_epoch >= Self::MAX_RENT_EPOCH && self.rent_epoch != u64::MAX {
+            violation("rent_epoch out of range");
+        }
+        if self.lamports > 0 && self.pubkey.is_zero() {
+            violation("zero pubkey with lamports");
+        }
+    }
+
+    /// Headers-only pass: call `f` for every account that passes `filters`,
+    /// without touching decoders. Returns the number of headers walked.
+    pub fn walk_matching(
+        buf: &[u8],
+        filters: &ResolvedFilters,
+        mut f: impl FnMut(&AccountHeader),
+    ) -> u64 {
+        let mut offset = 0;
+        let mut walked = 0;
+
+        while offset + size_of::<AccountHeader>() <= buf.len() {
+            let header = bytemuck::from_bytes::<AccountHeader>(
+                &buf[offset..offset + size_of::<AccountHeader>()],
+            );
+            offset += size_of::<AccountHeader>();
+            let Some(data) = account_data(buf, offset, header.data_len) else {
+                break;
+            };
+            offset = (offset + header.data_len as usize + 7) & !7;
+            walked += 1;
+
+            if filters.sampled(&header.pubkey) && filters.matches(header, data) {
+                f(header);
+            }
+        }
+
+        walked
+    }
+
+    /// Walk a raw AppendVec buffer like `parse_accounts`, but bounds-check
+    /// every `data_len` and report violations instead of decoding.
+    /// Returns the number of headers checked.
+    pub fn validate_accounts(
+        buf: &[u8],
+        mut violation: impl FnMut(&AccountHeader, &'static str),
+    ) -> u64 {
+        let mut offset = 0;
+        let mut checked = 0;
+
+        while offset + size_of::<AccountHeader>() <= buf.len() {
+            let header = bytemuck::from_bytes::<AccountHeader>(
+                &buf[offset..offset + size_of::<AccountHeader>()],
+            );
+            checked += 1;
+            header.check(|v| violation(header, v));
+
+            offset += size_of::<AccountHeader>();
+            let end = offset as u64 + header.data_len;
+            if end > buf.len() as u64 {
+                // Nothing after this is trustworthy.
+                violation(header, "data_len runs past end of file");
+                break;
+            }
+            offset = (end as usize + 7) & !7;
+        }
+
+        checked
+    }
+
+    /// Walk `buf` the way `parse_accounts` does and check each step: every
+    /// entry's data ends inside the buffer, and the padding up to the next
+    /// 8-byte boundary is zero (non-zero padding means `data_len` stopped
+    /// short and the next header really starts inside it, off the
+    /// boundary). Trailing bytes too short for a header must be zero as
+    /// well. With `expected_count`, the number of headers walked must match.
+    pub fn verify_alignment(buf: &[u8], expected_count: Option<usize>) -> anyhow::Result<()> {
+        const ALIGN: usize = 8;
+        let mut offset = 0;
+        let mut count = 0;
+
+        while offset + size_of::<AccountHeader>() <= buf.len() {
+            let header = bytemuck::from_bytes::<AccountHeader>(
+                &buf[offset..offset + size_of::<AccountHeader>()],
+            );
+            let data_start = offset + size_of::<AccountHeader>();
+            let data_end = data_start as u64 + header.data_len;
+            if data_end > buf.len() as u64 {
+                bail!(
+                    "header {count} at offset {offset}: data_len {} runs past the end ({} bytes)",
+                    header.data_len,
+                    buf.len()
+                );
+            }
+            let data_end = data_end as usize;
+            let next = ((data_end + ALIGN - 1) & !(ALIGN - 1)).min(buf.len());
+            if buf[data_end..next].iter().any(|&b| b != 0) {
+                bail!(
+                    "header {count} at offset {offset}: non-zero padding after data_len {}; \
+                     the next header starts inside it",
+                    header.data_len
+                );
+            }
+            offset = next;
+            count += 1;
+        }
+
+        if buf[offset..].iter().any(|&b| b != 0) {
+            bail!(
+                "{} non-zero trailing bytes at offset {offset}, too short for a header",
+                buf.len() - offset
+            );
+        }
+        if let Some(expected) = expected_count
+            && expected != count
+        {
+            bail!("expected {expected} accounts, walked {count}");
+        }
+        Ok(())
+    }
+
+    /// Stage 1: zstd (or bzip2 / gzip, by magic bytes) → lightweight tar → send raw buffers.
+    ///
+    /// `buf_size` is the compressed-side read buffer: larger means fewer read
+    /// syscalls for the decompressor, smaller saves memory. `window_log_max`
+    /// caps the zstd window, as in `format::open_decoder`.
+    ///
+    /// A stream that ends before the two-zero-block end-of-archive marker is
+    /// an error, so a truncated download doesn't pass for a complete one.
+    ///
+    /// The first `skip_entries` tar entries, of any kind, are read and
+    /// discarded.
+    ///
+    /// `on_entry` is called after each tar header with the entry name (lossy
+    /// UTF-8), its size, and whether it is an accounts file that will be sent
+    /// on (false for skipped ones).
     pub fn stream_raw(
         reader: impl Read + Send,
-        raw_tx: Sender<Vec<u8>>,
+        buf_size: usize,
+        window_log_max: u32,
+        skip_entries: usize,
+        raw_tx: Sender<(String, Vec<u8>)>,
         recycle_rx: Receiver<Vec<u8>>,
-    ) -> anyhow::Result<()> {
-        let buffered = BufReader::with_capacity(4 * 1024 * 1024, reader);
-        let mut decoder = zstd::Decoder::new(buffered)?;
-        decoder.window_log_max(31)?;
+        mut on_entry: Option<impl FnMut(&str, usize, bool)>,
+    ) -> anyhow::Result<StreamEnd> {
+        let buffered = BufReader::with_capacity(buf_size, reader);
+        // Concatenated zstd frames are already handled: unless `single_frame()`
+        // is set, the decoder resets its session when a frame ends and keeps
+        // reading, and the reset keeps parameters like `window_log_max`.
+        let mut decoder = crate::format::open_decoder(buffered, window_log_max)?;
 
         let mut header = [0u8; TAR_BLOCK];
         let mut skip_buf = [0u8; 65536];
+        let mut entries = 0;
 
         loop {
             match decoder.read_exact(&mut header) {
                 Ok(()) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    bail!("archive truncated: no end-of-archive marker")
+                }
                 Err(e) => return Err(e.into()),
             }
 
-            // Zero block = end of archive (name starts with NUL)
-            if header[0] == 0 {
-                break;
+            // End of archive = two zero blocks. Some writers pad past them,
+            // so stop here instead of reading to EOF.
+            if header.iter().all(|&b| b == 0) {
+                decoder
+                    .read_exact(&mut header)
+                    .context("archive truncated inside the end-of-archive marker")?;
+                if header.iter().any(|&b| b != 0) {
+                    bail!("corrupt end-of-archive marker: second block isn't zero");
+                }
+                return Ok(StreamEnd::Clean);
             }
 
             let size = parse_octal(&header[124..136]) as usize;
             let padded = (size + TAR_BLOCK - 1) & !(TAR_BLOCK - 1);
+            let accounts = entries >= skip_entries && is_accounts_entry(&header);
+            entries += 1;
+
+            if let Some(on_entry) = on_entry.as_mut() {
+                on_entry(&tar_entry_name(&header), size, accounts);
+            }
 
-            if is_accounts_entry(&header) {
+            if accounts {
                 let mut buf: Vec<u8> = recycle_rx
                     .try_recv()
                     .unwrap_or_else(|_| Vec::with_capacity(size));
                 // let mut buf = vec![0u8; size];
 
                 buf.resize(size, 0);
-                decoder.read_exact(&mut buf)?;
+                decoder
+                    .read_exact(&mut buf)
+                    .context("archive truncated mid-entry")?;
 
                 // Skip padding bytes to next 512 boundary
                 let padding = padded - size;
                 if padding > 0 {
-                    decoder.read_exact(&mut skip_buf[..padding])?;
+                    decoder
+                        .read_exact(&mut skip_buf[..padding])
+                        .context("archive truncated mid-entry")?;
                 }
 
-                raw_tx.send(buf)?;
+                // All parsers gone (e.g. --max-accounts reached) — stop reading.
+                if raw_tx.send((tar_entry_name(&header), buf)).is_err() {
+                    return Ok(StreamEnd::Stopped);
+                }
             } else {
                 // Skip entry data efficiently
                 let mut remaining = padded;
                 while remaining > 0 {
                     let chunk = remaining.min(skip_buf.len());
-                    decoder.read_exact(&mut skip_buf[..chunk])?;
+                    decoder
+                        .read_exact(&mut skip_buf[..chunk])
+                        .context("archive truncated mid-entry")?;
                     remaining -= chunk;
                 }
             }
         }
-
-        Ok(())
     }
 
     /// Stage 2: parse raw AppendVec buffer into filtered account headers + decoded batches.
@@ -136,12 +471,15 @@ impl AccountHeader {
         buf: &[u8],
         filters: &ResolvedFilters,
         decoders: &mut [Box<dyn crate::decoders::Decoder>],
-        decoder_map: &HashMap<Pubkey, Vec<usize>>,
+        router: &crate::decoders::DecoderRouter,
         decoded_tx: &Sender<(&'static str, RecordBatch)>,
         blocked_decoded: &AtomicU64,
+        counters: &mut ParseCounters,
     ) -> Vec<AccountHeader> {
         let mut offset = 0;
         let mut batch = Vec::new();
+        // The usual full-snapshot run: skip evaluating every unset filter.
+        let trivial = filters.is_trivial();
 
         while offset + size_of::<AccountHeader>() <= buf.len() {
             let header = bytemuck::from_bytes::<AccountHeader>(
@@ -150,33 +488,151 @@ impl AccountHeader {
 
             offset += size_of::<AccountHeader>();
 
-            let data = &buf[offset..offset + header.data_len as usize];
+            // Nothing after a header whose data overruns the buffer is
+            // trustworthy; the caller knows which entry it was.
+            let Some(data) = account_data(buf, offset, header.data_len) else {
+                counters.truncated_entries += 1;
+                break;
+            };
 
             offset += header.data_len as usize;
 
             offset = (offset + 7) & !7;
+            counters.accounts += 1;
+            counters.lamports = counters.lamports.saturating_add(header.lamports);
 
-            // O(1) lookup by owner — skips entirely for programs without decoders
-            if let Some(indices) = decoder_map.get(&header.owner) {
-                for &idx in indices {
-                    if decoders[idx].matches(&header.owner, header.data_len) {
-                        if let Some(batch) = decoders[idx].decode(header.pubkey, data, filters.include_spam) {
-                            if decoded_tx.is_full() {
-                                blocked_decoded.fetch_add(1, Ordering::Relaxed);
-                            }
-                            let _ = decoded_tx.send((decoders[idx].name(), batch));
+            if !filters.sampled(&header.pubkey) {
+                continue;
+            }
+
+            // O(1) lookup by (owner, data_len) — skips entirely for programs without decoders
+            if let Some(idx) = router.route(decoders, &header.owner, header.data_len) {
+                match decoders[idx].decode(
+                    header.pubkey,
+                    header.write_version,
+                    data,
+                    filters.include_spam,
+                ) {
+                    Ok(Some(batch)) => {
+                        let full = decoded_tx.is_full();
+                        if full {
+                            blocked_decoded.fetch_add(1, Ordering::Relaxed);
                         }
-                        break;
+                        counters.decoded_sends.record(full);
+                        let _ = decoded_tx.send((decoders[idx].batch_name(&batch), batch));
+                    }
+                    Ok(None) => {}
+                    Err(_) => {
+                        *counters
+                            .decode_errors
+                            .entry(decoders[idx].name())
+                            .or_default() += 1
                     }
                 }
             }
 
-            if !filters.matches(header) {
+            let keep = if trivial {
+                filters.include_dead || header.lamports != 0
+            } else {
+                filters.matches(header, data)
+            };
+            if !keep {
                 continue;
             }
+            if let Some(h) = counters.sizes.as_mut() {
+                h.record(header.data_len);
+            }
             batch.push(*header);
         }
 
         batch
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(data_len: u64, write_version: u64) -> AccountHeader {
+        AccountHeader {
+            write_version,
+            data_len,
+            lamports: 1,
+            ..AccountHeader::zeroed()
+        }
+    }
+
+    /// A header, its data, and zero padding up to the next 8-byte boundary.
+    fn entry(data: &[u8]) -> Vec<u8> {
+        let mut buf = bytemuck::bytes_of(&header(data.len() as u64, 1)).to_vec();
+        buf.extend_from_slice(data);
+        buf.resize(buf.len().next_multiple_of(8), 0);
+        buf
+    }
+
+    /// A 512-byte tar header with `name`, `magic` at 257 and `prefix` at 345.
+    fn tar_header(name: &str, type_flag: u8, magic: &[u8], prefix: &[u8]) -> [u8; TAR_BLOCK] {
+        let mut header = [0u8; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[156] = type_flag;
+        header[257..257 + magic.len()].copy_from_slice(magic);
+        header[345..345 + prefix.len()].copy_from_slice(prefix);
+        header
+    }
+
+    #[test]
+    fn entry_path_joins_the_posix_prefix() {
+        // "accounts/" only appears once prefix and name are joined.
+        let header = tar_header("123.4", b'0', POSIX_USTAR_MAGIC, b"snapshot/accounts");
+        assert_eq!(&*entry_path(&header), b"snapshot/accounts/123.4");
+        assert_eq!(tar_entry_name(&header), "snapshot/accounts/123.4");
+        assert!(is_accounts_entry(&header));
+
+        let header = tar_header("accounts/123.4", 0, POSIX_USTAR_MAGIC, b"");
+        assert_eq!(&*entry_path(&header), b"accounts/123.4");
+        assert!(is_accounts_entry(&header));
+
+        // Directories aren't account files, whatever their path.
+        let header = tar_header("accounts/", b'5', POSIX_USTAR_MAGIC, b"");
+        assert!(!is_accounts_entry(&header));
+    }
+
+    #[test]
+    fn entry_path_ignores_the_prefix_field_of_gnu_headers() {
+        // GNU tar keeps other fields at 345..500; they aren't a path prefix.
+        let header = tar_header("snapshots/123/123", b'0', b"ustar  \0", b"accounts/");
+        assert_eq!(&*entry_path(&header), b"snapshots/123/123");
+        assert!(!is_accounts_entry(&header));
+
+        let header = tar_header("accounts/123.4", b'0', b"ustar  \0", b"");
+        assert!(is_accounts_entry(&header));
+    }
+
+    #[test]
+    fn verify_alignment_accepts_aligned_entries() {
+        let buf = [entry(&[1; 3]), entry(&[2; 16]), entry(&[])].concat();
+        AccountHeader::verify_alignment(&buf, None).unwrap();
+        AccountHeader::verify_alignment(&buf, Some(3)).unwrap();
+        // Zeroed trailing bytes too short for a header are fine.
+        let padded = [buf, vec![0; 64]].concat();
+        AccountHeader::verify_alignment(&padded, Some(3)).unwrap();
+    }
+
+    #[test]
+    fn verify_alignment_rejects_misaligned_header() {
+        // The second header starts right after 3 bytes of data, not at the
+        // next 8-byte boundary, so its first bytes land in the padding.
+        let mut buf = bytemuck::bytes_of(&header(3, 1)).to_vec();
+        buf.extend_from_slice(&[9; 3]);
+        buf.extend_from_slice(bytemuck::bytes_of(&header(0, u64::MAX)));
+        let err = AccountHeader::verify_alignment(&buf, None).unwrap_err();
+        assert!(err.to_string().contains("non-zero padding"), "{err}");
+    }
+
+    #[test]
+    fn verify_alignment_rejects_count_mismatch() {
+        let buf = [entry(&[1; 8]), entry(&[2; 5])].concat();
+        let err = AccountHeader::verify_alignment(&buf, Some(3)).unwrap_err();
+        assert_eq!(err.to_string(), "expected 3 accounts, walked 2");
+    }
+}
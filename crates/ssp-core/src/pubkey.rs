@@ -1,11 +1,53 @@
 use std::fmt;
 
 use bytemuck::{Pod, Zeroable};
+use curve25519_dalek::edwards::CompressedEdwardsY;
 
 #[derive(Pod, Zeroable, Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[repr(C)]
 pub struct Pubkey([u8; 32]);
 
+/// Well-known program IDs.
+impl Pubkey {
+    /// `11111111111111111111111111111111`
+    pub const SYSTEM_PROGRAM: Self = Self::new([0; 32]);
+    /// `TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA`
+    pub const TOKEN_PROGRAM: Self = Self::new([
+        6, 221, 246, 225, 215, 101, 161, 147, 217, 203, 225, 70, 206, 235, 121, 172, 28, 180, 133,
+        237, 95, 91, 55, 145, 58, 140, 245, 133, 126, 255, 0, 169,
+    ]);
+    /// `TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb`
+    pub const TOKEN_2022_PROGRAM: Self = Self::new([
+        6, 221, 246, 225, 238, 117, 143, 222, 24, 66, 93, 188, 228, 108, 205, 218, 182, 26, 252,
+        77, 131, 185, 13, 39, 254, 189, 249, 40, 216, 161, 139, 252,
+    ]);
+    /// `ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL`
+    pub const ASSOCIATED_TOKEN: Self = Self::new([
+        140, 151, 37, 143, 78, 36, 137, 241, 187, 61, 16, 41, 20, 142, 13, 131, 11, 90, 19, 153,
+        218, 255, 16, 132, 4, 142, 123, 216, 219, 233, 248, 89,
+    ]);
+    /// `Stake11111111111111111111111111111111111111`
+    pub const STAKE_PROGRAM: Self = Self::new([
+        6, 161, 216, 23, 145, 55, 84, 42, 152, 52, 55, 189, 254, 42, 122, 178, 85, 127, 83, 92,
+        138, 120, 114, 43, 104, 164, 157, 192, 0, 0, 0, 0,
+    ]);
+    /// `Vote111111111111111111111111111111111111111`
+    pub const VOTE_PROGRAM: Self = Self::new([
+        7, 97, 72, 29, 53, 116, 116, 187, 124, 77, 118, 36, 235, 211, 189, 179, 216, 53, 94, 115,
+        209, 16, 67, 252, 13, 163, 83, 128, 0, 0, 0, 0,
+    ]);
+    /// `BPFLoaderUpgradeab1e11111111111111111111111`
+    pub const BPF_UPGRADEABLE_LOADER: Self = Self::new([
+        2, 168, 246, 145, 78, 136, 161, 176, 226, 16, 21, 62, 247, 99, 174, 43, 0, 194, 185, 61,
+        22, 193, 36, 210, 192, 83, 122, 16, 4, 128, 0, 0,
+    ]);
+    /// `metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s`
+    pub const METAPLEX_METADATA: Self = Self::new([
+        11, 112, 101, 177, 227, 209, 124, 69, 56, 157, 82, 127, 107, 4, 195, 205, 88, 184, 108,
+        115, 26, 160, 253, 181, 73, 182, 209, 188, 3, 248, 41, 70,
+    ]);
+}
+
 impl Pubkey {
     pub const fn new(bytes: [u8; 32]) -> Self {
         Self(bytes)
@@ -14,10 +56,21 @@ impl Pubkey {
     /// Decode a base58 string into a Pubkey.
     pub fn from_b58(s: &str) -> Result<Self, anyhow::Error> {
         let mut buf = [0u8; 32];
-        bs58::decode(s).onto(&mut buf)?;
+        let len = bs58::decode(s).onto(&mut buf)?;
+        anyhow::ensure!(
+            len == 32,
+            "pubkey {s:?} decodes to {len} bytes, expected 32"
+        );
         Ok(Self(buf))
     }
 
+    /// Whether `s` decodes to exactly 32 bytes. Decodes onto the stack like
+    /// `from_b58`, but skips building the error.
+    pub fn is_valid_b58(s: &str) -> bool {
+        let mut buf = [0u8; 32];
+        bs58::decode(s).onto(&mut buf) == Ok(32)
+    }
+
     /// Decode an optional base58 string. Returns Ok(None) if input is None.
     pub fn try_from_b58(s: Option<&str>) -> Result<Option<Self>, anyhow::Error> {
         s.map(Self::from_b58).transpose()
@@ -27,9 +80,26 @@ impl Pubkey {
         &self.0
     }
 
+    /// A copy of the key's bytes.
+    pub fn to_array(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// The key's bytes, for libraries that take a bare `[u8; 32]`.
+    pub fn into_array(self) -> [u8; 32] {
+        bytemuck::cast(self)
+    }
+
     pub fn is_zero(&self) -> bool {
         self.0 == [0u8; 32]
     }
+
+    /// Whether the bytes decompress to an ed25519 point, i.e. could be a
+    /// wallet's public key. Program-derived addresses are always off the
+    /// curve.
+    pub fn is_on_curve(&self) -> bool {
+        CompressedEdwardsY(self.0).decompress().is_some()
+    }
 }
 
 impl fmt::Display for Pubkey {
@@ -44,9 +114,150 @@ impl From<[u8; 32]> for Pubkey {
     }
 }
 
+impl From<Pubkey> for [u8; 32] {
+    fn from(pubkey: Pubkey) -> Self {
+        pubkey.into_array()
+    }
+}
+
+impl TryFrom<&[u8]> for Pubkey {
+    type Error = anyhow::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("pubkey is {} bytes, expected 32", bytes.len()))?;
+        Ok(Self(bytes))
+    }
+}
+
 /// Lets Pubkey be used directly where &[u8] is expected (e.g. BinaryArray).
 impl AsRef<[u8]> for Pubkey {
     fn as_ref(&self) -> &[u8] {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_consts_match_their_base58() {
+        let consts = [
+            (Pubkey::SYSTEM_PROGRAM, "11111111111111111111111111111111"),
+            (
+                Pubkey::TOKEN_PROGRAM,
+                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            ),
+            (
+                Pubkey::TOKEN_2022_PROGRAM,
+                "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+            ),
+            (
+                Pubkey::ASSOCIATED_TOKEN,
+                "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+            ),
+            (
+                Pubkey::STAKE_PROGRAM,
+                "Stake11111111111111111111111111111111111111",
+            ),
+            (
+                Pubkey::VOTE_PROGRAM,
+                "Vote111111111111111111111111111111111111111",
+            ),
+            (
+                Pubkey::BPF_UPGRADEABLE_LOADER,
+                "BPFLoaderUpgradeab1e11111111111111111111111",
+            ),
+            (
+                Pubkey::METAPLEX_METADATA,
+                "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+            ),
+        ];
+        for (key, b58) in consts {
+            assert_eq!(key, Pubkey::from_b58(b58).unwrap(), "{b58}");
+        }
+    }
+
+    #[test]
+    fn b58_round_trips_through_display() {
+        let mut bytes = [0u8; 32];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(37).wrapping_add(1);
+        }
+        let key = Pubkey::new(bytes);
+        let b58 = key.to_string();
+        assert!(Pubkey::is_valid_b58(&b58));
+        assert_eq!(Pubkey::from_b58(&b58).unwrap(), key);
+    }
+
+    #[test]
+    fn b58_leading_ones_are_zero_bytes() {
+        let mut bytes = [0xffu8; 32];
+        bytes[..3].fill(0);
+        let b58 = Pubkey::new(bytes).to_string();
+        assert!(b58.starts_with("111") && !b58.starts_with("1111"), "{b58}");
+        assert_eq!(Pubkey::from_b58(&b58).unwrap().as_bytes(), &bytes);
+
+        let zero = Pubkey::from_b58(&"1".repeat(32)).unwrap();
+        assert!(zero.is_zero());
+        // One '1' short is one zero byte short.
+        assert!(!Pubkey::is_valid_b58(&"1".repeat(31)));
+        assert!(Pubkey::from_b58(&"1".repeat(31)).is_err());
+    }
+
+    #[test]
+    fn b58_rejects_invalid_characters() {
+        let valid = Pubkey::TOKEN_PROGRAM.to_string();
+        for bad in ['0', 'O', 'I', 'l', '+', ' '] {
+            let s = format!("{bad}{}", &valid[1..]);
+            assert!(!Pubkey::is_valid_b58(&s), "{s:?}");
+            assert!(Pubkey::from_b58(&s).is_err(), "{s:?}");
+        }
+        assert!(!Pubkey::is_valid_b58(""));
+    }
+
+    #[test]
+    fn b58_rejects_wrong_decoded_length() {
+        let valid = Pubkey::TOKEN_PROGRAM.to_string();
+        // Too short: decodes fine, but not to 32 bytes.
+        let short = &valid[..valid.len() - 2];
+        assert!(!Pubkey::is_valid_b58(short));
+        let err = Pubkey::from_b58(short).unwrap_err().to_string();
+        assert!(err.contains("expected 32"), "{err}");
+        // Too long: overflows the 32-byte buffer.
+        let long = format!("{valid}zz");
+        assert!(!Pubkey::is_valid_b58(&long));
+        assert!(Pubkey::from_b58(&long).is_err());
+    }
+
+    /// RFC 8032 test-vector public keys, plus their USDC associated token
+    /// accounts (`find_program_address` over wallet, token program, mint).
+    /// A bump is rejected when its hash lands on the curve, so those
+    /// candidates are on it and the accepted address is off it.
+    #[test]
+    fn is_on_curve_separates_wallets_from_pdas() {
+        let on_curve = [
+            // RFC 8032 test keys 1-3.
+            "FVen3X669xLzsi6N2V91DoiyzHzg1uAgqiT8jZ9nS96Z",
+            "586Z7H2vpX9qNhN2T4e9Utugie3ogjbxzGaMtM3E6HR5",
+            "Hyx62wPQGyvXCoihZq1BrbUjBRh2LuNxWiiqMkfAuSZr",
+            // Bumps 255 for key 2, 255 and 254 for key 3.
+            "AyiWHNXB5eGHo57WPuA5rGRvsbw6QcGJnsD7UyHYyJRQ",
+            "CTr5k3orkN7WrGYGVocXaKqZji4nXNqhgTAfvj1sPYDR",
+            "GQv4289EXzdujkpyurapmFUYe2rni19FqShovy8hiLZD",
+        ];
+        let pdas = [
+            "HU2S9ByyqbnCD2SVfvr9qoLtDTtyTnMZoMaw1xpr6cTb",
+            "HKpJMFu3s2nEZ6WofQc3Xbb4RwGFb9AzTKdNwuZSvGGq",
+            "HNA9kTNttnnh4t7nxDAAwjnjDXq3j7ArEJMUSXfTtD7F",
+        ];
+        for b58 in on_curve {
+            assert!(Pubkey::from_b58(b58).unwrap().is_on_curve(), "{b58}");
+        }
+        for b58 in pdas {
+            assert!(!Pubkey::from_b58(b58).unwrap().is_on_curve(), "{b58}");
+        }
+    }
+}
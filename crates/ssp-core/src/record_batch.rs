@@ -4,18 +4,27 @@ use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
 use std::sync::Arc;
 
-pub fn account_schema() -> Schema {
-    Schema::new(vec![
+/// With `write_version`, a trailing `write_version` column is added (needed to
+/// pick the latest entry when deduplicating).
+pub fn account_schema(write_version: bool) -> Schema {
+    let mut fields = vec![
         Field::new("pubkey", DataType::Binary, false),
         Field::new("lamports", DataType::UInt64, false),
         Field::new("owner", DataType::Binary, false),
         Field::new("data_len", DataType::UInt64, false),
         Field::new("executable", DataType::Boolean, false),
         Field::new("rent_epoch", DataType::UInt64, false),
-    ])
+    ];
+    if write_version {
+        fields.push(Field::new("write_version", DataType::UInt64, false));
+    }
+    Schema::new(fields)
 }
 
-pub fn build_record_batch(headers: &[AccountHeader]) -> anyhow::Result<RecordBatch> {
+pub fn build_record_batch(
+    headers: &[AccountHeader],
+    write_version: bool,
+) -> anyhow::Result<RecordBatch> {
     let pubkeys: ArrayRef = Arc::new(BinaryArray::from_iter_values(
         headers.iter().map(|h| h.pubkey),
     ));
@@ -38,17 +47,21 @@ pub fn build_record_batch(headers: &[AccountHeader]) -> anyhow::Result<RecordBat
         headers.iter().map(|h| h.rent_epoch),
     ));
 
-    let batch = RecordBatch::try_new(
-        Arc::new(account_schema()),
-        vec![
-            pubkeys,
-            lamports,
-            owners,
-            data_lens,
-            executables,
-            rent_epochs,
-        ],
-    )?;
+    let mut columns = vec![
+        pubkeys,
+        lamports,
+        owners,
+        data_lens,
+        executables,
+        rent_epochs,
+    ];
+    if write_version {
+        columns.push(Arc::new(UInt64Array::from_iter_values(
+            headers.iter().map(|h| h.write_version),
+        )));
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(account_schema(write_version)), columns)?;
 
     Ok(batch)
 }
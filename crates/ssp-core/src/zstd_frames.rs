@@ -0,0 +1,443 @@
+//! Frame-parallel zstd decompression.
+//!
+//! A zstd stream made of many independent frames (`pzstd`, seekable zstd,
+//! `t2sz`) can be decompressed one frame per thread. Frame boundaries are
+//! found by walking the frame and block headers, which is cheap next to
+//! decompressing, so that stays on the reading thread; workers decompress
+//! whole frames and the reader hands the output back in order.
+//!
+//! Validator-produced snapshots are a single frame. `open` only takes the
+//! parallel path once it has seen two frames, and falls back to the plain
+//! streaming decoder, which is what those snapshots have always used, for a
+//! single frame or a first frame larger than `MAX_FRAME`. A later frame over
+//! `MAX_FRAME` is an error: it is buffered whole before a worker gets it, so
+//! an unbounded one could take all of memory. A frame whose output would pass
+//! `MAX_FRAME_OUTPUT` goes back to the reading thread and is streamed there.
+
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Read};
+
+use crossbeam::channel::{Receiver, Sender, bounded, unbounded};
+
+/// Largest frame (compressed) on the parallel path. The first frame decides
+/// whether that path is taken at all.
+pub const MAX_FRAME: usize = 64 * 1024 * 1024;
+/// Largest frame output a worker buffers; bigger frames are streamed.
+pub const MAX_FRAME_OUTPUT: usize = 128 * 1024 * 1024;
+/// Decompression threads for multi-frame streams.
+pub const DEFAULT_FRAME_WORKERS: usize = 4;
+/// Default `window_log_max`: snapshots use windows up to 2 GiB. Lower caps
+/// the decoder's memory, and input that needs a bigger window then fails.
+pub const DEFAULT_WINDOW_LOG_MAX: u32 = 31;
+
+const ZSTD_MAGIC: u32 = 0xFD2F_B528;
+const SKIPPABLE_MAGIC_MASK: u32 = 0xFFFF_FFF0;
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A50;
+
+enum FrameRead {
+    /// One whole frame was appended to the buffer.
+    Frame,
+    /// Clean end of input before any frame bytes.
+    Eof,
+    /// The frame passed the size limit; the buffer holds its bytes so far.
+    TooLarge,
+}
+
+/// Append the next frame of `reader` to `buf`, skipping skippable frames.
+/// Stops early with `TooLarge` once `buf` would grow past `limit`.
+fn read_frame(reader: &mut impl Read, buf: &mut Vec<u8>, limit: usize) -> io::Result<FrameRead> {
+    loop {
+        let start = buf.len();
+        let mut magic = [0u8; 4];
+        let mut filled = 0;
+        while filled < magic.len() {
+            match reader.read(&mut magic[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        match filled {
+            0 => return Ok(FrameRead::Eof),
+            4 => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "zstd input ends inside a frame's magic number",
+                ));
+            }
+        }
+        let magic_val = u32::from_le_bytes(magic);
+
+        if magic_val & SKIPPABLE_MAGIC_MASK == SKIPPABLE_MAGIC {
+            let mut size = [0u8; 4];
+            reader.read_exact(&mut size)?;
+            io::copy(
+                &mut reader.by_ref().take(u32::from_le_bytes(size) as u64),
+                &mut io::sink(),
+            )?;
+            continue;
+        }
+        if magic_val != ZSTD_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("not a zstd frame (magic {magic:02x?})"),
+            ));
+        }
+        buf.extend_from_slice(&magic);
+
+        let descriptor = read_bytes(reader, buf, 1)?[0];
+        let fcs_flag = descriptor >> 6;
+        let single_segment = descriptor & 0x20 != 0;
+        let has_checksum = descriptor & 0x04 != 0;
+        let dict_id_len = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+        let fcs_len = match fcs_flag {
+            0 => usize::from(single_segment),
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        let window_len = usize::from(!single_segment);
+        read_bytes(reader, buf, window_len + dict_id_len + fcs_len)?;
+
+        loop {
+            let header = read_bytes(reader, buf, 3)?;
+            let header = u32::from_le_bytes([header[0], header[1], header[2], 0]);
+            let last = header & 1 != 0;
+            let size = (header >> 3) as usize;
+            let content = match (header >> 1) & 3 {
+                0 | 2 => size, // raw, compressed
+                1 => 1,        // RLE: one byte repeated `size` times
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "reserved zstd block type",
+                    ));
+                }
+            };
+            if buf.len() - start + content > limit {
+                return Ok(FrameRead::TooLarge);
+            }
+            read_bytes(reader, buf, content)?;
+            if last {
+                break;
+            }
+        }
+        if has_checksum {
+            read_bytes(reader, buf, 4)?;
+        }
+        return Ok(FrameRead::Frame);
+    }
+}
+
+/// Append exactly `n` bytes from `reader` to `buf` and return them.
+fn read_bytes<'b>(reader: &mut impl Read, buf: &'b mut Vec<u8>, n: usize) -> io::Result<&'b [u8]> {
+    let start = buf.len();
+    buf.resize(start + n, 0);
+    reader.read_exact(&mut buf[start..])?;
+    Ok(&buf[start..])
+}
+
+/// A worker's result for one frame.
+enum FrameOutput {
+    Data(Vec<u8>),
+    /// The frame (compressed) whose output is over the limit, to stream.
+    TooLarge(Vec<u8>),
+}
+
+fn frame_decoder(
+    frame: Cursor<Vec<u8>>,
+    window_log_max: u32,
+) -> io::Result<zstd::Decoder<'static, Cursor<Vec<u8>>>> {
+    let mut decoder = zstd::Decoder::with_buffer(frame)?.single_frame();
+    decoder.window_log_max(window_log_max)?;
+    Ok(decoder)
+}
+
+/// Decompress `frame` unless its output passes `max_output` bytes, going by
+/// the header's content size when it has one and by a capped read otherwise.
+fn decompress_frame(
+    frame: Vec<u8>,
+    window_log_max: u32,
+    max_output: usize,
+) -> io::Result<FrameOutput> {
+    if let Ok(Some(size)) = zstd::zstd_safe::get_frame_content_size(&frame)
+        && size > max_output as u64
+    {
+        return Ok(FrameOutput::TooLarge(frame));
+    }
+    let mut decoder = frame_decoder(Cursor::new(frame), window_log_max)?;
+    let mut out = Vec::new();
+    decoder
+        .by_ref()
+        .take(max_output as u64 + 1)
+        .read_to_end(&mut out)?;
+    if out.len() > max_output {
+        return Ok(FrameOutput::TooLarge(decoder.finish().into_inner()));
+    }
+    Ok(FrameOutput::Data(out))
+}
+
+/// Streaming decoder for zstd input. `reader` must start at the first
+/// frame's magic bytes. Frames needing a window over `2^window_log_max`
+/// bytes fail to decode.
+pub fn open<'a>(
+    mut reader: impl Read + Send + 'a,
+    window_log_max: u32,
+) -> io::Result<Box<dyn Read + Send + 'a>> {
+    // Read ahead two frames: a stream with only one gains nothing from the
+    // workers, which would hold its whole output in memory.
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    if let FrameRead::Frame = read_frame(&mut reader, &mut first, MAX_FRAME)?
+        && let FrameRead::Frame = read_frame(&mut reader, &mut second, MAX_FRAME)?
+    {
+        return Ok(Box::new(ParallelZstdReader::new(
+            reader,
+            vec![first, second],
+            DEFAULT_FRAME_WORKERS,
+            window_log_max,
+            MAX_FRAME_OUTPUT,
+        )));
+    }
+    let read_ahead = Cursor::new(first).chain(Cursor::new(second));
+    let mut decoder = zstd::Decoder::new(read_ahead.chain(reader))?;
+    decoder.window_log_max(window_log_max)?;
+    Ok(Box::new(decoder))
+}
+
+type FrameResult = (u64, io::Result<FrameOutput>);
+
+/// Reads frames from `reader`, decompresses them on worker threads, and
+/// yields the output in the original order.
+pub struct ParallelZstdReader<R> {
+    reader: R,
+    /// Frames read ahead by `open`, dispatched on the first `read`.
+    read_ahead: std::vec::IntoIter<Vec<u8>>,
+    window_log_max: u32,
+    job_tx: Sender<(u64, Vec<u8>)>,
+    result_rx: Receiver<FrameResult>,
+    max_in_flight: u64,
+    /// Sequence number of the next frame to dispatch and to emit.
+    next_dispatch: u64,
+    next_emit: u64,
+    /// Finished frames that arrived ahead of `next_emit`.
+    pending: BTreeMap<u64, FrameOutput>,
+    input_done: bool,
+    /// The frame being emitted: a worker's output, or a streaming decoder
+    /// for one that was too large.
+    current: Box<dyn Read + Send>,
+}
+
+impl<R: Read> ParallelZstdReader<R> {
+    fn new(
+        reader: R,
+        read_ahead: Vec<Vec<u8>>,
+        workers: usize,
+        window_log_max: u32,
+        max_output: usize,
+    ) -> Self {
+        let (job_tx, job_rx) = bounded::<(u64, Vec<u8>)>(workers);
+        let (result_tx, result_rx) = unbounded::<FrameResult>();
+        // Workers exit once the job channel closes, i.e. when the reader
+        // is dropped; one mid-frame finishes that frame first.
+        for _ in 0..workers {
+            let job_rx = job_rx.clone();
+            let result_tx = result_tx.clone();
+            std::thread::spawn(move || {
+                while let Ok((seq, frame)) = job_rx.recv() {
+                    let out = decompress_frame(frame, window_log_max, max_output);
+                    if result_tx.send((seq, out)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Self {
+            reader,
+            read_ahead: read_ahead.into_iter(),
+            window_log_max,
+            job_tx,
+            result_rx,
+            max_in_flight: 2 * workers as u64,
+            next_dispatch: 0,
+            next_emit: 0,
+            pending: BTreeMap::new(),
+            input_done: false,
+            current: Box::new(io::empty()),
+        }
+    }
+
+    /// Read and queue frames until `max_in_flight` are outstanding.
+    fn dispatch(&mut self) -> io::Result<()> {
+        while !self.input_done && self.next_dispatch - self.next_emit < self.max_in_flight {
+            let frame = match self.read_ahead.next() {
+                Some(frame) => frame,
+                None => {
+                    let mut frame = Vec::new();
+                    match read_frame(&mut self.reader, &mut frame, MAX_FRAME)? {
+                        FrameRead::Frame => frame,
+                        FrameRead::Eof => {
+                            self.input_done = true;
+                            break;
+                        }
+                        FrameRead::TooLarge => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!(
+                                    "zstd frame {} is over {} MiB compressed",
+                                    self.next_dispatch,
+                                    MAX_FRAME >> 20
+                                ),
+                            ));
+                        }
+                    }
+                }
+            };
+            self.job_tx
+                .send((self.next_dispatch, frame))
+                .map_err(|_| io::Error::other("zstd worker exited"))?;
+            self.next_dispatch += 1;
+        }
+        Ok(())
+    }
+
+    /// Load the next frame's output into `current`. False at end of stream.
+    fn next_frame(&mut self) -> io::Result<bool> {
+        self.dispatch()?;
+        if self.next_emit == self.next_dispatch {
+            return Ok(false);
+        }
+        let out = loop {
+            if let Some(out) = self.pending.remove(&self.next_emit) {
+                break out;
+            }
+            let (seq, result) = self
+                .result_rx
+                .recv()
+                .map_err(|_| io::Error::other("zstd workers exited"))?;
+            self.pending.insert(seq, result?);
+        };
+        self.next_emit += 1;
+        self.current = match out {
+            FrameOutput::Data(out) => Box::new(Cursor::new(out)),
+            FrameOutput::TooLarge(frame) => {
+                Box::new(frame_decoder(Cursor::new(frame), self.window_log_max)?)
+            }
+        };
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for ParallelZstdReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            if !self.next_frame()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multi_frame_round_trip() {
+        let chunks: Vec<Vec<u8>> = (0..10u32)
+            .map(|i| {
+                (0..100_000 + i * 1_000)
+                    .map(|j| (j * 7 + i) as u8)
+                    .collect()
+            })
+            .collect();
+        let mut input = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            input.extend(zstd::encode_all(chunk.as_slice(), 3).unwrap());
+            if i == 4 {
+                // A skippable frame between two data frames.
+                input.extend(SKIPPABLE_MAGIC.to_le_bytes());
+                input.extend(3u32.to_le_bytes());
+                input.extend([1, 2, 3]);
+            }
+        }
+
+        let mut out = Vec::new();
+        open(input.as_slice(), DEFAULT_WINDOW_LOG_MAX)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, chunks.concat());
+    }
+
+    #[test]
+    fn frames_over_the_output_limit_are_streamed() {
+        let chunks: Vec<Vec<u8>> = (0..4u8)
+            .map(|i| vec![i; 10_000 * (i as usize + 1)])
+            .collect();
+        let frames: Vec<Vec<u8>> = chunks
+            .iter()
+            .map(|chunk| zstd::encode_all(chunk.as_slice(), 3).unwrap())
+            .collect();
+        // With and without the content size in the frame header.
+        let unsized_frame = {
+            let mut encoder = zstd::Encoder::new(Vec::new(), 3).unwrap();
+            encoder.include_contentsize(false).unwrap();
+            io::copy(&mut [9u8; 30_000].as_slice(), &mut encoder).unwrap();
+            encoder.finish().unwrap()
+        };
+        let rest = [frames[2].clone(), unsized_frame, frames[3].clone()].concat();
+
+        let mut reader = ParallelZstdReader::new(
+            rest.as_slice(),
+            frames[..2].to_vec(),
+            2,
+            DEFAULT_WINDOW_LOG_MAX,
+            25_000,
+        );
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        let expected = [
+            &chunks[0][..],
+            &chunks[1],
+            &chunks[2],
+            &[9; 30_000],
+            &chunks[3],
+        ]
+        .concat();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn single_frame_round_trip() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let input = zstd::encode_all(data.as_slice(), 3).unwrap();
+        let mut out = Vec::new();
+        open(input.as_slice(), DEFAULT_WINDOW_LOG_MAX)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn truncated_magic_is_an_error() {
+        let mut input = zstd::encode_all(&[1u8; 100][..], 3).unwrap();
+        input.extend(&ZSTD_MAGIC.to_le_bytes()[..2]);
+        let mut buf = Vec::new();
+        let mut reader = input.as_slice();
+        assert!(matches!(
+            read_frame(&mut reader, &mut buf, MAX_FRAME),
+            Ok(FrameRead::Frame)
+        ));
+        let err = read_frame(&mut reader, &mut buf, MAX_FRAME).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}